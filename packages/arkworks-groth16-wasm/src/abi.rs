@@ -0,0 +1,561 @@
+//! ABI input encoding and public input decoding.
+//!
+//! Flattens named Noir inputs into field elements following the circuit ABI,
+//! and places them on the parameter witnesses of the entry function. The
+//! reverse direction turns a proof's public inputs back into named values.
+
+use ark_bn254::Fr;
+use ark_ff::PrimeField;
+use num_bigint::{BigInt, BigUint, Sign};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::acir_to_r1cs::WitnessMap;
+use crate::acir_types::{AbiType, AbiVisibility, AcirProgram, CircuitAbi};
+use crate::error::ArkworksError;
+
+impl AbiType {
+    /// Number of field elements a value of this type flattens to
+    pub fn field_count(&self) -> usize {
+        match self {
+            AbiType::Field | AbiType::Boolean | AbiType::Integer { .. } => 1,
+            AbiType::String { length } => *length as usize,
+            AbiType::Array { length, typ } => *length as usize * typ.field_count(),
+            AbiType::Struct { fields, .. } => fields.iter().map(|(_, typ)| typ.field_count()).sum(),
+            AbiType::Tuple { fields } => fields.iter().map(AbiType::field_count).sum(),
+        }
+    }
+}
+
+/// Flatten named inputs into field elements, in ABI parameter order
+///
+/// `inputs` is a JSON object keyed by parameter name. Every parameter must
+/// be present and no other keys are accepted.
+pub fn flatten_inputs(abi: &CircuitAbi, inputs: &Value) -> Result<Vec<Fr>, ArkworksError> {
+    let inputs = inputs
+        .as_object()
+        .ok_or_else(|| ArkworksError::InvalidInput("Inputs must be a JSON object".to_string()))?;
+
+    if let Some(name) = inputs
+        .keys()
+        .find(|name| !abi.parameters.iter().any(|param| &param.name == *name))
+    {
+        return Err(ArkworksError::InvalidInput(format!(
+            "Unexpected input '{}'",
+            name
+        )));
+    }
+
+    let mut fields = Vec::new();
+    for param in &abi.parameters {
+        let value = inputs.get(&param.name).ok_or_else(|| {
+            ArkworksError::InvalidInput(format!("Missing input '{}'", param.name))
+        })?;
+        encode_value(&param.typ, value, &param.name, &mut fields)?;
+    }
+    Ok(fields)
+}
+
+/// Encode named inputs as the initial witness map of a program
+///
+/// Noir allocates the flattened inputs to the entry function's parameter
+/// witnesses in ascending order.
+pub fn encode_inputs(
+    abi: &CircuitAbi,
+    program: &AcirProgram,
+    inputs: &Value,
+) -> Result<WitnessMap, ArkworksError> {
+    let main = program
+        .functions
+        .first()
+        .ok_or_else(|| ArkworksError::ParseError("ACIR program has no functions".to_string()))?;
+
+    let mut parameters: Vec<u32> = main
+        .private_parameters
+        .iter()
+        .chain(&main.public_parameters.witnesses)
+        .copied()
+        .collect();
+    parameters.sort_unstable();
+    parameters.dedup();
+
+    let fields = flatten_inputs(abi, inputs)?;
+    if fields.len() != parameters.len() {
+        return Err(ArkworksError::InvalidInput(format!(
+            "ABI describes {} input fields but the circuit has {} parameter witnesses",
+            fields.len(),
+            parameters.len()
+        )));
+    }
+
+    Ok(parameters.into_iter().zip(fields).collect())
+}
+
+fn invalid(path: &str, reason: impl std::fmt::Display) -> ArkworksError {
+    ArkworksError::InvalidInput(format!("Input '{}' {}", path, reason))
+}
+
+/// Append the field elements of one value, `path` naming it for errors
+fn encode_value(
+    typ: &AbiType,
+    value: &Value,
+    path: &str,
+    out: &mut Vec<Fr>,
+) -> Result<(), ArkworksError> {
+    match typ {
+        AbiType::Field => {
+            let value = parse_integer(value, path)?;
+            let magnitude = value.magnitude();
+            if magnitude >= &BigUint::from(Fr::MODULUS) {
+                return Err(invalid(path, "does not fit in the field"));
+            }
+            let fr = Fr::from(magnitude.clone());
+            out.push(if value.sign() == Sign::Minus { -fr } else { fr });
+        }
+        AbiType::Boolean => {
+            let bit = match value {
+                Value::Bool(b) => *b,
+                Value::Number(n) if n.as_u64() == Some(0) => false,
+                Value::Number(n) if n.as_u64() == Some(1) => true,
+                _ => return Err(invalid(path, "must be a boolean")),
+            };
+            out.push(Fr::from(bit));
+        }
+        AbiType::Integer { sign, width } => {
+            let value = parse_integer(value, path)?;
+            let magnitude = value.magnitude();
+            let encoded = match (sign.as_str(), value.sign()) {
+                ("unsigned", Sign::Minus) => {
+                    return Err(invalid(path, format!("is negative but declared u{}", width)));
+                }
+                ("unsigned", _) if magnitude.bits() > *width as u64 => {
+                    return Err(invalid(path, format!("does not fit in u{}", width)));
+                }
+                ("unsigned", _) => magnitude.clone(),
+                ("signed", Sign::Minus) => {
+                    // |v| <= 2^(w-1), encoded in two's complement as 2^w - |v|
+                    if (magnitude - 1u32).bits() >= *width as u64 {
+                        return Err(invalid(path, format!("does not fit in i{}", width)));
+                    }
+                    (BigUint::from(1u32) << *width) - magnitude
+                }
+                ("signed", _) if magnitude.bits() >= *width as u64 => {
+                    return Err(invalid(path, format!("does not fit in i{}", width)));
+                }
+                ("signed", _) => magnitude.clone(),
+                (other, _) => {
+                    return Err(ArkworksError::InvalidInput(format!(
+                        "Unknown integer sign '{}' in ABI",
+                        other
+                    )));
+                }
+            };
+            out.push(Fr::from(encoded));
+        }
+        AbiType::String { length } => {
+            let s = value.as_str().ok_or_else(|| invalid(path, "must be a string"))?;
+            if s.len() != *length as usize {
+                return Err(invalid(
+                    path,
+                    format!("has {} bytes, expected {}", s.len(), length),
+                ));
+            }
+            out.extend(s.bytes().map(|b| Fr::from(b as u64)));
+        }
+        AbiType::Array { length, typ } => {
+            let items = value.as_array().ok_or_else(|| invalid(path, "must be an array"))?;
+            if items.len() != *length as usize {
+                return Err(invalid(
+                    path,
+                    format!("has {} elements, expected {}", items.len(), length),
+                ));
+            }
+            for (i, item) in items.iter().enumerate() {
+                encode_value(typ, item, &format!("{}[{}]", path, i), out)?;
+            }
+        }
+        AbiType::Struct { fields, .. } => {
+            let object = value.as_object().ok_or_else(|| invalid(path, "must be an object"))?;
+            if let Some(key) = object.keys().find(|key| !fields.iter().any(|(name, _)| &name == key)) {
+                return Err(invalid(path, format!("has unexpected field '{}'", key)));
+            }
+            for (name, typ) in fields {
+                let field_path = format!("{}.{}", path, name);
+                let field = object
+                    .get(name)
+                    .ok_or_else(|| invalid(&field_path, "is missing"))?;
+                encode_value(typ, field, &field_path, out)?;
+            }
+        }
+        AbiType::Tuple { fields } => {
+            let items = value.as_array().ok_or_else(|| invalid(path, "must be an array"))?;
+            if items.len() != fields.len() {
+                return Err(invalid(
+                    path,
+                    format!("has {} elements, expected {}", items.len(), fields.len()),
+                ));
+            }
+            for (i, (typ, item)) in fields.iter().zip(items).enumerate() {
+                encode_value(typ, item, &format!("{}.{}", path, i), out)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Parse a JSON number or a decimal/hex string, either possibly negative
+fn parse_integer(value: &Value, path: &str) -> Result<BigInt, ArkworksError> {
+    match value {
+        Value::Number(n) => n
+            .as_i64()
+            .map(BigInt::from)
+            .or_else(|| n.as_u64().map(BigInt::from))
+            .ok_or_else(|| invalid(path, format!("{} is not an integer", n))),
+        Value::String(s) => {
+            let (sign, digits) = match s.strip_prefix('-') {
+                Some(rest) => (Sign::Minus, rest),
+                None => (Sign::Plus, s.as_str()),
+            };
+            let magnitude = match digits.strip_prefix("0x") {
+                Some(hex) => BigUint::parse_bytes(hex.as_bytes(), 16),
+                None => BigUint::parse_bytes(digits.as_bytes(), 10),
+            }
+            .ok_or_else(|| invalid(path, format!("'{}' is not an integer", s)))?;
+            Ok(BigInt::from_biguint(sign, magnitude))
+        }
+        _ => Err(invalid(path, "must be a number or a numeric string")),
+    }
+}
+
+/// Public values of a proof, decoded through the ABI
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DecodedPublicInputs {
+    /// Public parameters by name
+    pub parameters: Map<String, Value>,
+    /// Return value of the circuit, if it has one
+    pub return_value: Option<Value>,
+}
+
+/// Decode public inputs into named values
+///
+/// `public_inputs` holds the flattened public parameters in ABI order,
+/// followed by the return value. Fields decode to 0x-prefixed hex strings,
+/// integers up to 32 bits to JSON numbers and wider integers to decimal
+/// strings, so that no value loses precision in JavaScript.
+pub fn decode_public_inputs(
+    abi: &CircuitAbi,
+    public_inputs: &[Fr],
+) -> Result<DecodedPublicInputs, ArkworksError> {
+    let public_parameters: Vec<_> = abi
+        .parameters
+        .iter()
+        .filter(|param| param.visibility == AbiVisibility::Public)
+        .collect();
+    let expected = public_parameters.iter().map(|param| param.typ.field_count()).sum::<usize>()
+        + abi.return_type.as_ref().map_or(0, |ret| ret.abi_type.field_count());
+    if public_inputs.len() != expected {
+        return Err(ArkworksError::InvalidInput(format!(
+            "ABI describes {} public fields but {} public inputs were given",
+            expected,
+            public_inputs.len()
+        )));
+    }
+
+    let mut fields = public_inputs.iter();
+    let mut parameters = Map::new();
+    for param in public_parameters {
+        let value = decode_value(&param.typ, &mut fields, &param.name)?;
+        parameters.insert(param.name.clone(), value);
+    }
+    let return_value = abi
+        .return_type
+        .as_ref()
+        .map(|ret| decode_value(&ret.abi_type, &mut fields, "return"))
+        .transpose()?;
+
+    Ok(DecodedPublicInputs {
+        parameters,
+        return_value,
+    })
+}
+
+/// Decode one value from the front of `fields`, `path` naming it for errors
+fn decode_value(
+    typ: &AbiType,
+    fields: &mut std::slice::Iter<Fr>,
+    path: &str,
+) -> Result<Value, ArkworksError> {
+    let mut next = |path: &str| {
+        fields
+            .next()
+            .map(|fr| BigUint::from(*fr))
+            .ok_or_else(|| invalid(path, "is missing from the public inputs"))
+    };
+
+    Ok(match typ {
+        AbiType::Field => {
+            let value = next(path)?;
+            Value::String(format!("0x{:064x}", value))
+        }
+        AbiType::Boolean => match u8::try_from(next(path)?) {
+            Ok(0) => Value::Bool(false),
+            Ok(1) => Value::Bool(true),
+            _ => return Err(invalid(path, "is not a boolean")),
+        },
+        AbiType::Integer { sign, width } => {
+            let value = next(path)?;
+            if value.bits() > *width as u64 {
+                return Err(invalid(path, format!("does not fit in {} bits", width)));
+            }
+            let value = match sign.as_str() {
+                // Two's complement: values from 2^(w-1) up are negative
+                "signed" if *width > 0 && value.bit(*width as u64 - 1) => {
+                    BigInt::from(value) - (BigInt::from(1u32) << *width)
+                }
+                "signed" | "unsigned" => BigInt::from(value),
+                other => {
+                    return Err(ArkworksError::InvalidInput(format!(
+                        "Unknown integer sign '{}' in ABI",
+                        other
+                    )));
+                }
+            };
+            match i64::try_from(&value) {
+                Ok(small) if *width <= 32 => Value::from(small),
+                _ => Value::String(value.to_string()),
+            }
+        }
+        AbiType::String { length } => {
+            let bytes = (0..*length)
+                .map(|_| u8::try_from(next(path)?).map_err(|_| invalid(path, "is not a byte string")))
+                .collect::<Result<Vec<u8>, _>>()?;
+            let s = String::from_utf8(bytes).map_err(|_| invalid(path, "is not valid UTF-8"))?;
+            Value::String(s)
+        }
+        AbiType::Array { length, typ } => Value::Array(
+            (0..*length)
+                .map(|i| decode_value(typ, fields, &format!("{}[{}]", path, i)))
+                .collect::<Result<_, _>>()?,
+        ),
+        AbiType::Struct { fields: members, .. } => {
+            let mut object = Map::new();
+            for (name, typ) in members {
+                let value = decode_value(typ, fields, &format!("{}.{}", path, name))?;
+                object.insert(name.clone(), value);
+            }
+            Value::Object(object)
+        }
+        AbiType::Tuple { fields: members } => Value::Array(
+            members
+                .iter()
+                .enumerate()
+                .map(|(i, typ)| decode_value(typ, fields, &format!("{}.{}", path, i)))
+                .collect::<Result<_, _>>()?,
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn abi() -> CircuitAbi {
+        // Parameters as Noir serializes them, struct fields included
+        serde_json::from_value(json!({
+            "parameters": [
+                { "name": "age", "type": { "kind": "integer", "sign": "unsigned", "width": 8 }, "visibility": "public" },
+                { "name": "delta", "type": { "kind": "integer", "sign": "signed", "width": 8 }, "visibility": "private" },
+                {
+                    "name": "user",
+                    "type": {
+                        "kind": "struct", "path": "User",
+                        "fields": [
+                            { "name": "id", "type": { "kind": "field" } },
+                            { "name": "active", "type": { "kind": "boolean" } },
+                            { "name": "balances", "type": { "kind": "array", "length": 2, "type": { "kind": "field" } } }
+                        ]
+                    },
+                    "visibility": "private"
+                },
+                { "name": "tag", "type": { "kind": "string", "length": 2 }, "visibility": "private" },
+                {
+                    "name": "pair",
+                    "type": { "kind": "tuple", "fields": [{ "kind": "field" }, { "kind": "boolean" }] },
+                    "visibility": "private"
+                }
+            ],
+            "return_type": { "abi_type": { "kind": "field" }, "visibility": "public" },
+            "error_types": {}
+        }))
+        .unwrap()
+    }
+
+    fn inputs() -> Value {
+        json!({
+            "age": 21,
+            "delta": "-3",
+            "user": { "id": "0x10", "active": true, "balances": [5, "-1"] },
+            "tag": "hi",
+            "pair": ["7", false]
+        })
+    }
+
+    #[test]
+    fn test_flatten_inputs() {
+        let abi = abi();
+        assert_eq!(abi.parameters.iter().map(|p| p.typ.field_count()).sum::<usize>(), 10);
+
+        let fields = flatten_inputs(&abi, &inputs()).unwrap();
+        let expected = vec![
+            Fr::from(21u64),
+            Fr::from(253u64), // -3 as i8
+            Fr::from(16u64),
+            Fr::from(1u64),
+            Fr::from(5u64),
+            -Fr::from(1u64),
+            Fr::from(b'h' as u64),
+            Fr::from(b'i' as u64),
+            Fr::from(7u64),
+            Fr::from(0u64),
+        ];
+        assert_eq!(fields, expected);
+    }
+
+    #[test]
+    fn test_encode_inputs_uses_parameter_witnesses() {
+        let program: AcirProgram = serde_json::from_value(json!({
+            "functions": [{
+                "current_witness_index": 12,
+                "opcodes": [],
+                "private_parameters": [3, 4, 5, 6, 7, 8, 9, 10, 11],
+                "public_parameters": { "witnesses": [2] },
+                "return_values": { "witnesses": [12] }
+            }]
+        }))
+        .unwrap();
+
+        let witness = encode_inputs(&abi(), &program, &inputs()).unwrap();
+        assert_eq!(witness.len(), 10);
+        assert_eq!(witness[&2], Fr::from(21u64));
+        assert_eq!(witness[&10], Fr::from(7u64));
+        assert_eq!(witness[&11], Fr::from(0u64));
+    }
+
+    #[test]
+    fn test_rejects_out_of_range_integers() {
+        let abi = abi();
+        let cases = [
+            ("age", json!(256)),
+            ("age", json!(-1)),
+            ("delta", json!(128)),
+            ("delta", json!("-129")),
+            ("age", json!(1.5)),
+        ];
+        for (name, value) in cases {
+            let mut inputs = inputs();
+            inputs[name] = value;
+            assert!(
+                matches!(flatten_inputs(&abi, &inputs), Err(ArkworksError::InvalidInput(_))),
+                "{} = {} should be rejected",
+                name,
+                inputs[name]
+            );
+        }
+
+        // The bounds themselves are accepted
+        let mut inputs = inputs();
+        inputs["age"] = json!(255);
+        inputs["delta"] = json!(-128);
+        assert!(flatten_inputs(&abi, &inputs).is_ok());
+    }
+
+    #[test]
+    fn test_rejects_malformed_inputs() {
+        let abi = abi();
+
+        let mut missing = inputs();
+        missing["user"].as_object_mut().unwrap().remove("active");
+        let err = flatten_inputs(&abi, &missing).unwrap_err();
+        assert!(err.to_string().contains("'user.active'"));
+
+        let mut extra = inputs();
+        extra["nonce"] = json!(1);
+        assert!(flatten_inputs(&abi, &extra).is_err());
+
+        let mut short = inputs();
+        short["user"]["balances"] = json!([1]);
+        let err = flatten_inputs(&abi, &short).unwrap_err();
+        assert!(err.to_string().contains("'user.balances'"));
+
+        let mut wrong_tag = inputs();
+        wrong_tag["tag"] = json!("hey");
+        assert!(flatten_inputs(&abi, &wrong_tag).is_err());
+    }
+
+    #[test]
+    fn test_decode_public_inputs() {
+        let abi: CircuitAbi = serde_json::from_value(json!({
+            "parameters": [
+                { "name": "secret", "type": { "kind": "field" }, "visibility": "private" },
+                { "name": "delta", "type": { "kind": "integer", "sign": "signed", "width": 8 }, "visibility": "public" },
+                {
+                    "name": "user",
+                    "type": {
+                        "kind": "struct", "path": "User",
+                        "fields": [
+                            { "name": "id", "type": { "kind": "field" } },
+                            { "name": "balance", "type": { "kind": "integer", "sign": "unsigned", "width": 64 } }
+                        ]
+                    },
+                    "visibility": "public"
+                }
+            ],
+            "return_type": {
+                "abi_type": {
+                    "kind": "tuple",
+                    "fields": [{ "kind": "boolean" }, { "kind": "string", "length": 2 }, { "kind": "array", "length": 2, "type": { "kind": "integer", "sign": "signed", "width": 64 } }]
+                },
+                "visibility": "public"
+            },
+            "error_types": {}
+        }))
+        .unwrap();
+
+        let public_inputs = vec![
+            Fr::from(253u64), // -3 as i8
+            Fr::from(16u64),
+            Fr::from(u64::MAX),
+            Fr::from(1u64),
+            Fr::from(b'o' as u64),
+            Fr::from(b'k' as u64),
+            Fr::from(u64::MAX), // -1 as i64
+            Fr::from(5u64),
+        ];
+        let decoded = decode_public_inputs(&abi, &public_inputs).unwrap();
+        assert_eq!(
+            serde_json::to_value(&decoded).unwrap(),
+            json!({
+                "parameters": {
+                    "delta": -3,
+                    "user": {
+                        "id": format!("0x{:064x}", 16),
+                        "balance": u64::MAX.to_string()
+                    }
+                },
+                "return_value": [true, "ok", ["-1", "5"]]
+            })
+        );
+
+        // Values are checked against their declared types
+        let mut not_bool = public_inputs.clone();
+        not_bool[3] = Fr::from(2u64);
+        assert!(decode_public_inputs(&abi, &not_bool).is_err());
+
+        let mut too_wide = public_inputs.clone();
+        too_wide[0] = Fr::from(256u64);
+        assert!(decode_public_inputs(&abi, &too_wide).is_err());
+
+        assert!(decode_public_inputs(&abi, &public_inputs[..7]).is_err());
+    }
+}
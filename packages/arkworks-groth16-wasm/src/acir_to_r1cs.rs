@@ -4,20 +4,27 @@
 //! to R1CS (Rank-1 Constraint System) for Groth16 proving with arkworks.
 
 use ark_bn254::Fr;
-use ark_ff::PrimeField;
+use ark_ff::{AdditiveGroup, BigInteger, Field, One, PrimeField, Zero};
 use ark_relations::r1cs::{
     ConstraintSynthesizer, ConstraintSystemRef, LinearCombination, SynthesisError, Variable,
 };
-use std::collections::HashMap;
+use num_bigint::{BigInt, BigUint};
+use std::collections::{BTreeMap, HashMap};
 
-use crate::acir_types::{AcirCircuit, AcirProgram, Expression, Opcode};
+use crate::acir_types::{
+    AcirCall, AcirCircuit, AcirProgram, Expression, MemoryInit, MemoryOp, Opcode,
+};
 use crate::error::ArkworksError;
+use crate::gadgets::bigint::{self, BigIntVar};
+use crate::gadgets::{
+    blake2s, blake3, ecdsa, embedded_curve, keccak, nonnative, pedersen, poseidon2, schnorr, sha256,
+};
 
 /// Witness values for circuit execution
 pub type WitnessMap = HashMap<u32, Fr>;
 
 /// R1CS circuit converted from ACIR
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct AcirR1cs {
     /// Number of witnesses (including w_0 = 1)
     pub num_witnesses: usize,
@@ -29,17 +36,477 @@ pub struct AcirR1cs {
     pub return_values: Vec<u32>,
     /// R1CS constraints: (A, B, C) where A * B = C
     pub constraints: Vec<R1csConstraint>,
+    /// Auxiliary witnesses introduced by the converter, in evaluation order
+    pub aux_witnesses: Vec<AuxWitnessComputation>,
+    /// ACIR calls inlined into this circuit, in execution order
+    pub calls: Vec<InlinedCall>,
+    /// Constraints added by black box gadgets, by black box name
+    pub black_box_constraints: BTreeMap<String, usize>,
+    /// ACIR opcode each constraint was converted from, in constraint order;
+    /// empty when the constraints did not come from ACIR
+    pub origins: Vec<OpcodeOrigin>,
+    /// Assertion messages of the converted opcodes that have one
+    pub assert_messages: BTreeMap<OpcodeOrigin, String>,
+    /// Original index of each witness, by new index, once the optimizer has
+    /// renumbered the constraints
+    ///
+    /// Caller-supplied witnesses, calls and auxiliary computations keep the
+    /// original numbering; `assign_witness` moves their values over.
+    pub renumbering: Option<Vec<u32>>,
+}
+
+/// ACIR opcode a constraint was converted from
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct OpcodeOrigin {
+    /// Index of the function in `AcirProgram.functions`
+    pub function: u32,
+    /// Index of the opcode within that function
+    pub opcode: usize,
+}
+
+/// An ACIR `Call` whose callee was inlined into the caller's R1CS
+#[derive(Clone, Debug)]
+pub struct InlinedCall {
+    /// Index of the callee in `AcirProgram.functions`
+    pub function_id: u32,
+    /// Callee ACIR witness index -> R1CS witness index
+    pub witnesses: HashMap<u32, u32>,
+}
+
+impl AcirR1cs {
+    /// Place witness values solved for an inlined call into the R1CS witness map
+    ///
+    /// `call` indexes `self.calls`, and `callee_witness` is keyed by the
+    /// callee's own ACIR witness indices.
+    pub fn insert_call_witnesses(
+        &self,
+        call: usize,
+        callee_witness: &WitnessMap,
+        witness: &mut WitnessMap,
+    ) -> Result<(), ArkworksError> {
+        let frame = self.calls.get(call).ok_or_else(|| {
+            ArkworksError::InvalidInput(format!("No inlined call with index {}", call))
+        })?;
+        for (callee_idx, value) in callee_witness {
+            if let Some(&idx) = frame.witnesses.get(callee_idx) {
+                witness.entry(idx).or_insert(*value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Witnesses of the public statement: public inputs, then return values
+    ///
+    /// This is Noir's ordering of public parameters followed by returns. A
+    /// witness appears twice when a public input is also returned.
+    pub fn public_witnesses(&self) -> Vec<u32> {
+        self.public_inputs
+            .iter()
+            .chain(&self.return_values)
+            .copied()
+            .collect()
+    }
+
+    /// Values of the public statement, in `public_witnesses` order
+    pub fn public_values(&self, witness: &WitnessMap) -> Result<Vec<Fr>, ArkworksError> {
+        self.public_witnesses()
+            .into_iter()
+            .map(|idx| witness_value(witness, idx))
+            .collect()
+    }
+
+    /// Compute auxiliary witnesses from the caller-supplied witness values
+    ///
+    /// The witness map from noir_js only covers ACIR witnesses, so every
+    /// witness the converter allocates on top of those is derived here.
+    pub fn compute_aux_witnesses(&self, witness: &mut WitnessMap) -> Result<(), ArkworksError> {
+        for computation in &self.aux_witnesses {
+            computation.compute(witness)?;
+        }
+        Ok(())
+    }
+
+    /// Compute auxiliary witnesses, then number the values like the constraints
+    pub fn assign_witness(&self, mut witness: WitnessMap) -> Result<WitnessMap, ArkworksError> {
+        self.compute_aux_witnesses(&mut witness)?;
+        match &self.renumbering {
+            None => Ok(witness),
+            Some(original) => (1..original.len())
+                .map(|idx| Ok((idx as u32, witness_value(&witness, original[idx])?)))
+                .collect(),
+        }
+    }
 }
 
 /// Single R1CS constraint: A * B = C
 /// Each component is a linear combination of (coefficient, witness_index)
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct R1csConstraint {
     pub a: Vec<(Fr, u32)>,
     pub b: Vec<(Fr, u32)>,
     pub c: Vec<(Fr, u32)>,
 }
 
+impl R1csConstraint {
+    /// Rewrite every witness index through `map`
+    pub(crate) fn relocate(&self, map: &HashMap<u32, u32>) -> Self {
+        Self {
+            a: relocate_lc(&self.a, map),
+            b: relocate_lc(&self.b, map),
+            c: relocate_lc(&self.c, map),
+        }
+    }
+
+    /// Evaluate A, B and C for the given witness values
+    pub fn evaluate(&self, witness: &WitnessMap) -> Result<(Fr, Fr, Fr), ArkworksError> {
+        Ok((
+            evaluate_lc(&self.a, witness)?,
+            evaluate_lc(&self.b, witness)?,
+            evaluate_lc(&self.c, witness)?,
+        ))
+    }
+
+    /// Check whether A * B = C holds for the given witness values
+    pub fn is_satisfied(&self, witness: &WitnessMap) -> Result<bool, ArkworksError> {
+        let (a, b, c) = self.evaluate(witness)?;
+        Ok(a * b == c)
+    }
+}
+
+/// Instruction for computing an auxiliary witness added by the converter
+#[derive(Clone, Debug)]
+pub enum AuxWitnessComputation {
+    /// target = lhs * rhs, only when the caller did not supply it
+    Product {
+        target: u32,
+        lhs: Vec<(Fr, u32)>,
+        rhs: Vec<(Fr, u32)>,
+    },
+    /// bits = little-endian binary decomposition of source
+    BitDecomposition { source: u32, bits: Vec<u32> },
+    /// target = lhs * rhs + linear, only when the caller did not supply it
+    Quadratic {
+        target: u32,
+        lhs: Vec<(Fr, u32)>,
+        rhs: Vec<(Fr, u32)>,
+        linear: Vec<(Fr, u32)>,
+    },
+    /// target = numerator / denominator (0 when the denominator is 0), only
+    /// when the caller did not supply it
+    Quotient {
+        target: u32,
+        numerator: Vec<(Fr, u32)>,
+        denominator: Vec<(Fr, u32)>,
+    },
+    /// target = sum(coeff * witness), only when the caller did not supply it
+    ///
+    /// Used for ACIR outputs such as AND/XOR results: a wrong caller value
+    /// is kept so that it fails its constraint instead of being corrected.
+    Linear { target: u32, terms: Vec<(Fr, u32)> },
+    /// selectors[j] = 1 if index == j, else 0
+    OneHot { index: Vec<(Fr, u32)>, selectors: Vec<u32> },
+    /// target = cells[index], only when the caller did not supply it
+    ArrayRead { target: u32, index: Vec<(Fr, u32)>, cells: Vec<u32> },
+    /// updated = previous with the cell at index replaced by value
+    ArrayWrite {
+        index: Vec<(Fr, u32)>,
+        value: Vec<(Fr, u32)>,
+        previous: Vec<u32>,
+        updated: Vec<u32>,
+    },
+    /// limbs = 86-bit limbs of (numerator / denominator) mod modulus, or of 0
+    /// when the denominator is not invertible
+    ///
+    /// Numerator and denominator are integers sum(2^(86 i) * c_i) of columns
+    /// read as signed values.
+    NonNativeResidue {
+        numerator: Vec<Vec<(Fr, u32)>>,
+        denominator: Vec<Vec<(Fr, u32)>>,
+        modulus: BigUint,
+        limbs: Vec<u32>,
+    },
+    /// limbs = limbs of value / modulus + offset for an integer value given
+    /// as columns, the last limb taking the high bits
+    NonNativeQuotient {
+        value: Vec<Vec<(Fr, u32)>>,
+        modulus: BigUint,
+        offset: BigUint,
+        limbs: Vec<u32>,
+    },
+    /// bytes = little-endian bytes of a non-negative integer given as
+    /// columns, only when the caller did not supply them
+    IntegerBytes {
+        value: Vec<Vec<(Fr, u32)>>,
+        bytes: Vec<u32>,
+    },
+    /// Run computations only when predicate is non-zero; otherwise zero the witnesses
+    ///
+    /// Used for calls under a false predicate, which ACVM never executes.
+    Predicated {
+        predicate: Vec<(Fr, u32)>,
+        witnesses: Vec<u32>,
+        computations: Vec<AuxWitnessComputation>,
+    },
+}
+
+impl AuxWitnessComputation {
+    /// Compute the target witness value and store it in the witness map
+    pub fn compute(&self, witness: &mut WitnessMap) -> Result<(), ArkworksError> {
+        match self {
+            AuxWitnessComputation::Product { target, lhs, rhs } => {
+                if !witness.contains_key(target) {
+                    let value = evaluate_lc(lhs, witness)? * evaluate_lc(rhs, witness)?;
+                    witness.insert(*target, value);
+                }
+            }
+            AuxWitnessComputation::BitDecomposition { source, bits } => {
+                let value = witness_value(witness, *source)?.into_bigint();
+                if value.num_bits() as usize > bits.len() {
+                    return Err(ArkworksError::InvalidInput(format!(
+                        "Witness {} does not fit in {} bits",
+                        source,
+                        bits.len()
+                    )));
+                }
+                for (i, &bit) in bits.iter().enumerate() {
+                    witness.insert(bit, Fr::from(value.get_bit(i) as u64));
+                }
+            }
+            AuxWitnessComputation::Quadratic { target, lhs, rhs, linear } => {
+                if !witness.contains_key(target) {
+                    let value = evaluate_lc(lhs, witness)? * evaluate_lc(rhs, witness)?
+                        + evaluate_lc(linear, witness)?;
+                    witness.insert(*target, value);
+                }
+            }
+            AuxWitnessComputation::Quotient { target, numerator, denominator } => {
+                if !witness.contains_key(target) {
+                    let denominator = evaluate_lc(denominator, witness)?;
+                    let value = match denominator.inverse() {
+                        Some(inverse) => evaluate_lc(numerator, witness)? * inverse,
+                        None => Fr::zero(),
+                    };
+                    witness.insert(*target, value);
+                }
+            }
+            AuxWitnessComputation::Linear { target, terms } => {
+                if !witness.contains_key(target) {
+                    let value = evaluate_lc(terms, witness)?;
+                    witness.insert(*target, value);
+                }
+            }
+            AuxWitnessComputation::OneHot { index, selectors } => {
+                let position = memory_index(index, selectors.len(), witness)?;
+                for (j, &selector) in selectors.iter().enumerate() {
+                    witness.insert(selector, Fr::from((j == position) as u64));
+                }
+            }
+            AuxWitnessComputation::ArrayRead { target, index, cells } => {
+                if !witness.contains_key(target) {
+                    let position = memory_index(index, cells.len(), witness)?;
+                    let value = witness_value(witness, cells[position])?;
+                    witness.insert(*target, value);
+                }
+            }
+            AuxWitnessComputation::ArrayWrite { index, value, previous, updated } => {
+                let position = memory_index(index, previous.len(), witness)?;
+                let value = evaluate_lc(value, witness)?;
+                for (j, (&old, &new)) in previous.iter().zip(updated).enumerate() {
+                    let cell = if j == position { value } else { witness_value(witness, old)? };
+                    witness.insert(new, cell);
+                }
+            }
+            AuxWitnessComputation::NonNativeResidue { numerator, denominator, modulus, limbs } => {
+                let numerator = evaluate_integer(numerator, witness)?;
+                let denominator = evaluate_integer(denominator, witness)?;
+                let value = match nonnative::inverse(&nonnative::modulo(&denominator, modulus), modulus) {
+                    Some(inverse) => nonnative::modulo(&numerator, modulus) * inverse % modulus,
+                    None => BigUint::zero(),
+                };
+                let values = nonnative::limb_values(&value, limbs.len());
+                witness.extend(limbs.iter().copied().zip(values));
+            }
+            AuxWitnessComputation::NonNativeQuotient { value, modulus, offset, limbs } => {
+                let value = evaluate_integer(value, witness)?;
+                let residue = BigInt::from(nonnative::modulo(&value, modulus));
+                let quotient = (value - residue) / BigInt::from(modulus.clone());
+                let quotient = (quotient + BigInt::from(offset.clone())).to_biguint().ok_or_else(|| {
+                    ArkworksError::InvalidInput("Non-native quotient out of range".to_string())
+                })?;
+                let values = nonnative::limb_values(&quotient, limbs.len());
+                witness.extend(limbs.iter().copied().zip(values));
+            }
+            AuxWitnessComputation::IntegerBytes { value, bytes } => {
+                let value = evaluate_integer(value, witness)?.to_biguint().ok_or_else(|| {
+                    ArkworksError::InvalidInput("Negative integer has no bytes".to_string())
+                })?;
+                let mut values = value.to_bytes_le();
+                values.resize(bytes.len().max(values.len()), 0);
+                for (&idx, &byte) in bytes.iter().zip(&values) {
+                    witness.entry(idx).or_insert(Fr::from(byte));
+                }
+            }
+            AuxWitnessComputation::Predicated { predicate, witnesses, computations } => {
+                if evaluate_lc(predicate, witness)?.is_zero() {
+                    for &idx in witnesses {
+                        witness.entry(idx).or_insert(Fr::zero());
+                    }
+                } else {
+                    for computation in computations {
+                        computation.compute(witness)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Rewrite every witness index through `map`
+    fn relocate(&self, map: &HashMap<u32, u32>) -> Self {
+        let idx = |i: &u32| map.get(i).copied().unwrap_or(*i);
+        let all = |v: &[u32]| v.iter().map(idx).collect::<Vec<_>>();
+        match self {
+            AuxWitnessComputation::Product { target, lhs, rhs } => AuxWitnessComputation::Product {
+                target: idx(target),
+                lhs: relocate_lc(lhs, map),
+                rhs: relocate_lc(rhs, map),
+            },
+            AuxWitnessComputation::BitDecomposition { source, bits } => {
+                AuxWitnessComputation::BitDecomposition { source: idx(source), bits: all(bits) }
+            }
+            AuxWitnessComputation::Quadratic { target, lhs, rhs, linear } => {
+                AuxWitnessComputation::Quadratic {
+                    target: idx(target),
+                    lhs: relocate_lc(lhs, map),
+                    rhs: relocate_lc(rhs, map),
+                    linear: relocate_lc(linear, map),
+                }
+            }
+            AuxWitnessComputation::Quotient { target, numerator, denominator } => {
+                AuxWitnessComputation::Quotient {
+                    target: idx(target),
+                    numerator: relocate_lc(numerator, map),
+                    denominator: relocate_lc(denominator, map),
+                }
+            }
+            AuxWitnessComputation::Linear { target, terms } => AuxWitnessComputation::Linear {
+                target: idx(target),
+                terms: relocate_lc(terms, map),
+            },
+            AuxWitnessComputation::OneHot { index, selectors } => AuxWitnessComputation::OneHot {
+                index: relocate_lc(index, map),
+                selectors: all(selectors),
+            },
+            AuxWitnessComputation::ArrayRead { target, index, cells } => {
+                AuxWitnessComputation::ArrayRead {
+                    target: idx(target),
+                    index: relocate_lc(index, map),
+                    cells: all(cells),
+                }
+            }
+            AuxWitnessComputation::ArrayWrite { index, value, previous, updated } => {
+                AuxWitnessComputation::ArrayWrite {
+                    index: relocate_lc(index, map),
+                    value: relocate_lc(value, map),
+                    previous: all(previous),
+                    updated: all(updated),
+                }
+            }
+            AuxWitnessComputation::NonNativeResidue { numerator, denominator, modulus, limbs } => {
+                AuxWitnessComputation::NonNativeResidue {
+                    numerator: numerator.iter().map(|c| relocate_lc(c, map)).collect(),
+                    denominator: denominator.iter().map(|c| relocate_lc(c, map)).collect(),
+                    modulus: modulus.clone(),
+                    limbs: all(limbs),
+                }
+            }
+            AuxWitnessComputation::NonNativeQuotient { value, modulus, offset, limbs } => {
+                AuxWitnessComputation::NonNativeQuotient {
+                    value: value.iter().map(|c| relocate_lc(c, map)).collect(),
+                    modulus: modulus.clone(),
+                    offset: offset.clone(),
+                    limbs: all(limbs),
+                }
+            }
+            AuxWitnessComputation::IntegerBytes { value, bytes } => {
+                AuxWitnessComputation::IntegerBytes {
+                    value: value.iter().map(|c| relocate_lc(c, map)).collect(),
+                    bytes: all(bytes),
+                }
+            }
+            AuxWitnessComputation::Predicated { predicate, witnesses, computations } => {
+                AuxWitnessComputation::Predicated {
+                    predicate: relocate_lc(predicate, map),
+                    witnesses: all(witnesses),
+                    computations: computations.iter().map(|c| c.relocate(map)).collect(),
+                }
+            }
+        }
+    }
+}
+
+/// Rewrite the witness indices of a linear combination through `map`
+fn relocate_lc(terms: &[(Fr, u32)], map: &HashMap<u32, u32>) -> Vec<(Fr, u32)> {
+    terms
+        .iter()
+        .map(|&(coeff, idx)| (coeff, map.get(&idx).copied().unwrap_or(idx)))
+        .collect()
+}
+
+/// Evaluate an ACIR expression against the witness values
+pub fn evaluate_expression(expr: &Expression, witness: &WitnessMap) -> Result<Fr, ArkworksError> {
+    let mut value = parse_field_element(&expr.q_c)?;
+    for (coeff, idx) in &expr.linear_combinations {
+        value += parse_field_element(coeff)? * witness_value(witness, *idx)?;
+    }
+    for (coeff, a, b) in &expr.mul_terms {
+        value += parse_field_element(coeff)? * witness_value(witness, *a)? * witness_value(witness, *b)?;
+    }
+    Ok(value)
+}
+
+/// Look up a witness value, treating w_0 as the constant 1
+fn witness_value(witness: &WitnessMap, idx: u32) -> Result<Fr, ArkworksError> {
+    if idx == 0 {
+        return Ok(Fr::one());
+    }
+    witness
+        .get(&idx)
+        .copied()
+        .ok_or(ArkworksError::MissingWitness(idx))
+}
+
+/// Evaluate a memory index and check it against the block size
+fn memory_index(index: &[(Fr, u32)], size: usize, witness: &WitnessMap) -> Result<usize, ArkworksError> {
+    let value = evaluate_lc(index, witness)?;
+    let position = value.into_bigint().as_ref()[0] as usize;
+    if value.into_bigint().num_bits() > 64 || position >= size {
+        return Err(ArkworksError::InvalidInput(format!(
+            "Memory index {} out of bounds for block of size {}",
+            value, size
+        )));
+    }
+    Ok(position)
+}
+
+/// Evaluate a linear combination of (coefficient, witness_index) terms
+fn evaluate_lc(terms: &[(Fr, u32)], witness: &WitnessMap) -> Result<Fr, ArkworksError> {
+    let mut sum = Fr::zero();
+    for &(coeff, idx) in terms {
+        sum += coeff * witness_value(witness, idx)?;
+    }
+    Ok(sum)
+}
+
+/// Evaluate an integer sum(2^(86 i) * c_i) of columns read as signed values
+fn evaluate_integer(columns: &[Vec<(Fr, u32)>], witness: &WitnessMap) -> Result<BigInt, ArkworksError> {
+    let mut value = BigInt::zero();
+    for column in columns.iter().rev() {
+        value = (value << nonnative::LIMB_BITS) + nonnative::signed(evaluate_lc(column, witness)?);
+    }
+    Ok(value)
+}
+
 /// Circuit synthesizer for arkworks Groth16
 pub struct AcirCircuitSynthesizer {
     pub r1cs: AcirR1cs,
@@ -60,11 +527,23 @@ impl ConstraintSynthesizer<Fr> for AcirCircuitSynthesizer {
         // w_0 is always 1 (constant one)
         variables.insert(0, Variable::One);
 
-        // Allocate public inputs first (required by Groth16)
-        for &idx in &self.r1cs.public_inputs {
-            let value = self.witness.as_ref().and_then(|w| w.get(&idx).copied());
+        // Allocate the public statement first (required by Groth16):
+        // public inputs, then return values
+        for idx in self.r1cs.public_witnesses() {
+            let value = self.witness.as_ref().and_then(|w| witness_value(w, idx).ok());
             let var = cs.new_input_variable(|| value.ok_or(SynthesisError::AssignmentMissing))?;
-            variables.insert(idx, var);
+            match variables.get(&idx) {
+                // A repeated witness gets its own input, bound to the first
+                Some(&existing) => {
+                    let mut lc = LinearCombination::zero();
+                    lc += (Fr::one(), var);
+                    lc += (-Fr::one(), existing);
+                    cs.enforce_constraint(lc, LinearCombination::from(Variable::One), LinearCombination::zero())?;
+                }
+                None => {
+                    variables.insert(idx, var);
+                }
+            }
         }
 
         // Allocate all other witnesses as private
@@ -95,103 +574,758 @@ fn build_lc(terms: &[(Fr, u32)], variables: &HashMap<u32, Variable>) -> LinearCo
     let mut lc = LinearCombination::zero();
     for &(coeff, idx) in terms {
         if let Some(&var) = variables.get(&idx) {
-            lc = lc + (coeff, var);
+            lc += (coeff, var);
         }
     }
     lc
 }
 
-/// Parse a hex field element string to Fr
+/// Parse a field element from a `0x`-prefixed hex or a decimal string,
+/// optionally negated with a leading `-`; surrounding whitespace is ignored
+///
+/// Strings without the `0x` prefix are decimal: `"10"` is ten, not
+/// sixteen as in earlier releases, and hex digits without the prefix are
+/// an error.
+///
+/// The magnitude must be canonical, i.e. less than the field modulus: a
+/// value that only fits after reduction is rejected rather than silently
+/// turned into a different element.
 pub fn parse_field_element(s: &str) -> Result<Fr, ArkworksError> {
+    let invalid =
+        |reason: String| ArkworksError::ParseError(format!("Invalid field element '{}': {}", s, reason));
+
     let s = s.trim();
-    if s.is_empty() || s == "0" || s == "0x0" || s == "0x00" {
-        return Ok(Fr::from(0u64));
+    let (negative, unsigned) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s),
+    };
+    // Digits of the widest canonical value, r - 1, in each radix
+    let (radix, digits, max_digits, kind) = match unsigned.strip_prefix("0x") {
+        Some(hex) => (16, hex, 64, "hex"),
+        None => (10, unsigned, 77, "decimal"),
+    };
+
+    if digits.is_empty() {
+        return Err(invalid("no digits".to_string()));
+    }
+    if let Some(c) = digits.chars().find(|c| !c.is_digit(radix)) {
+        let hint = if radix == 10 && c.is_ascii_hexdigit() {
+            " (hex values need a 0x prefix)"
+        } else {
+            ""
+        };
+        return Err(invalid(format!("{:?} is not a {} digit{}", c, kind, hint)));
+    }
+    if digits.len() > max_digits {
+        return Err(invalid(format!(
+            "{} {} digits is longer than the {} a field element can have",
+            digits.len(),
+            kind,
+            max_digits
+        )));
     }
 
-    let hex_str = s.strip_prefix("0x").unwrap_or(s);
+    let magnitude =
+        BigUint::parse_bytes(digits.as_bytes(), radix).expect("digits are validated above");
+    if magnitude >= BigUint::from(Fr::MODULUS) {
+        return Err(invalid("not less than the BN254 scalar field modulus".to_string()));
+    }
 
-    // Parse hex string to big integer
-    let bytes = hex::decode(hex_str.trim_start_matches('0').to_string().as_str())
-        .or_else(|_| {
-            // Pad to even length
-            let padded = if hex_str.len() % 2 == 1 {
-                format!("0{}", hex_str)
-            } else {
-                hex_str.to_string()
-            };
-            hex::decode(&padded)
-        })
-        .map_err(|e| ArkworksError::ParseError(format!("Invalid hex: {}", e)))?;
-
-    // Convert to big-endian 32 bytes
-    let mut be_bytes = [0u8; 32];
-    let start = 32 - bytes.len().min(32);
-    be_bytes[start..].copy_from_slice(&bytes[bytes.len().saturating_sub(32)..]);
-
-    Fr::from_be_bytes_mod_order(&be_bytes)
-        .try_into()
-        .map_err(|_| ArkworksError::ParseError("Field element out of range".to_string()))
+    let value = Fr::from(magnitude);
+    Ok(if negative { -value } else { value })
 }
 
 /// Convert ACIR program to R1CS
 pub fn acir_to_r1cs(program: &AcirProgram) -> Result<AcirR1cs, ArkworksError> {
     // Get main function (index 0)
-    let circuit = program
-        .functions
-        .first()
-        .ok_or_else(|| ArkworksError::ParseError("No main function in ACIR".to_string()))?;
+    if program.functions.is_empty() {
+        return Err(ArkworksError::ParseError("No main function in ACIR".to_string()));
+    }
 
-    convert_circuit(circuit)
+    let mut calls = CallContext {
+        stack: vec![0],
+        converted: HashMap::new(),
+    };
+    convert_function(program, 0, &mut calls)
 }
 
-/// Convert a single ACIR circuit to R1CS
-fn convert_circuit(circuit: &AcirCircuit) -> Result<AcirR1cs, ArkworksError> {
-    let num_witnesses = (circuit.current_witness_index + 1) as usize;
-    let public_inputs = circuit.public_parameters.witnesses.clone();
-    let private_inputs = circuit.private_parameters.clone();
-    let return_values = circuit.return_values.witnesses.clone();
+/// Call state shared by the functions converted for one program
+struct CallContext {
+    /// Functions currently being inlined, so that recursive calls are
+    /// reported instead of looping forever
+    stack: Vec<u32>,
+    /// Converted functions by id: each is converted once, then relocated
+    /// into every call site
+    converted: HashMap<u32, AcirR1cs>,
+}
+
+/// Incrementally builds R1CS constraints for a circuit
+///
+/// Auxiliary witnesses are allocated past the ACIR witnesses, and each one
+/// is paired with the computation the prover uses to fill it in.
+pub(crate) struct R1csBuilder {
+    num_witnesses: usize,
+    constraints: Vec<R1csConstraint>,
+    aux_witnesses: Vec<AuxWitnessComputation>,
+    /// Current cell witnesses of each memory block, by block id
+    memory_blocks: HashMap<u32, Vec<u32>>,
+    /// Inlined calls, in execution order
+    calls: Vec<InlinedCall>,
+    /// Constraints added by black box gadgets, by black box name
+    black_box_constraints: BTreeMap<String, usize>,
+    /// BigInt black box values, by BigInt id
+    bigints: HashMap<u32, BigIntVar>,
+    /// Opcode being converted, recorded for each constraint added
+    origin: OpcodeOrigin,
+    /// Origin of each constraint
+    origins: Vec<OpcodeOrigin>,
+}
+
+impl R1csBuilder {
+    fn new(num_witnesses: usize) -> Self {
+        Self {
+            num_witnesses,
+            constraints: Vec::new(),
+            aux_witnesses: Vec::new(),
+            memory_blocks: HashMap::new(),
+            calls: Vec::new(),
+            black_box_constraints: BTreeMap::new(),
+            bigints: HashMap::new(),
+            origin: OpcodeOrigin::default(),
+            origins: Vec::new(),
+        }
+    }
+
+    /// Allocate a fresh auxiliary witness index
+    pub(crate) fn new_witness(&mut self) -> u32 {
+        let idx = self.num_witnesses as u32;
+        self.num_witnesses += 1;
+        idx
+    }
+
+    /// Add an A * B = C constraint
+    pub(crate) fn add_constraint(&mut self, a: Vec<(Fr, u32)>, b: Vec<(Fr, u32)>, c: Vec<(Fr, u32)>) {
+        self.constraints.push(R1csConstraint { a, b, c });
+        self.origins.push(self.origin);
+    }
+
+    /// Allocate a witness constrained to lhs * rhs
+    pub(crate) fn product(&mut self, lhs: u32, rhs: u32) -> u32 {
+        self.product_lc(vec![(Fr::one(), lhs)], vec![(Fr::one(), rhs)])
+    }
+
+    /// Allocate a witness constrained to the product of two linear combinations
+    pub(crate) fn product_lc(&mut self, lhs: Vec<(Fr, u32)>, rhs: Vec<(Fr, u32)>) -> u32 {
+        let target = self.new_witness();
+        self.add_constraint(lhs.clone(), rhs.clone(), vec![(Fr::one(), target)]);
+        self.aux_witnesses.push(AuxWitnessComputation::Product { target, lhs, rhs });
+        target
+    }
+
+    /// Allocate a witness constrained to lhs * rhs + linear
+    pub(crate) fn quadratic(
+        &mut self,
+        lhs: Vec<(Fr, u32)>,
+        rhs: Vec<(Fr, u32)>,
+        linear: Vec<(Fr, u32)>,
+    ) -> u32 {
+        let target = self.new_witness();
+        // lhs * rhs = target - linear
+        let mut c = vec![(Fr::one(), target)];
+        c.extend(linear.iter().map(|&(coeff, w)| (-coeff, w)));
+        self.add_constraint(lhs.clone(), rhs.clone(), c);
+        self.aux_witnesses.push(AuxWitnessComputation::Quadratic { target, lhs, rhs, linear });
+        target
+    }
+
+    /// Allocate a witness q constrained by q * denominator = numerator
+    ///
+    /// The prover sets q = 0 when the denominator is zero, which only
+    /// satisfies the constraint if the numerator is zero too.
+    pub(crate) fn quotient(&mut self, numerator: Vec<(Fr, u32)>, denominator: Vec<(Fr, u32)>) -> u32 {
+        let target = self.new_witness();
+        self.add_constraint(vec![(Fr::one(), target)], denominator.clone(), numerator.clone());
+        self.aux_witnesses.push(AuxWitnessComputation::Quotient {
+            target,
+            numerator,
+            denominator,
+        });
+        target
+    }
 
-    let mut constraints = Vec::new();
+    /// A boolean combination equal to 1 exactly when `value` is zero
+    ///
+    /// With inv the prover's inverse of value (0 if none), adds
+    /// value * inv = t and value * (1 - t) = 0, and returns 1 - t.
+    pub(crate) fn is_zero(&mut self, value: Vec<(Fr, u32)>) -> Vec<(Fr, u32)> {
+        let inverse = self.new_witness();
+        self.aux_witnesses.push(AuxWitnessComputation::Quotient {
+            target: inverse,
+            numerator: vec![(Fr::one(), 0)],
+            denominator: value.clone(),
+        });
+        let t = self.product_lc(value.clone(), vec![(Fr::one(), inverse)]);
+        let is_zero = vec![(Fr::one(), 0), (-Fr::one(), t)];
+        self.add_constraint(value, is_zero.clone(), vec![]);
+        is_zero
+    }
+
+    /// Register the computation of auxiliary witnesses a gadget allocated
+    pub(crate) fn add_aux_witness(&mut self, computation: AuxWitnessComputation) {
+        self.aux_witnesses.push(computation);
+    }
+
+    /// Constrain an ACIR output witness to a linear combination
+    ///
+    /// The prover fills the output in from the combination unless the
+    /// caller already supplied it.
+    pub(crate) fn assign_output(&mut self, output: u32, terms: Vec<(Fr, u32)>) {
+        self.aux_witnesses.push(AuxWitnessComputation::Linear {
+            target: output,
+            terms: terms.clone(),
+        });
+
+        // (terms - output) * 1 = 0
+        let mut terms = terms;
+        terms.push((-Fr::one(), output));
+        self.add_constraint(terms, vec![(Fr::one(), 0)], vec![]);
+    }
+
+    /// Decompose a witness into `num_bits` boolean witnesses (little-endian)
+    ///
+    /// Adds b * b = b for every bit and sum(2^i * b_i) = source, which
+    /// together range-check the source to [0, 2^num_bits).
+    pub(crate) fn decompose_bits(&mut self, source: u32, num_bits: u32) -> Vec<u32> {
+        let bits: Vec<u32> = (0..num_bits).map(|_| self.new_witness()).collect();
+
+        let mut recomposition = Vec::with_capacity(bits.len() + 1);
+        let mut power = Fr::one();
+        for &bit in &bits {
+            // Booleanity: b * b = b
+            self.add_constraint(
+                vec![(Fr::one(), bit)],
+                vec![(Fr::one(), bit)],
+                vec![(Fr::one(), bit)],
+            );
+            recomposition.push((power, bit));
+            power.double_in_place();
+        }
+
+        // Recomposition: (sum(2^i * b_i) - source) * 1 = 0
+        recomposition.push((-Fr::one(), source));
+        self.add_constraint(recomposition, vec![(Fr::one(), 0)], vec![]);
+
+        self.aux_witnesses.push(AuxWitnessComputation::BitDecomposition {
+            source,
+            bits: bits.clone(),
+        });
+        bits
+    }
 
-    for opcode in &circuit.opcodes {
-        match opcode {
-            Opcode::AssertZero { value } => {
-                // Convert AssertZero expression to R1CS constraint
-                let expr_constraints = expression_to_r1cs(value)?;
-                constraints.extend(expr_constraints);
+    /// Turn an ACIR expression into a linear combination
+    ///
+    /// Every mul term is replaced by an auxiliary product witness, and the
+    /// constant becomes a coefficient on w_0.
+    fn expression_to_lc(&mut self, expr: &Expression) -> Result<Vec<(Fr, u32)>, ArkworksError> {
+        let mut lc = Vec::new();
+        for (coeff, witness) in &expr.linear_combinations {
+            lc.push((parse_field_element(coeff)?, *witness));
+        }
+        for (coeff, a_wit, b_wit) in &expr.mul_terms {
+            let coeff_fr = parse_field_element(coeff)?;
+            let product = self.product(*a_wit, *b_wit);
+            lc.push((coeff_fr, product));
+        }
+        let q_c = parse_field_element(&expr.q_c)?;
+        if !q_c.is_zero() {
+            lc.push((q_c, 0));
+        }
+        Ok(lc)
+    }
+
+    /// Get a single witness equal to a linear combination
+    ///
+    /// Reuses the witness when the combination is just `1 * w`.
+    pub(crate) fn materialize(&mut self, lc: Vec<(Fr, u32)>) -> u32 {
+        if let [(coeff, witness)] = lc.as_slice() {
+            if coeff.is_one() {
+                return *witness;
             }
-            Opcode::BlackBoxFuncCall(bb) => {
-                // Black box functions need special handling
-                // For now, we support only basic operations
-                // More complex operations (SHA256, Pedersen) need native implementations
-                convert_black_box(bb, &mut constraints)?;
+        }
+
+        let target = self.new_witness();
+        self.aux_witnesses.push(AuxWitnessComputation::Linear {
+            target,
+            terms: lc.clone(),
+        });
+
+        // (lc - target) * 1 = 0
+        let mut terms = lc;
+        terms.push((-Fr::one(), target));
+        self.add_constraint(terms, vec![(Fr::one(), 0)], vec![]);
+        target
+    }
+
+    /// Value of a BigInt id, if a BigInt black box defined it
+    pub(crate) fn bigint(&self, id: u32) -> Option<BigIntVar> {
+        self.bigints.get(&id).cloned()
+    }
+
+    /// Bind a BigInt id to a value, replacing any earlier one
+    pub(crate) fn define_bigint(&mut self, id: u32, value: BigIntVar) {
+        self.bigints.insert(id, value);
+    }
+
+    /// Initialize a memory block with the given cell witnesses
+    fn memory_init(&mut self, init: &MemoryInit) {
+        self.memory_blocks.insert(init.block_id, init.init.clone());
+    }
+
+    /// Constrain a read from or write to a memory block
+    ///
+    /// Constant indices address the cell directly. Dynamic indices use a
+    /// one-hot selector vector s over the block:
+    ///   s_j * s_j = s_j, sum(s_j) = 1, sum(j * s_j) = index
+    /// so an out-of-bounds index is unsatisfiable. A read then enforces
+    /// s_j * (cell_j - value) = 0 for every cell, and a write creates new
+    /// cells with s_j * (value - cell_j) = cell'_j - cell_j.
+    fn memory_op(&mut self, op: &MemoryOp) -> Result<(), ArkworksError> {
+        let cells = self.memory_blocks.get(&op.block_id).cloned().ok_or_else(|| {
+            ArkworksError::InvalidInput(format!(
+                "MemoryOp on uninitialized block {}",
+                op.block_id
+            ))
+        })?;
+        let index = self.expression_to_lc(&op.index)?;
+        let value = self.expression_to_lc(&op.value)?;
+        let is_write = op.op.inner == 1;
+
+        // Constant index: address the cell directly
+        if index.iter().all(|&(_, w)| w == 0) {
+            let constant: Fr = index.iter().map(|&(coeff, _)| coeff).sum();
+            let position = constant.into_bigint().as_ref()[0] as usize;
+            if constant.into_bigint().num_bits() > 64 || position >= cells.len() {
+                return Err(ArkworksError::InvalidInput(format!(
+                    "Memory index {} out of bounds for block {} of size {}",
+                    constant,
+                    op.block_id,
+                    cells.len()
+                )));
+            }
+
+            if is_write {
+                let cell = self.materialize(value);
+                if let Some(block) = self.memory_blocks.get_mut(&op.block_id) {
+                    block[position] = cell;
+                }
+            } else {
+                self.read_into(&value, &index, &cells);
+                // (cell - value) * 1 = 0
+                let mut terms = vec![(Fr::one(), cells[position])];
+                terms.extend(value.iter().map(|&(coeff, w)| (-coeff, w)));
+                self.add_constraint(terms, vec![(Fr::one(), 0)], vec![]);
+            }
+            return Ok(());
+        }
+
+        // Dynamic index: one-hot selectors over the block
+        let selectors = self.one_hot(index.clone(), cells.len());
+
+        if is_write {
+            let updated: Vec<u32> = (0..cells.len()).map(|_| self.new_witness()).collect();
+            self.aux_witnesses.push(AuxWitnessComputation::ArrayWrite {
+                index,
+                value: value.clone(),
+                previous: cells.clone(),
+                updated: updated.clone(),
+            });
+            for ((&selector, &old), &new) in selectors.iter().zip(&cells).zip(&updated) {
+                // s_j * (value - cell_j) = cell'_j - cell_j
+                let mut diff = value.clone();
+                diff.push((-Fr::one(), old));
+                self.add_constraint(
+                    vec![(Fr::one(), selector)],
+                    diff,
+                    vec![(Fr::one(), new), (-Fr::one(), old)],
+                );
             }
-            Opcode::MemoryOp(_) | Opcode::MemoryInit(_) => {
-                // Memory operations are handled during witness generation
-                // They don't produce R1CS constraints directly
+            self.memory_blocks.insert(op.block_id, updated);
+        } else {
+            self.read_into(&value, &index, &cells);
+            for (&selector, &cell) in selectors.iter().zip(&cells) {
+                // s_j * (cell_j - value) = 0
+                let mut diff = vec![(Fr::one(), cell)];
+                diff.extend(value.iter().map(|&(coeff, w)| (-coeff, w)));
+                self.add_constraint(vec![(Fr::one(), selector)], diff, vec![]);
             }
-            Opcode::BrilligCall(_) => {
-                // Brillig calls are for unconstrained code
-                // They're executed during witness generation, not in R1CS
+        }
+
+        Ok(())
+    }
+
+    /// One-hot selectors s_0..s_size for a dynamic index
+    ///
+    /// Adds s_j * s_j = s_j, sum(s_j) = 1 and sum(j * s_j) = index, so an
+    /// index outside [0, size) is unsatisfiable.
+    pub(crate) fn one_hot(&mut self, index: Vec<(Fr, u32)>, size: usize) -> Vec<u32> {
+        let selectors: Vec<u32> = (0..size).map(|_| self.new_witness()).collect();
+        self.aux_witnesses.push(AuxWitnessComputation::OneHot {
+            index: index.clone(),
+            selectors: selectors.clone(),
+        });
+
+        let mut sum = Vec::with_capacity(selectors.len() + 1);
+        let mut weighted = Vec::with_capacity(selectors.len() + index.len());
+        for (j, &selector) in selectors.iter().enumerate() {
+            self.add_constraint(
+                vec![(Fr::one(), selector)],
+                vec![(Fr::one(), selector)],
+                vec![(Fr::one(), selector)],
+            );
+            sum.push((Fr::one(), selector));
+            if j > 0 {
+                weighted.push((Fr::from(j as u64), selector));
             }
-            Opcode::Call(_) => {
-                // Function calls should be inlined during ACIR compilation
-                return Err(ArkworksError::UnsupportedOpcode(
-                    "ACIR Call opcode not supported - circuit should be flattened".to_string(),
-                ));
+        }
+        // (sum(s_j) - 1) * 1 = 0
+        sum.push((-Fr::one(), 0));
+        self.add_constraint(sum, vec![(Fr::one(), 0)], vec![]);
+        // (sum(j * s_j) - index) * 1 = 0
+        weighted.extend(index.iter().map(|&(coeff, w)| (-coeff, w)));
+        self.add_constraint(weighted, vec![(Fr::one(), 0)], vec![]);
+        selectors
+    }
+
+    /// Fill in the witness a read stores its result in
+    ///
+    /// ACIR reads target a single witness; other value shapes must be
+    /// supplied by the caller.
+    fn read_into(&mut self, value: &[(Fr, u32)], index: &[(Fr, u32)], cells: &[u32]) {
+        if let [(coeff, target)] = value {
+            if coeff.is_one() && *target != 0 {
+                self.aux_witnesses.push(AuxWitnessComputation::ArrayRead {
+                    target: *target,
+                    index: index.to_vec(),
+                    cells: cells.to_vec(),
+                });
             }
         }
     }
 
+    /// Inline a converted callee for an ACIR `Call`
+    ///
+    /// The callee's witnesses are relocated into this circuit: parameters
+    /// (in ascending index order, as ACVM assigns them) bind to the call
+    /// inputs, return values bind to the call outputs, and everything else
+    /// gets fresh witnesses. `callee_witnesses` is the callee's
+    /// `current_witness_index`, bounding its ACIR witnesses.
+    ///
+    /// Under a non-constant predicate p, which must be boolean, each callee
+    /// constraint A * B = C becomes A * B = t, p * (t - C) = 0 and outputs
+    /// become p * r = output, matching ACVM, which skips the call and
+    /// zeroes its outputs when p = 0.
+    fn inline_call(
+        &mut self,
+        call: &AcirCall,
+        callee: &AcirR1cs,
+        callee_witnesses: u32,
+    ) -> Result<(), ArkworksError> {
+        let mut parameters: Vec<u32> = callee
+            .private_inputs
+            .iter()
+            .chain(&callee.public_inputs)
+            .copied()
+            .collect();
+        parameters.sort_unstable();
+        parameters.dedup();
+
+        if parameters.len() != call.inputs.len() || callee.return_values.len() != call.outputs.len() {
+            return Err(ArkworksError::InvalidInput(format!(
+                "Call to function {} has {} inputs and {} outputs, expected {} and {}",
+                call.id,
+                call.inputs.len(),
+                call.outputs.len(),
+                parameters.len(),
+                callee.return_values.len()
+            )));
+        }
+
+        // Resolve the predicate: None means the call always executes
+        let predicate = match &call.predicate {
+            None => None,
+            Some(expr) => {
+                let lc = self.expression_to_lc(expr)?;
+                if lc.iter().all(|&(_, w)| w == 0) {
+                    let constant: Fr = lc.iter().map(|&(coeff, _)| coeff).sum();
+                    if constant.is_zero() {
+                        // The call never executes: its outputs are zero
+                        for &output in &call.outputs {
+                            self.aux_witnesses.push(AuxWitnessComputation::Linear {
+                                target: output,
+                                terms: vec![],
+                            });
+                            self.add_constraint(vec![(Fr::one(), output)], vec![(Fr::one(), 0)], vec![]);
+                        }
+                        return Ok(());
+                    }
+                    None
+                } else {
+                    let p = self.materialize(lc);
+                    // Booleanity: p * p = p
+                    self.add_constraint(
+                        vec![(Fr::one(), p)],
+                        vec![(Fr::one(), p)],
+                        vec![(Fr::one(), p)],
+                    );
+                    Some(p)
+                }
+            }
+        };
+
+        let mut map: HashMap<u32, u32> = HashMap::new();
+        map.insert(0, 0);
+        for (&param, input) in parameters.iter().zip(&call.inputs) {
+            let lc = self.expression_to_lc(input)?;
+            let w = self.materialize(lc);
+            map.insert(param, w);
+        }
+
+        // Unconditional calls write their return values straight into the outputs
+        if predicate.is_none() {
+            for (&ret, &output) in callee.return_values.iter().zip(&call.outputs) {
+                map.entry(ret).or_insert(output);
+            }
+        }
+
+        let mut internal = Vec::new();
+        for idx in 1..callee.num_witnesses as u32 {
+            if let std::collections::hash_map::Entry::Vacant(entry) = map.entry(idx) {
+                let w = self.new_witness();
+                entry.insert(w);
+                internal.push(w);
+            }
+        }
+
+        // Record where the callee's ACIR witnesses live, then its own calls
+        self.calls.push(InlinedCall {
+            function_id: call.id,
+            witnesses: (1..=callee_witnesses).map(|i| (i, map[&i])).collect(),
+        });
+        for frame in &callee.calls {
+            self.calls.push(InlinedCall {
+                function_id: frame.function_id,
+                witnesses: frame.witnesses.iter().map(|(&k, v)| (k, map[v])).collect(),
+            });
+        }
+
+        let computations: Vec<AuxWitnessComputation> =
+            callee.aux_witnesses.iter().map(|c| c.relocate(&map)).collect();
+
+        match predicate {
+            None => {
+                self.aux_witnesses.extend(computations);
+                self.constraints
+                    .extend(callee.constraints.iter().map(|c| c.relocate(&map)));
+                self.origins.extend(&callee.origins);
+            }
+            Some(p) => {
+                self.aux_witnesses.push(AuxWitnessComputation::Predicated {
+                    predicate: vec![(Fr::one(), p)],
+                    witnesses: internal,
+                    computations,
+                });
+                // Gates keep the origin of the callee constraint they guard
+                let call_origin = self.origin;
+                for (constraint, &origin) in callee.constraints.iter().zip(&callee.origins) {
+                    self.origin = origin;
+                    let constraint = constraint.relocate(&map);
+                    let mut gated = if constraint.b == [(Fr::one(), 0)] {
+                        // Linear constraint: p * (A - C) = 0
+                        constraint.a
+                    } else {
+                        // p * (t - C) = 0 where t = A * B
+                        let t = self.new_witness();
+                        self.aux_witnesses.push(AuxWitnessComputation::Product {
+                            target: t,
+                            lhs: constraint.a.clone(),
+                            rhs: constraint.b.clone(),
+                        });
+                        self.add_constraint(constraint.a, constraint.b, vec![(Fr::one(), t)]);
+                        vec![(Fr::one(), t)]
+                    };
+                    gated.extend(constraint.c.iter().map(|&(coeff, w)| (-coeff, w)));
+                    self.add_constraint(vec![(Fr::one(), p)], gated, vec![]);
+                }
+                self.origin = call_origin;
+            }
+        }
+
+        // Link return values that could not be written into the outputs directly
+        for (&ret, &output) in callee.return_values.iter().zip(&call.outputs) {
+            let inner = map[&ret];
+            if inner == output {
+                continue;
+            }
+            match predicate {
+                None => {
+                    self.aux_witnesses.push(AuxWitnessComputation::Linear {
+                        target: output,
+                        terms: vec![(Fr::one(), inner)],
+                    });
+                    self.add_constraint(
+                        vec![(Fr::one(), inner), (-Fr::one(), output)],
+                        vec![(Fr::one(), 0)],
+                        vec![],
+                    );
+                }
+                Some(p) => {
+                    self.aux_witnesses.push(AuxWitnessComputation::Product {
+                        target: output,
+                        lhs: vec![(Fr::one(), p)],
+                        rhs: vec![(Fr::one(), inner)],
+                    });
+                    self.add_constraint(
+                        vec![(Fr::one(), p)],
+                        vec![(Fr::one(), inner)],
+                        vec![(Fr::one(), output)],
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Constrain `output` to the bitwise AND (or XOR) of two `num_bits` values
+    ///
+    /// Both operands are decomposed into bits and each bit pair is multiplied:
+    ///   AND: c_i = a_i * b_i
+    ///   XOR: c_i = a_i + b_i - 2 * a_i * b_i
+    /// The output is then recomposed from the c_i.
+    fn bitwise(&mut self, lhs: u32, rhs: u32, num_bits: u32, output: u32, xor: bool) {
+        let lhs_bits = self.decompose_bits(lhs, num_bits);
+        let rhs_bits = self.decompose_bits(rhs, num_bits);
+
+        let two = Fr::from(2u64);
+        let mut terms = Vec::new();
+        let mut power = Fr::one();
+        for (&a, &b) in lhs_bits.iter().zip(&rhs_bits) {
+            let product = self.product(a, b);
+            if xor {
+                terms.push((power, a));
+                terms.push((power, b));
+                terms.push((-(two * power), product));
+            } else {
+                terms.push((power, product));
+            }
+            power.double_in_place();
+        }
+
+        // sum(2^i * c_i) = output
+        self.assign_output(output, terms);
+    }
+}
+
+/// Convert a single ACIR function to R1CS
+fn convert_function(
+    program: &AcirProgram,
+    id: u32,
+    calls: &mut CallContext,
+) -> Result<AcirR1cs, ArkworksError> {
+    let circuit: &AcirCircuit = program
+        .functions
+        .get(id as usize)
+        .ok_or_else(|| ArkworksError::ParseError(format!("No function {} in ACIR", id)))?;
+
+    let public_inputs = circuit.public_parameters.witnesses.clone();
+    let private_inputs = circuit.private_parameters.clone();
+    let return_values = circuit.return_values.witnesses.clone();
+
+    let mut builder = R1csBuilder::new((circuit.current_witness_index + 1) as usize);
+    let mut assert_messages: BTreeMap<OpcodeOrigin, String> = circuit
+        .messages_by_opcode()
+        .into_iter()
+        .map(|(opcode, message)| (OpcodeOrigin { function: id, opcode }, message))
+        .collect();
+
+    for (index, opcode) in circuit.opcodes.iter().enumerate() {
+        builder.origin = OpcodeOrigin { function: id, opcode: index };
+        convert_opcode(program, opcode, &mut builder, &mut assert_messages, calls)
+            .map_err(|e| e.at_opcode(builder.origin))?;
+    }
+
     Ok(AcirR1cs {
-        num_witnesses,
+        num_witnesses: builder.num_witnesses,
         public_inputs,
         private_inputs,
         return_values,
-        constraints,
+        constraints: builder.constraints,
+        aux_witnesses: builder.aux_witnesses,
+        calls: builder.calls,
+        black_box_constraints: builder.black_box_constraints,
+        origins: builder.origins,
+        assert_messages,
+        renumbering: None,
     })
 }
 
+/// Convert one opcode of a function into `builder`
+fn convert_opcode(
+    program: &AcirProgram,
+    opcode: &Opcode,
+    builder: &mut R1csBuilder,
+    assert_messages: &mut BTreeMap<OpcodeOrigin, String>,
+    calls: &mut CallContext,
+) -> Result<(), ArkworksError> {
+    match opcode {
+        Opcode::AssertZero { value } => {
+            // Convert AssertZero expression to R1CS constraint
+            expression_to_r1cs(value, builder)?;
+        }
+        Opcode::BlackBoxFuncCall(bb) => {
+            // Black box functions map to gadgets; their constraint
+            // counts are tallied by name for acir_to_r1cs_info
+            let before = builder.constraints.len();
+            convert_black_box(bb, builder)?;
+            let added = builder.constraints.len() - before;
+            *builder.black_box_constraints.entry(bb.name().to_string()).or_default() += added;
+        }
+        Opcode::MemoryInit(init) => {
+            builder.memory_init(init);
+        }
+        Opcode::MemoryOp(op) => {
+            builder.memory_op(op)?;
+        }
+        Opcode::BrilligCall(_) => {
+            // Brillig calls are for unconstrained code
+            // They're executed during witness generation, not in R1CS
+        }
+        Opcode::Call(call) => {
+            if calls.stack.contains(&call.id) {
+                return Err(ArkworksError::UnsupportedOpcode(format!(
+                    "Recursive ACIR call to function {}",
+                    call.id
+                )));
+            }
+            if !calls.converted.contains_key(&call.id) {
+                calls.stack.push(call.id);
+                let callee = convert_function(program, call.id, calls)?;
+                calls.stack.pop();
+                calls.converted.insert(call.id, callee);
+            }
+            let callee = &calls.converted[&call.id];
+
+            let callee_witnesses = program.functions[call.id as usize].current_witness_index;
+            builder.inline_call(call, callee, callee_witnesses)?;
+            for (name, count) in &callee.black_box_constraints {
+                *builder.black_box_constraints.entry(name.clone()).or_default() += count;
+            }
+            assert_messages.extend(callee.assert_messages.iter().map(|(&k, v)| (k, v.clone())));
+        }
+    }
+    Ok(())
+}
+
 /// Convert an ACIR expression to R1CS constraints
 ///
 /// ACIR Expression: sum(linear_combinations) + sum(mul_terms) + q_c = 0
@@ -209,189 +1343,211 @@ fn convert_circuit(circuit: &AcirCircuit) -> Result<AcirR1cs, ArkworksError> {
 ///   => A = a, B = b, C = -(linear + q_c)
 ///
 /// Case 3: Multiple multiplications
-///   Need intermediate variables
-fn expression_to_r1cs(expr: &Expression) -> Result<Vec<R1csConstraint>, ArkworksError> {
-    let mut constraints = Vec::new();
-
-    let linear = &expr.linear_combinations;
-    let mul_terms = &expr.mul_terms;
+///   Every product after the first gets an auxiliary witness m_i = a_i * b_i,
+///   which then enters the final constraint as a linear term:
+///   coeff_0 * a_0 * b_0 = -(linear + sum(coeff_i * m_i) + q_c)
+fn expression_to_r1cs(expr: &Expression, builder: &mut R1csBuilder) -> Result<(), ArkworksError> {
     let q_c = parse_field_element(&expr.q_c)?;
 
-    match mul_terms.len() {
-        0 => {
+    let mut linear: Vec<(Fr, u32)> = Vec::new();
+    for (coeff, witness) in &expr.linear_combinations {
+        linear.push((parse_field_element(coeff)?, *witness));
+    }
+
+    // Replace every extra product with an auxiliary witness
+    if expr.mul_terms.len() > 1 {
+        for (coeff, a_wit, b_wit) in &expr.mul_terms[1..] {
+            let coeff_fr = parse_field_element(coeff)?;
+            let product = builder.product(*a_wit, *b_wit);
+            linear.push((coeff_fr, product));
+        }
+    }
+
+    match expr.mul_terms.first() {
+        None => {
             // Pure linear constraint: linear + q_c = 0
             // (linear + q_c) * 1 = 0
-            let mut a_terms: Vec<(Fr, u32)> = Vec::new();
-
-            // Add linear terms
-            for (coeff, witness) in linear {
-                let coeff_fr = parse_field_element(coeff)?;
-                a_terms.push((coeff_fr, *witness));
-            }
+            let mut a_terms = linear;
 
             // Add constant (witness 0 = 1)
-            if q_c != Fr::from(0u64) {
+            if !q_c.is_zero() {
                 a_terms.push((q_c, 0));
             }
 
             // A * 1 = 0
-            constraints.push(R1csConstraint {
-                a: a_terms,
-                b: vec![(Fr::from(1u64), 0)], // 1 * w_0 where w_0 = 1
-                c: vec![],                    // = 0
-            });
+            builder.add_constraint(
+                a_terms,
+                vec![(Fr::one(), 0)], // 1 * w_0 where w_0 = 1
+                vec![],               // = 0
+            );
         }
-        1 => {
+        Some((mul_coeff, a_wit, b_wit)) => {
             // Single multiplication: coeff * a * b + linear + q_c = 0
             // => coeff * a * b = -(linear + q_c)
-            let (mul_coeff, a_wit, b_wit) = &mul_terms[0];
             let mul_coeff_fr = parse_field_element(mul_coeff)?;
 
             // Build C = -(linear + q_c)
-            let mut c_terms: Vec<(Fr, u32)> = Vec::new();
-            for (coeff, witness) in linear {
-                let coeff_fr = parse_field_element(coeff)?;
-                c_terms.push((-coeff_fr, *witness));
-            }
-            if q_c != Fr::from(0u64) {
+            let mut c_terms: Vec<(Fr, u32)> = linear
+                .into_iter()
+                .map(|(coeff, witness)| (-coeff, witness))
+                .collect();
+            if !q_c.is_zero() {
                 c_terms.push((-q_c, 0));
             }
 
             // (coeff * a) * b = C
-            constraints.push(R1csConstraint {
-                a: vec![(mul_coeff_fr, *a_wit)],
-                b: vec![(Fr::from(1u64), *b_wit)],
-                c: c_terms,
-            });
-        }
-        _ => {
-            // Multiple multiplications - need to handle with intermediate variables
-            // For now, we combine them if possible or return an error
-            // This case is complex and requires circuit restructuring
-
-            // Simple case: all mul terms can be combined
-            // sum(coeff_i * a_i * b_i) + linear + q_c = 0
-            //
-            // We use a sequence of additions with auxiliary variables:
-            // m_1 = coeff_1 * a_1 * b_1
-            // m_2 = coeff_2 * a_2 * b_2
-            // ...
-            // m_1 + m_2 + ... + linear + q_c = 0
-
-            // For simplicity in this implementation, we only support
-            // the case where we can reduce to basic form
-            return Err(ArkworksError::UnsupportedOpcode(
-                format!(
-                    "Multiple multiplication terms ({}) in single expression not yet supported",
-                    mul_terms.len()
-                ),
-            ));
+            builder.add_constraint(
+                vec![(mul_coeff_fr, *a_wit)],
+                vec![(Fr::one(), *b_wit)],
+                c_terms,
+            );
         }
     }
 
-    Ok(constraints)
+    Ok(())
 }
 
 /// Convert black box function to R1CS constraints
 fn convert_black_box(
     bb: &crate::acir_types::BlackBoxFuncCall,
-    _constraints: &mut Vec<R1csConstraint>,
+    builder: &mut R1csBuilder,
 ) -> Result<(), ArkworksError> {
-    use crate::acir_types::BlackBoxFuncCall;
+    use crate::acir_types::{BlackBoxFuncCall, FunctionInput};
 
     match bb {
         BlackBoxFuncCall::Range { input } => {
             // Range check: input must fit in num_bits bits
-            // For R1CS, this requires bit decomposition constraints
-            // This is expensive but necessary for soundness
-            let _num_bits = input.num_bits;
-            let _witness = input.witness;
-
-            // For now, we skip range constraints in R1CS
-            // A proper implementation would add bit decomposition constraints
-            // This is a security note: real implementation needs proper range checks
+            // Every field element fits in the modulus bit size, so wider
+            // checks hold trivially and would make the decomposition ambiguous
+            // Constant inputs are checked against their bit size when parsed
+            if let Some(witness) = input.to_witness() {
+                if input.num_bits < Fr::MODULUS_BIT_SIZE {
+                    builder.decompose_bits(witness, input.num_bits);
+                }
+            }
             Ok(())
         }
-        BlackBoxFuncCall::And { lhs, rhs, output } => {
-            // AND is not directly expressible in R1CS
-            // Need bit decomposition
-            let _ = (lhs, rhs, output);
-            Err(ArkworksError::UnsupportedOpcode(
-                "AND black box not yet supported in R1CS".to_string(),
-            ))
+        BlackBoxFuncCall::And { lhs, rhs, output } | BlackBoxFuncCall::Xor { lhs, rhs, output } => {
+            // AND/XOR are not directly expressible in R1CS, so we work bit by bit
+            // Both operands share the width of lhs, as in ACVM
+            let num_bits = lhs.num_bits;
+            if num_bits >= Fr::MODULUS_BIT_SIZE {
+                return Err(ArkworksError::UnsupportedOpcode(format!(
+                    "Bitwise black box on {} bits exceeds the field size",
+                    num_bits
+                )));
+            }
+            let xor = matches!(bb, BlackBoxFuncCall::Xor { .. });
+            let (lhs, rhs) = (builder.materialize(lhs.lc()), builder.materialize(rhs.lc()));
+            builder.bitwise(lhs, rhs, num_bits, *output, xor);
+            Ok(())
         }
-        BlackBoxFuncCall::Xor { lhs, rhs, output } => {
-            // XOR is not directly expressible in R1CS
-            let _ = (lhs, rhs, output);
-            Err(ArkworksError::UnsupportedOpcode(
-                "XOR black box not yet supported in R1CS".to_string(),
-            ))
+        BlackBoxFuncCall::Sha256 { inputs, outputs } => {
+            sha256::hash_gadget(builder, inputs, outputs)
         }
-        BlackBoxFuncCall::Sha256 { .. }
-        | BlackBoxFuncCall::Blake2s { .. }
-        | BlackBoxFuncCall::Blake3 { .. }
-        | BlackBoxFuncCall::Keccak256 { .. }
-        | BlackBoxFuncCall::Keccakf1600 { .. } => {
-            // Hash functions require custom R1CS gadgets
-            // These are very expensive in terms of constraints
-            Err(ArkworksError::UnsupportedOpcode(
-                "Hash function black boxes not yet supported in R1CS".to_string(),
-            ))
+        BlackBoxFuncCall::Sha256Compression { inputs, hash_values, outputs } => {
+            sha256::compression_gadget(builder, inputs, hash_values, outputs)
         }
-        BlackBoxFuncCall::PedersenCommitment { .. } | BlackBoxFuncCall::PedersenHash { .. } => {
-            // Pedersen operations on embedded curve
-            Err(ArkworksError::UnsupportedOpcode(
-                "Pedersen black box not yet supported in R1CS".to_string(),
-            ))
+        BlackBoxFuncCall::Keccak256 { inputs, var_message_size, outputs } => {
+            keccak::hash_gadget(builder, inputs, var_message_size.as_ref(), outputs)
         }
-        BlackBoxFuncCall::EcdsaSecp256k1 { .. } | BlackBoxFuncCall::EcdsaSecp256r1 { .. } => {
-            // ECDSA verification is very expensive in R1CS
-            Err(ArkworksError::UnsupportedOpcode(
-                "ECDSA black box not yet supported in R1CS".to_string(),
-            ))
+        BlackBoxFuncCall::Keccakf1600 { inputs, outputs } => {
+            keccak::permutation_gadget(builder, inputs, outputs)
         }
-        BlackBoxFuncCall::SchnorrVerify { .. } => {
-            Err(ArkworksError::UnsupportedOpcode(
-                "Schnorr black box not yet supported in R1CS".to_string(),
-            ))
+        BlackBoxFuncCall::Blake2s { inputs, outputs } => {
+            blake2s::hash_gadget(builder, inputs, outputs)
         }
-        BlackBoxFuncCall::FixedBaseScalarMul { .. } | BlackBoxFuncCall::EmbeddedCurveAdd { .. } => {
-            // Embedded curve operations
-            Err(ArkworksError::UnsupportedOpcode(
-                "Embedded curve black box not yet supported in R1CS".to_string(),
-            ))
+        BlackBoxFuncCall::Blake3 { inputs, outputs } => blake3::hash_gadget(builder, inputs, outputs),
+        BlackBoxFuncCall::PedersenCommitment { inputs, domain_separator, outputs } => {
+            pedersen::commitment_gadget(builder, inputs, *domain_separator, *outputs);
+            Ok(())
+        }
+        BlackBoxFuncCall::PedersenHash { inputs, domain_separator, output } => {
+            pedersen::hash_gadget(builder, inputs, *domain_separator, *output);
+            Ok(())
+        }
+        BlackBoxFuncCall::EcdsaSecp256k1 {
+            public_key_x,
+            public_key_y,
+            signature,
+            hashed_message,
+            output,
+        }
+        | BlackBoxFuncCall::EcdsaSecp256r1 {
+            public_key_x,
+            public_key_y,
+            signature,
+            hashed_message,
+            output,
+        } => {
+            let curve = if matches!(bb, BlackBoxFuncCall::EcdsaSecp256k1 { .. }) {
+                ecdsa::Curve::secp256k1()
+            } else {
+                ecdsa::Curve::secp256r1()
+            };
+            ecdsa::verify_gadget(
+                builder,
+                &curve,
+                public_key_x,
+                public_key_y,
+                signature,
+                hashed_message,
+                *output,
+            )
+        }
+        BlackBoxFuncCall::SchnorrVerify {
+            public_key_x,
+            public_key_y,
+            signature,
+            message,
+            output,
+        } => schnorr::verify_gadget(builder, public_key_x, public_key_y, signature, message, *output),
+        BlackBoxFuncCall::EmbeddedCurveAdd { input1, input2, outputs } => {
+            embedded_curve::add_gadget(builder, input1, input2, *outputs);
+            Ok(())
+        }
+        BlackBoxFuncCall::MultiScalarMul { points, scalars, outputs } => {
+            embedded_curve::multi_scalar_mul_gadget(builder, points, scalars, *outputs)
+        }
+        BlackBoxFuncCall::FixedBaseScalarMul { low, high, outputs } => {
+            embedded_curve::fixed_base_scalar_mul_gadget(builder, low, high, *outputs);
+            Ok(())
         }
         BlackBoxFuncCall::RecursiveAggregation { .. } => {
             Err(ArkworksError::UnsupportedOpcode(
                 "Recursive aggregation not supported in R1CS".to_string(),
             ))
         }
-        BlackBoxFuncCall::BigIntAdd { .. }
-        | BlackBoxFuncCall::BigIntSub { .. }
-        | BlackBoxFuncCall::BigIntMul { .. }
-        | BlackBoxFuncCall::BigIntDiv { .. }
-        | BlackBoxFuncCall::BigIntFromLeBytes { .. }
-        | BlackBoxFuncCall::BigIntToLeBytes { .. } => {
-            Err(ArkworksError::UnsupportedOpcode(
-                "BigInt black box not yet supported in R1CS".to_string(),
-            ))
+        BlackBoxFuncCall::BigIntAdd { lhs, rhs, output } => {
+            bigint::operation_gadget(builder, bigint::Operation::Add, *lhs, *rhs, *output)
         }
-        BlackBoxFuncCall::Poseidon2Permutation { .. } => {
-            // Poseidon is ZK-friendly but still needs custom implementation
-            Err(ArkworksError::UnsupportedOpcode(
-                "Poseidon2 black box not yet supported in R1CS".to_string(),
-            ))
+        BlackBoxFuncCall::BigIntSub { lhs, rhs, output } => {
+            bigint::operation_gadget(builder, bigint::Operation::Sub, *lhs, *rhs, *output)
         }
-        BlackBoxFuncCall::Sha256Compression { .. } => {
-            Err(ArkworksError::UnsupportedOpcode(
-                "SHA256 compression not yet supported in R1CS".to_string(),
-            ))
+        BlackBoxFuncCall::BigIntMul { lhs, rhs, output } => {
+            bigint::operation_gadget(builder, bigint::Operation::Mul, *lhs, *rhs, *output)
         }
-        BlackBoxFuncCall::Unknown => {
-            Err(ArkworksError::UnsupportedOpcode(
-                "Unknown black box function".to_string(),
-            ))
+        BlackBoxFuncCall::BigIntDiv { lhs, rhs, output } => {
+            bigint::operation_gadget(builder, bigint::Operation::Div, *lhs, *rhs, *output)
+        }
+        BlackBoxFuncCall::BigIntFromLeBytes { inputs, modulus, output } => {
+            bigint::from_le_bytes_gadget(builder, inputs, modulus, *output)
+        }
+        BlackBoxFuncCall::BigIntToLeBytes { input, outputs } => {
+            bigint::to_le_bytes_gadget(builder, *input, outputs)
         }
+        BlackBoxFuncCall::Poseidon2Permutation { inputs, outputs, len } => {
+            let inputs: Vec<Vec<(Fr, u32)>> = inputs.iter().map(FunctionInput::lc).collect();
+            let len = len.unwrap_or(inputs.len() as u32);
+            poseidon2::permutation_gadget(builder, &inputs, outputs, len)
+        }
+        BlackBoxFuncCall::Aes128Encrypt { .. } => Err(ArkworksError::UnsupportedOpcode(
+            "AES128Encrypt black box not supported in R1CS".to_string(),
+        )),
+        BlackBoxFuncCall::Unknown { name } => Err(ArkworksError::UnsupportedOpcode(format!(
+            "Unknown black box function {}",
+            name
+        ))),
     }
 }
 
@@ -405,6 +1561,50 @@ mod tests {
         assert_eq!(parse_field_element("0x1").unwrap(), Fr::from(1u64));
         assert_eq!(parse_field_element("0x10").unwrap(), Fr::from(16u64));
         assert_eq!(parse_field_element("0xff").unwrap(), Fr::from(255u64));
+        assert_eq!(parse_field_element("0xFF").unwrap(), Fr::from(255u64));
+        assert_eq!(parse_field_element("255").unwrap(), Fr::from(255u64));
+        assert_eq!(parse_field_element("-0x5").unwrap(), -Fr::from(5u64));
+        assert_eq!(parse_field_element("-5").unwrap(), -Fr::from(5u64));
+        assert_eq!(parse_field_element("-0").unwrap(), Fr::from(0u64));
+        assert_eq!(parse_field_element(" 0x2a\n").unwrap(), Fr::from(42u64));
+
+        // Zero-padded 32-byte hex, as written by witness_to_json
+        let padded = format!("0x{}", "0".repeat(63) + "7");
+        assert_eq!(parse_field_element(&padded).unwrap(), Fr::from(7u64));
+
+        let r_minus_one = BigUint::from(Fr::MODULUS) - 1u32;
+        assert_eq!(parse_field_element(&r_minus_one.to_string()).unwrap(), -Fr::one());
+        assert_eq!(
+            parse_field_element(&format!("0x{}", r_minus_one.to_str_radix(16))).unwrap(),
+            -Fr::one()
+        );
+    }
+
+    #[test]
+    fn test_parse_field_element_rejects_non_canonical() {
+        let reason = |s: &str| match parse_field_element(s) {
+            Err(ArkworksError::ParseError(message)) => message,
+            other => panic!("expected {:?} to be rejected, got {:?}", s, other),
+        };
+
+        let r = BigUint::from(Fr::MODULUS);
+        assert!(reason(&r.to_string()).contains("not less than"));
+        assert!(reason(&format!("0x{}", r.to_str_radix(16))).contains("not less than"));
+        assert!(reason(&format!("-{}", r)).contains("not less than"));
+        assert!(reason(&format!("0x{}", "f".repeat(64))).contains("not less than"));
+
+        assert!(reason(&format!("0x{}", "0".repeat(65))).contains("65 hex digits"));
+        assert!(reason(&"1".repeat(78)).contains("78 decimal digits"));
+
+        assert!(reason("").contains("no digits"));
+        assert!(reason("0x").contains("no digits"));
+        assert!(reason("-").contains("no digits"));
+        assert!(reason("ff").contains("'f' is not a decimal digit (hex values need a 0x prefix)"));
+        assert!(reason("0xfg").contains("'g' is not a hex digit"));
+        assert!(reason("1 2").contains("' ' is not a decimal digit"));
+        assert!(reason("   ").contains("no digits"));
+        assert!(reason("+1").contains("'+' is not a decimal digit"));
+        assert!(reason("--1").contains("'-' is not a decimal digit"));
     }
 
     #[test]
@@ -416,8 +1616,437 @@ mod tests {
             q_c: "-0x5".to_string(),
         };
 
-        // This should fail because we don't support negative constants in simple hex
-        // In a real implementation, we'd handle this properly
-        let _ = expression_to_r1cs(&expr);
+        let mut builder = R1csBuilder::new(3);
+        expression_to_r1cs(&expr, &mut builder).unwrap();
+        assert_eq!(builder.constraints.len(), 1);
+
+        let r1cs = AcirR1cs {
+            num_witnesses: builder.num_witnesses,
+            public_inputs: vec![],
+            private_inputs: vec![1, 2],
+            return_values: vec![],
+            constraints: builder.constraints,
+            aux_witnesses: builder.aux_witnesses,
+            calls: vec![],
+            black_box_constraints: BTreeMap::new(),
+            origins: vec![],
+            assert_messages: BTreeMap::new(),
+            renumbering: None,
+        };
+
+        // 2*1 + 3*1 - 5 = 0, but 2*2 + 3*1 - 5 != 0
+        let mut witness = WitnessMap::new();
+        witness.insert(1, Fr::from(1u64));
+        witness.insert(2, Fr::from(1u64));
+        assert!(r1cs.constraints[0].is_satisfied(&witness).unwrap());
+        witness.insert(1, Fr::from(2u64));
+        assert!(!r1cs.constraints[0].is_satisfied(&witness).unwrap());
+    }
+
+    #[test]
+    fn test_multiple_mul_terms_to_r1cs() {
+        // Expression: w1*w2 + 2*w3*w4 - w1*w3 - w5 = 0
+        let neg_one = format!("0x{}", hex::encode(crate::gnark_compat::fr_to_be_bytes(&-Fr::one())));
+        let expr = Expression {
+            linear_combinations: vec![(neg_one.clone(), 5)],
+            mul_terms: vec![
+                ("0x1".to_string(), 1, 2),
+                ("0x2".to_string(), 3, 4),
+                (neg_one, 1, 3),
+            ],
+            q_c: "0x0".to_string(),
+        };
+
+        let mut builder = R1csBuilder::new(6);
+        expression_to_r1cs(&expr, &mut builder).unwrap();
+
+        // One auxiliary product per extra mul term, plus the final constraint
+        assert_eq!(builder.num_witnesses, 8);
+        assert_eq!(builder.constraints.len(), 3);
+        assert_eq!(builder.aux_witnesses.len(), 2);
+
+        let r1cs = AcirR1cs {
+            num_witnesses: builder.num_witnesses,
+            public_inputs: vec![5],
+            private_inputs: vec![1, 2, 3, 4],
+            return_values: vec![],
+            constraints: builder.constraints,
+            aux_witnesses: builder.aux_witnesses,
+            calls: vec![],
+            black_box_constraints: BTreeMap::new(),
+            origins: vec![],
+            assert_messages: BTreeMap::new(),
+            renumbering: None,
+        };
+
+        // w1=3, w2=4, w3=5, w4=6 => w5 = 12 + 60 - 15 = 57
+        let mut witness = WitnessMap::new();
+        for (idx, value) in [(1u32, 3u64), (2, 4), (3, 5), (4, 6), (5, 57)] {
+            witness.insert(idx, Fr::from(value));
+        }
+        r1cs.compute_aux_witnesses(&mut witness).unwrap();
+
+        assert_eq!(witness[&6], Fr::from(30u64));
+        assert_eq!(witness[&7], Fr::from(15u64));
+        for constraint in &r1cs.constraints {
+            assert!(constraint.is_satisfied(&witness).unwrap());
+        }
+
+        // A wrong output must violate the final constraint
+        witness.insert(5, Fr::from(58u64));
+        assert!(!r1cs.constraints[2].is_satisfied(&witness).unwrap());
+    }
+
+    #[test]
+    fn test_range_constraint_bit_decomposition() {
+        use crate::acir_types::{BlackBoxFuncCall, FunctionInput};
+
+        let range = BlackBoxFuncCall::Range {
+            input: FunctionInput::witness(1, 8),
+        };
+
+        let mut builder = R1csBuilder::new(2);
+        convert_black_box(&range, &mut builder).unwrap();
+
+        // 8 booleanity constraints plus one recomposition
+        assert_eq!(builder.num_witnesses, 10);
+        assert_eq!(builder.constraints.len(), 9);
+
+        let mut witness = WitnessMap::new();
+        witness.insert(1, Fr::from(200u64));
+        for computation in &builder.aux_witnesses {
+            computation.compute(&mut witness).unwrap();
+        }
+        for constraint in &builder.constraints {
+            assert!(constraint.is_satisfied(&witness).unwrap());
+        }
+
+        // 256 does not fit in 8 bits
+        witness.insert(1, Fr::from(256u64));
+        let result = builder.aux_witnesses[0].compute(&mut witness);
+        assert!(matches!(result, Err(ArkworksError::InvalidInput(_))));
+
+        // The bits of 200 no longer recompose to the input
+        assert!(!builder.constraints[8].is_satisfied(&witness).unwrap());
+    }
+
+    /// Convert a single bitwise black box and solve it for the given operands
+    fn solve_bitwise(xor: bool, lhs: u64, rhs: u64, num_bits: u32) -> (R1csBuilder, WitnessMap) {
+        use crate::acir_types::{BlackBoxFuncCall, FunctionInput};
+
+        let (lhs_input, rhs_input) = (
+            FunctionInput::witness(1, num_bits),
+            FunctionInput::witness(2, num_bits),
+        );
+        let bb = if xor {
+            BlackBoxFuncCall::Xor { lhs: lhs_input, rhs: rhs_input, output: 3 }
+        } else {
+            BlackBoxFuncCall::And { lhs: lhs_input, rhs: rhs_input, output: 3 }
+        };
+
+        let mut builder = R1csBuilder::new(4);
+        convert_black_box(&bb, &mut builder).unwrap();
+
+        let mut witness = WitnessMap::new();
+        witness.insert(1, Fr::from(lhs));
+        witness.insert(2, Fr::from(rhs));
+        for computation in &builder.aux_witnesses {
+            computation.compute(&mut witness).unwrap();
+        }
+        (builder, witness)
+    }
+
+    #[test]
+    fn test_and_xor_black_boxes() {
+        for (xor, expected) in [(false, 0b1000_0010u64), (true, 0b0111_0101u64)] {
+            let (builder, witness) = solve_bitwise(xor, 0b1100_0110, 0b1011_0011, 8);
+
+            assert_eq!(witness[&3], Fr::from(expected));
+            for constraint in &builder.constraints {
+                assert!(constraint.is_satisfied(&witness).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn test_bitwise_rejects_wrong_output() {
+        let (builder, mut witness) = solve_bitwise(false, 0xffff_ffff, 0x1234_5678, 32);
+        assert_eq!(witness[&3], Fr::from(0x1234_5678u64));
+
+        witness.insert(3, Fr::from(0x1234_5679u64));
+        let satisfied = builder
+            .constraints
+            .iter()
+            .all(|c| c.is_satisfied(&witness).unwrap());
+        assert!(!satisfied);
+    }
+
+    /// Build a circuit from a JSON list of opcodes
+    fn circuit_from_opcodes(current_witness_index: u32, opcodes: serde_json::Value) -> AcirCircuit {
+        serde_json::from_value(serde_json::json!({
+            "current_witness_index": current_witness_index,
+            "opcodes": opcodes,
+            "private_parameters": [],
+            "public_parameters": { "witnesses": [] },
+            "return_values": { "witnesses": [] }
+        }))
+        .unwrap()
+    }
+
+    fn program_from_functions(functions: Vec<AcirCircuit>) -> AcirProgram {
+        AcirProgram {
+            functions,
+            unconstrained_functions: vec![],
+            debug_symbols: None,
+            file_map: None,
+        }
+    }
+
+    /// Compute aux witnesses and report whether every constraint holds
+    fn solve_and_check(r1cs: &AcirR1cs, witness: &mut WitnessMap) -> bool {
+        r1cs.compute_aux_witnesses(witness).unwrap();
+        r1cs.constraints.iter().all(|c| c.is_satisfied(witness).unwrap())
+    }
+
+    #[test]
+    fn test_memory_read_write() {
+        // block 0 = [w1, w2, w3]
+        // w5 = block0[w4]; block0[w4] = w6; w7 = block0[2]
+        let circuit = circuit_from_opcodes(
+            7,
+            serde_json::json!([
+                { "type": "MemoryInit", "block_id": 0, "init": [1, 2, 3] },
+                {
+                    "type": "MemoryOp", "block_id": 0, "op": { "inner": 0 },
+                    "index": { "linear_combinations": [["0x1", 4]], "q_c": "0x0" },
+                    "value": { "linear_combinations": [["0x1", 5]], "q_c": "0x0" }
+                },
+                {
+                    "type": "MemoryOp", "block_id": 0, "op": { "inner": 1 },
+                    "index": { "linear_combinations": [["0x1", 4]], "q_c": "0x0" },
+                    "value": { "linear_combinations": [["0x1", 6]], "q_c": "0x0" }
+                },
+                {
+                    "type": "MemoryOp", "block_id": 0, "op": { "inner": 0 },
+                    "index": { "q_c": "0x2" },
+                    "value": { "linear_combinations": [["0x1", 7]], "q_c": "0x0" }
+                }
+            ]),
+        );
+        let r1cs = acir_to_r1cs(&program_from_functions(vec![circuit])).unwrap();
+
+        let mut witness = WitnessMap::new();
+        for (idx, value) in [(1u32, 10u64), (2, 20), (3, 30), (4, 2), (6, 99)] {
+            witness.insert(idx, Fr::from(value));
+        }
+        assert!(solve_and_check(&r1cs, &mut witness));
+        assert_eq!(witness[&5], Fr::from(30u64));
+        assert_eq!(witness[&7], Fr::from(99u64));
+
+        // A read value that does not match the addressed cell is rejected
+        witness.insert(5, Fr::from(20u64));
+        assert!(!r1cs.constraints.iter().all(|c| c.is_satisfied(&witness).unwrap()));
+    }
+
+    #[test]
+    fn test_memory_index_out_of_bounds() {
+        let circuit = circuit_from_opcodes(
+            3,
+            serde_json::json!([
+                { "type": "MemoryInit", "block_id": 0, "init": [1, 2] },
+                {
+                    "type": "MemoryOp", "block_id": 0, "op": { "inner": 0 },
+                    "index": { "linear_combinations": [["0x1", 3]], "q_c": "0x0" },
+                    "value": { "linear_combinations": [["0x1", 1]], "q_c": "0x0" }
+                }
+            ]),
+        );
+        let r1cs = acir_to_r1cs(&program_from_functions(vec![circuit])).unwrap();
+
+        let mut witness = WitnessMap::new();
+        for (idx, value) in [(1u32, 5u64), (2, 6), (3, 2)] {
+            witness.insert(idx, Fr::from(value));
+        }
+        let result = r1cs.compute_aux_witnesses(&mut witness);
+        assert!(matches!(result, Err(ArkworksError::InvalidInput(_))));
+
+        // Constant indices are checked at conversion time
+        let circuit = circuit_from_opcodes(
+            2,
+            serde_json::json!([
+                { "type": "MemoryInit", "block_id": 0, "init": [1] },
+                {
+                    "type": "MemoryOp", "block_id": 0, "op": { "inner": 0 },
+                    "index": { "q_c": "0x1" },
+                    "value": { "linear_combinations": [["0x1", 2]], "q_c": "0x0" }
+                }
+            ]),
+        );
+        assert!(acir_to_r1cs(&program_from_functions(vec![circuit])).is_err());
+    }
+
+    /// Callee: fn double_if_small(w1) -> w2, with w2 = 2 * w1 and w1 < 2^8
+    fn double_callee() -> AcirCircuit {
+        let mut callee = circuit_from_opcodes(
+            2,
+            serde_json::json!([
+                { "type": "BlackBoxFuncCall", "name": "RANGE", "input": { "witness": 1, "num_bits": 8 } },
+                {
+                    "type": "AssertZero",
+                    "value": { "linear_combinations": [["0x2", 1], [format!("0x{}", hex::encode(crate::gnark_compat::fr_to_be_bytes(&-Fr::one()))), 2]], "q_c": "0x0" }
+                }
+            ]),
+        );
+        callee.private_parameters = vec![1];
+        callee.return_values.witnesses = vec![2];
+        callee
+    }
+
+    #[test]
+    fn test_inline_call() {
+        // main: w2 = double(w1 + 1); w3 = double(w2)
+        let main = circuit_from_opcodes(
+            3,
+            serde_json::json!([
+                {
+                    "type": "Call", "id": 1,
+                    "inputs": [{ "linear_combinations": [["0x1", 1]], "q_c": "0x1" }],
+                    "outputs": [2]
+                },
+                {
+                    "type": "Call", "id": 1,
+                    "inputs": [{ "linear_combinations": [["0x1", 2]], "q_c": "0x0" }],
+                    "outputs": [3]
+                }
+            ]),
+        );
+        let r1cs = acir_to_r1cs(&program_from_functions(vec![main, double_callee()])).unwrap();
+        assert_eq!(r1cs.calls.len(), 2);
+
+        let mut witness = WitnessMap::new();
+        witness.insert(1, Fr::from(4u64));
+        witness.insert(2, Fr::from(10u64));
+        witness.insert(3, Fr::from(20u64));
+
+        // Callee witnesses are placed through the recorded frames
+        let mut first = WitnessMap::new();
+        first.insert(1, Fr::from(5u64));
+        first.insert(2, Fr::from(10u64));
+        r1cs.insert_call_witnesses(0, &first, &mut witness).unwrap();
+        let mut second = WitnessMap::new();
+        second.insert(1, Fr::from(10u64));
+        second.insert(2, Fr::from(20u64));
+        r1cs.insert_call_witnesses(1, &second, &mut witness).unwrap();
+
+        assert!(solve_and_check(&r1cs, &mut witness));
+
+        // The callee's range check is enforced for every call
+        let mut witness = WitnessMap::new();
+        witness.insert(1, Fr::from(200u64));
+        witness.insert(2, Fr::from(402u64));
+        witness.insert(3, Fr::from(804u64));
+        r1cs.insert_call_witnesses(0, &first, &mut witness).unwrap();
+        assert!(r1cs.compute_aux_witnesses(&mut witness).is_err());
+    }
+
+    #[test]
+    fn test_inline_call_with_predicate() {
+        // main: w3 = if w2 { double(w1) } else { 0 }
+        let main = circuit_from_opcodes(
+            3,
+            serde_json::json!([
+                {
+                    "type": "Call", "id": 1,
+                    "inputs": [{ "linear_combinations": [["0x1", 1]], "q_c": "0x0" }],
+                    "outputs": [3],
+                    "predicate": { "linear_combinations": [["0x1", 2]], "q_c": "0x0" }
+                }
+            ]),
+        );
+        let r1cs = acir_to_r1cs(&program_from_functions(vec![main, double_callee()])).unwrap();
+
+        // Predicate false: the callee is skipped even though w1 is out of range
+        let mut witness = WitnessMap::new();
+        witness.insert(1, Fr::from(1000u64));
+        witness.insert(2, Fr::zero());
+        assert!(solve_and_check(&r1cs, &mut witness));
+        assert_eq!(witness[&3], Fr::zero());
+
+        // Predicate true: the callee runs and its output is forwarded
+        let mut witness = WitnessMap::new();
+        witness.insert(1, Fr::from(7u64));
+        witness.insert(2, Fr::one());
+        let mut callee = WitnessMap::new();
+        callee.insert(1, Fr::from(7u64));
+        callee.insert(2, Fr::from(14u64));
+        r1cs.insert_call_witnesses(0, &callee, &mut witness).unwrap();
+        assert!(solve_and_check(&r1cs, &mut witness));
+        assert_eq!(witness[&3], Fr::from(14u64));
+
+        // A wrong output is rejected
+        witness.insert(3, Fr::from(15u64));
+        assert!(!r1cs.constraints.iter().all(|c| c.is_satisfied(&witness).unwrap()));
+    }
+
+    #[test]
+    fn test_nested_calls_converted_once() {
+        // f_i(w1) -> w2 calls f_{i+1} twice, the second time under a zero
+        // predicate, and the last function returns its input. Converting
+        // each callee at every call site would take 2^DEPTH conversions.
+        const DEPTH: u32 = 40;
+        let mut functions: Vec<AcirCircuit> = (0..DEPTH)
+            .map(|i| {
+                let input = serde_json::json!([{ "linear_combinations": [["0x1", 1]], "q_c": "0x0" }]);
+                circuit_from_opcodes(
+                    3,
+                    serde_json::json!([
+                        { "type": "Call", "id": i + 1, "inputs": input, "outputs": [2] },
+                        {
+                            "type": "Call", "id": i + 1, "inputs": input, "outputs": [3],
+                            "predicate": { "q_c": "0x0" }
+                        }
+                    ]),
+                )
+            })
+            .collect();
+        functions.push(circuit_from_opcodes(
+            2,
+            serde_json::json!([
+                { "type": "AssertZero", "value": { "linear_combinations": [["0x1", 1], ["-0x1", 2]] } }
+            ]),
+        ));
+        for function in &mut functions {
+            function.private_parameters = vec![1];
+            function.return_values.witnesses = vec![2];
+        }
+
+        let r1cs = acir_to_r1cs(&program_from_functions(functions)).unwrap();
+        assert_eq!(r1cs.calls.len(), DEPTH as usize);
+        assert!(r1cs.constraints.len() <= 4 * DEPTH as usize);
+
+        let mut witness = WitnessMap::new();
+        witness.insert(1, Fr::from(5u64));
+        witness.insert(2, Fr::from(5u64));
+        assert!(solve_and_check(&r1cs, &mut witness));
+        witness.insert(2, Fr::from(6u64));
+        assert!(!r1cs.constraints.iter().all(|c| c.is_satisfied(&witness).unwrap()));
+    }
+
+    #[test]
+    fn test_recursive_call_rejected() {
+        let mut main = circuit_from_opcodes(
+            1,
+            serde_json::json!([
+                {
+                    "type": "Call", "id": 0,
+                    "inputs": [{ "linear_combinations": [["0x1", 1]], "q_c": "0x0" }],
+                    "outputs": []
+                }
+            ]),
+        );
+        main.private_parameters = vec![1];
+        let err = acir_to_r1cs(&program_from_functions(vec![main])).err().unwrap();
+        assert!(matches!(err.inner(), ArkworksError::UnsupportedOpcode(_)));
+        assert_eq!(err.origin(), Some(OpcodeOrigin { function: 0, opcode: 0 }));
     }
 }
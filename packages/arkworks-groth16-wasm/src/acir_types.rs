@@ -2,8 +2,21 @@
 //!
 //! These types represent the ACIR format output by the Noir compiler.
 //! We parse these to convert to R1CS constraints for Groth16 proving.
+//!
+//! Black box calls are accepted in the shapes of several ACIR revisions:
+//! inputs as `{ witness, num_bits }`, as `{ input: { Witness | Constant },
+//! num_bits }` or as a bare `{ Witness | Constant }` whose bit size is
+//! implied by the black box, and calls tagged by a `name` field or by a
+//! single key holding their fields.
 
+use ark_bn254::Fr;
+use ark_ff::{BigInteger, PrimeField};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use crate::debug_info::DebugInfo;
+use crate::error::ArkworksError;
 
 /// Witness index in the circuit
 pub type WitnessIndex = u32;
@@ -18,7 +31,30 @@ pub struct AcirProgram {
     pub functions: Vec<AcirCircuit>,
     /// Unconstrained functions (for unconstrained Noir code)
     #[serde(default)]
-    pub unconstrained_functions: Vec<serde_json::Value>,
+    pub unconstrained_functions: Vec<BrilligBytecode>,
+    /// Noir debug symbols, compressed as nargo writes them or decompressed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub debug_symbols: Option<Value>,
+    /// Noir source files the debug symbols refer to, by file id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_map: Option<Value>,
+}
+
+impl AcirProgram {
+    /// Source locations of the opcodes, if the program has debug symbols
+    pub fn debug_info(&self) -> Result<Option<DebugInfo>, ArkworksError> {
+        debug_info(self.debug_symbols.as_ref(), self.file_map.as_ref())
+    }
+}
+
+/// Parse debug symbols and the file map, if there are debug symbols
+fn debug_info(
+    debug_symbols: Option<&Value>,
+    file_map: Option<&Value>,
+) -> Result<Option<DebugInfo>, ArkworksError> {
+    debug_symbols
+        .map(|symbols| DebugInfo::parse(symbols, file_map.unwrap_or(&Value::Null)))
+        .transpose()
 }
 
 /// A single ACIR circuit (function)
@@ -42,6 +78,45 @@ pub struct AcirCircuit {
     pub assert_messages: Vec<serde_json::Value>,
 }
 
+impl AcirCircuit {
+    /// Assertion messages by ACIR opcode index
+    ///
+    /// Entries are `[location, payload]` pairs. Static strings are returned
+    /// as they are; a payload Noir encodes through an error selector is
+    /// described by the selector, as its string lives in the ABI.
+    pub fn messages_by_opcode(&self) -> BTreeMap<usize, String> {
+        self.assert_messages.iter().filter_map(assert_message).collect()
+    }
+}
+
+/// Parse one `[location, payload]` entry of `assert_messages`
+fn assert_message(entry: &Value) -> Option<(usize, String)> {
+    let [location, payload] = entry.as_array()?.as_slice() else {
+        return None;
+    };
+    // Brillig locations ("3.5" or { "Brillig": .. }) are not ACIR opcodes
+    let opcode = match location {
+        Value::Number(n) => n.as_u64()?,
+        Value::String(s) => s.parse().ok()?,
+        Value::Object(o) => o.get("Acir")?.as_u64()?,
+        _ => return None,
+    };
+    let message = match payload {
+        Value::String(s) => s.clone(),
+        Value::Object(o) => match o.get("StaticString") {
+            Some(s) => s.as_str()?.to_string(),
+            None => {
+                let selector = o
+                    .get("error_selector")
+                    .or_else(|| o.get("Dynamic").and_then(|d| d.get(0)))?;
+                format!("assertion with error selector {}", selector)
+            }
+        },
+        _ => return None,
+    };
+    Some((opcode as usize, message))
+}
+
 /// Expression width for circuit optimization
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", content = "width")]
@@ -75,7 +150,7 @@ pub enum Opcode {
     AssertZero { value: Expression },
 
     /// Black box function call (SHA256, Pedersen, etc.)
-    #[serde(rename = "BlackBoxFuncCall")]
+    #[serde(rename = "BlackBoxFuncCall", deserialize_with = "black_box::deserialize")]
     BlackBoxFuncCall(BlackBoxFuncCall),
 
     /// Memory operations
@@ -105,7 +180,7 @@ pub struct Expression {
     #[serde(default)]
     pub mul_terms: Vec<(FieldElement, WitnessIndex, WitnessIndex)>,
     /// Constant term
-    #[serde(default)]
+    #[serde(default = "zero_constant")]
     pub q_c: FieldElement,
 }
 
@@ -114,16 +189,21 @@ impl Default for Expression {
         Self {
             linear_combinations: Vec::new(),
             mul_terms: Vec::new(),
-            q_c: "0x0".to_string(),
+            q_c: zero_constant(),
         }
     }
 }
 
+/// Constant term of an expression that leaves it out
+fn zero_constant() -> FieldElement {
+    "0x0".to_string()
+}
+
 /// Black box function call
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "name")]
 pub enum BlackBoxFuncCall {
-    #[serde(rename = "SHA256")]
+    #[serde(rename = "SHA256", alias = "Sha256")]
     Sha256 {
         inputs: Vec<FunctionInput>,
         outputs: Vec<WitnessIndex>,
@@ -141,6 +221,9 @@ pub enum BlackBoxFuncCall {
     #[serde(rename = "Keccak256")]
     Keccak256 {
         inputs: Vec<FunctionInput>,
+        /// Number of input bytes to hash, when fewer than `inputs`
+        #[serde(default)]
+        var_message_size: Option<FunctionInput>,
         outputs: Vec<WitnessIndex>,
     },
     #[serde(rename = "Keccakf1600")]
@@ -196,19 +279,29 @@ pub enum BlackBoxFuncCall {
         input2: (FunctionInput, FunctionInput, FunctionInput),
         outputs: (WitnessIndex, WitnessIndex, WitnessIndex),
     },
-    #[serde(rename = "AND")]
+    /// Sum of scalar multiples of Grumpkin points
+    ///
+    /// `points` holds (x, y, is_infinite) triples and `scalars` (low, high)
+    /// pairs of 128-bit limbs; the outputs are the resulting triple.
+    #[serde(rename = "MultiScalarMul")]
+    MultiScalarMul {
+        points: Vec<FunctionInput>,
+        scalars: Vec<FunctionInput>,
+        outputs: (WitnessIndex, WitnessIndex, WitnessIndex),
+    },
+    #[serde(rename = "AND", alias = "And")]
     And {
         lhs: FunctionInput,
         rhs: FunctionInput,
         output: WitnessIndex,
     },
-    #[serde(rename = "XOR")]
+    #[serde(rename = "XOR", alias = "Xor")]
     Xor {
         lhs: FunctionInput,
         rhs: FunctionInput,
         output: WitnessIndex,
     },
-    #[serde(rename = "RANGE")]
+    #[serde(rename = "RANGE", alias = "Range")]
     Range { input: FunctionInput },
     #[serde(rename = "RecursiveAggregation")]
     RecursiveAggregation {
@@ -252,11 +345,13 @@ pub enum BlackBoxFuncCall {
         input: u32,
         outputs: Vec<WitnessIndex>,
     },
+    /// Poseidon2 permutation; newer ACIR leaves out `len`, the state width
     #[serde(rename = "Poseidon2Permutation")]
     Poseidon2Permutation {
         inputs: Vec<FunctionInput>,
         outputs: Vec<WitnessIndex>,
-        len: u32,
+        #[serde(default)]
+        len: Option<u32>,
     },
     #[serde(rename = "Sha256Compression")]
     Sha256Compression {
@@ -264,20 +359,257 @@ pub enum BlackBoxFuncCall {
         hash_values: Vec<FunctionInput>,
         outputs: Vec<WitnessIndex>,
     },
-    /// Catch-all for unknown black box functions
-    #[serde(other)]
-    Unknown,
+    #[serde(rename = "AES128Encrypt")]
+    Aes128Encrypt {
+        inputs: Vec<FunctionInput>,
+        iv: Vec<FunctionInput>,
+        key: Vec<FunctionInput>,
+        outputs: Vec<WitnessIndex>,
+    },
+    /// A black box this crate does not know, by its ACIR name
+    #[serde(skip)]
+    Unknown { name: String },
+}
+
+impl BlackBoxFuncCall {
+    /// Name of the black box function, as serialized in ACIR
+    pub fn name(&self) -> &str {
+        match self {
+            BlackBoxFuncCall::Sha256 { .. } => "SHA256",
+            BlackBoxFuncCall::Blake2s { .. } => "Blake2s",
+            BlackBoxFuncCall::Blake3 { .. } => "Blake3",
+            BlackBoxFuncCall::Keccak256 { .. } => "Keccak256",
+            BlackBoxFuncCall::Keccakf1600 { .. } => "Keccakf1600",
+            BlackBoxFuncCall::PedersenCommitment { .. } => "PedersenCommitment",
+            BlackBoxFuncCall::PedersenHash { .. } => "PedersenHash",
+            BlackBoxFuncCall::EcdsaSecp256k1 { .. } => "EcdsaSecp256k1",
+            BlackBoxFuncCall::EcdsaSecp256r1 { .. } => "EcdsaSecp256r1",
+            BlackBoxFuncCall::SchnorrVerify { .. } => "SchnorrVerify",
+            BlackBoxFuncCall::FixedBaseScalarMul { .. } => "FixedBaseScalarMul",
+            BlackBoxFuncCall::EmbeddedCurveAdd { .. } => "EmbeddedCurveAdd",
+            BlackBoxFuncCall::MultiScalarMul { .. } => "MultiScalarMul",
+            BlackBoxFuncCall::And { .. } => "AND",
+            BlackBoxFuncCall::Xor { .. } => "XOR",
+            BlackBoxFuncCall::Range { .. } => "RANGE",
+            BlackBoxFuncCall::RecursiveAggregation { .. } => "RecursiveAggregation",
+            BlackBoxFuncCall::BigIntAdd { .. } => "BigIntAdd",
+            BlackBoxFuncCall::BigIntSub { .. } => "BigIntSub",
+            BlackBoxFuncCall::BigIntMul { .. } => "BigIntMul",
+            BlackBoxFuncCall::BigIntDiv { .. } => "BigIntDiv",
+            BlackBoxFuncCall::BigIntFromLeBytes { .. } => "BigIntFromLeBytes",
+            BlackBoxFuncCall::BigIntToLeBytes { .. } => "BigIntToLeBytes",
+            BlackBoxFuncCall::Poseidon2Permutation { .. } => "Poseidon2Permutation",
+            BlackBoxFuncCall::Sha256Compression { .. } => "Sha256Compression",
+            BlackBoxFuncCall::Aes128Encrypt { .. } => "AES128Encrypt",
+            BlackBoxFuncCall::Unknown { name } => name,
+        }
+    }
 }
 
 /// Input to a black box function
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "FunctionInputRepr")]
 pub struct FunctionInput {
-    /// Witness index
-    pub witness: WitnessIndex,
+    /// Witness or constant value
+    pub input: ConstantOrWitness,
     /// Number of bits (for range constraints)
     pub num_bits: u32,
 }
 
+/// Value of a black box input
+///
+/// Only newer ACIR allows constants, which are serialized as hex strings.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ConstantOrWitness {
+    Constant(#[serde(with = "field_hex")] Fr),
+    Witness(WitnessIndex),
+}
+
+impl std::fmt::Display for ConstantOrWitness {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConstantOrWitness::Constant(c) => write!(f, "constant {}", c),
+            ConstantOrWitness::Witness(w) => write!(f, "witness {}", w),
+        }
+    }
+}
+
+impl FunctionInput {
+    /// A witness input of the given bit size
+    pub fn witness(witness: WitnessIndex, num_bits: u32) -> Self {
+        Self {
+            input: ConstantOrWitness::Witness(witness),
+            num_bits,
+        }
+    }
+
+    /// The witness of the input, unless it is a constant
+    pub fn to_witness(&self) -> Option<WitnessIndex> {
+        match self.input {
+            ConstantOrWitness::Witness(w) => Some(w),
+            ConstantOrWitness::Constant(_) => None,
+        }
+    }
+
+    /// The input as a linear combination, constants being multiples of w_0
+    pub fn lc(&self) -> Vec<(Fr, WitnessIndex)> {
+        match self.input {
+            ConstantOrWitness::Witness(w) => vec![(Fr::from(1u64), w)],
+            ConstantOrWitness::Constant(c) => vec![(c, 0)],
+        }
+    }
+}
+
+/// Serialized shapes of `FunctionInput` across ACIR revisions
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FunctionInputRepr {
+    Legacy { witness: WitnessIndex, num_bits: u32 },
+    Tagged { input: ConstantOrWitness, num_bits: u32 },
+}
+
+impl TryFrom<FunctionInputRepr> for FunctionInput {
+    type Error = String;
+
+    fn try_from(repr: FunctionInputRepr) -> Result<Self, String> {
+        let input = match repr {
+            FunctionInputRepr::Legacy { witness, num_bits } => FunctionInput::witness(witness, num_bits),
+            FunctionInputRepr::Tagged { input, num_bits } => FunctionInput { input, num_bits },
+        };
+        if let ConstantOrWitness::Constant(c) = input.input {
+            if c.into_bigint().num_bits() > input.num_bits {
+                return Err(format!(
+                    "constant black box input {} does not fit in {} bits",
+                    c, input.num_bits
+                ));
+            }
+        }
+        Ok(input)
+    }
+}
+
+/// Field elements as 0x-prefixed hex strings
+mod field_hex {
+    use ark_bn254::Fr;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &Fr, serializer: S) -> Result<S::Ok, S::Error> {
+        let bytes = crate::gnark_compat::fr_to_be_bytes(value);
+        serializer.serialize_str(&format!("0x{}", hex::encode(bytes)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Fr, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        crate::acir_to_r1cs::parse_field_element(&s).map_err(D::Error::custom)
+    }
+}
+
+/// Version-tolerant deserialization of black box calls
+mod black_box {
+    use super::BlackBoxFuncCall;
+    use serde::{de::Error, Deserialize, Deserializer};
+    use serde_json::{Map, Value};
+
+    /// ACIR names of the black boxes `BlackBoxFuncCall` models, aliases
+    /// included
+    const KNOWN: &[&str] = &[
+        "SHA256", "Sha256", "Blake2s", "Blake3", "Keccak256", "Keccakf1600",
+        "PedersenCommitment", "PedersenHash", "EcdsaSecp256k1", "EcdsaSecp256r1",
+        "SchnorrVerify", "FixedBaseScalarMul", "EmbeddedCurveAdd", "MultiScalarMul",
+        "AND", "And", "XOR", "Xor", "RANGE", "Range", "RecursiveAggregation",
+        "BigIntAdd", "BigIntSub", "BigIntMul", "BigIntDiv", "BigIntFromLeBytes",
+        "BigIntToLeBytes", "Poseidon2Permutation", "Sha256Compression", "AES128Encrypt",
+    ];
+
+    /// Bits of a bare input, which the black box implies
+    ///
+    /// AND, XOR and RANGE carry a call-level `num_bits`; byte-oriented
+    /// inputs have 8 bits, Keccak lanes 64, SHA-256 words 32, and
+    /// everything else is a full field element.
+    fn implied_num_bits(name: &str, field: &str, call: &Map<String, Value>) -> u64 {
+        let field_bits = u64::from(<ark_bn254::Fr as ark_ff::PrimeField>::MODULUS_BIT_SIZE);
+        match (name, field) {
+            ("AND" | "And" | "XOR" | "Xor" | "RANGE" | "Range", _) => {
+                call.get("num_bits").and_then(Value::as_u64).unwrap_or(field_bits)
+            }
+            ("Keccakf1600", _) => 64,
+            ("Sha256Compression", _) | ("Keccak256", "var_message_size") => 32,
+            ("SchnorrVerify", "public_key_x" | "public_key_y") => field_bits,
+            (
+                "SHA256" | "Sha256" | "Blake2s" | "Blake3" | "Keccak256" | "EcdsaSecp256k1"
+                | "EcdsaSecp256r1" | "SchnorrVerify" | "BigIntFromLeBytes" | "AES128Encrypt",
+                _,
+            ) => 8,
+            _ => field_bits,
+        }
+    }
+
+    /// Rewrite bare `{ Witness | Constant }` inputs as `{ input, num_bits }`
+    fn upgrade_inputs(value: &mut Value, num_bits: u64) {
+        match value {
+            Value::Array(items) => {
+                for item in items {
+                    upgrade_inputs(item, num_bits);
+                }
+            }
+            Value::Object(object)
+                if object.len() == 1
+                    && (object.contains_key("Witness") || object.contains_key("Constant")) =>
+            {
+                let input = std::mem::take(object);
+                object.insert("input".to_string(), Value::Object(input));
+                object.insert("num_bits".to_string(), Value::from(num_bits));
+            }
+            _ => {}
+        }
+    }
+
+    /// Bring a call into the `{ name, ..fields }` shape with upgraded inputs
+    fn normalize(value: Value) -> Result<(String, Value), String> {
+        let mut call = match value {
+            Value::Object(call) => call,
+            other => return Err(format!("expected a black box call, got {}", other)),
+        };
+        let name = match call.get("name") {
+            Some(Value::String(name)) => name.clone(),
+            Some(other) => return Err(format!("invalid black box name {}", other)),
+            None => match call.len() {
+                // { "AND": { "lhs": .., .. } }
+                1 => {
+                    let (name, fields) = call.into_iter().next().unwrap_or_default();
+                    call = match fields {
+                        Value::Object(fields) => fields,
+                        other => return Err(format!("invalid fields for black box {}: {}", name, other)),
+                    };
+                    name
+                }
+                _ => return Err("black box call has no name".to_string()),
+            },
+        };
+
+        let fields: Vec<String> = call.keys().filter(|k| *k != "name").cloned().collect();
+        for field in fields {
+            let num_bits = implied_num_bits(&name, &field, &call);
+            if let Some(value) = call.get_mut(&field) {
+                upgrade_inputs(value, num_bits);
+            }
+        }
+        call.insert("name".to_string(), Value::String(name.clone()));
+        Ok((name, Value::Object(call)))
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<BlackBoxFuncCall, D::Error> {
+        let (name, call) = normalize(Value::deserialize(deserializer)?).map_err(D::Error::custom)?;
+        if !KNOWN.contains(&name.as_str()) {
+            return Ok(BlackBoxFuncCall::Unknown { name });
+        }
+        serde_json::from_value(call)
+            .map_err(|e| D::Error::custom(format!("black box {}: {}", name, e)))
+    }
+}
+
 /// Memory operation
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MemoryOp {
@@ -312,21 +644,265 @@ pub struct BrilligCall {
 
 /// Brillig inputs
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(tag = "type")]
+#[serde(from = "BrilligInputsRepr")]
 pub enum BrilligInputs {
     Single(Expression),
     Array(Vec<Expression>),
     MemoryArray(u32),
 }
 
+/// Serialized shapes of `BrilligInputs` across releases
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BrilligInputsRepr {
+    Tagged(TaggedBrilligInputs),
+    Legacy(LegacyBrilligInputs),
+}
+
+/// `{ "Single": .. }`, `{ "Array": .. }` or `{ "MemoryArray": .. }`
+#[derive(Deserialize)]
+enum TaggedBrilligInputs {
+    Single(Expression),
+    Array(Vec<Expression>),
+    MemoryArray(u32),
+}
+
+/// `{ "type": "Single", ..expression }`, as earlier releases read inputs;
+/// that shape has no room for the other variants
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+enum LegacyBrilligInputs {
+    Single(Expression),
+}
+
+impl From<BrilligInputsRepr> for BrilligInputs {
+    fn from(repr: BrilligInputsRepr) -> Self {
+        match repr {
+            BrilligInputsRepr::Tagged(TaggedBrilligInputs::Single(expr))
+            | BrilligInputsRepr::Legacy(LegacyBrilligInputs::Single(expr)) => BrilligInputs::Single(expr),
+            BrilligInputsRepr::Tagged(TaggedBrilligInputs::Array(exprs)) => BrilligInputs::Array(exprs),
+            BrilligInputsRepr::Tagged(TaggedBrilligInputs::MemoryArray(block_id)) => {
+                BrilligInputs::MemoryArray(block_id)
+            }
+        }
+    }
+}
+
 /// Brillig outputs
+///
+/// Earlier releases tagged outputs internally, a shape serde cannot read
+/// for either variant, so there is no older shape to accept.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(tag = "type")]
 pub enum BrilligOutputs {
     Simple(WitnessIndex),
     Array(Vec<WitnessIndex>),
 }
 
+/// Bytecode of a single unconstrained (Brillig) function
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BrilligBytecode {
+    pub bytecode: Vec<BrilligOpcode>,
+}
+
+/// Address in Brillig memory
+///
+/// Relative addresses are offset by the stack pointer stored at address 0.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum MemoryAddress {
+    Direct(usize),
+    Relative(usize),
+}
+
+/// Bit size of a Brillig integer
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum IntegerBitSize {
+    U1,
+    U8,
+    U16,
+    U32,
+    U64,
+    U128,
+}
+
+impl IntegerBitSize {
+    /// Number of bits in the integer type
+    pub fn bits(self) -> u32 {
+        match self {
+            IntegerBitSize::U1 => 1,
+            IntegerBitSize::U8 => 8,
+            IntegerBitSize::U16 => 16,
+            IntegerBitSize::U32 => 32,
+            IntegerBitSize::U64 => 64,
+            IntegerBitSize::U128 => 128,
+        }
+    }
+}
+
+/// Type of a Brillig memory value
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum BitSize {
+    Field,
+    Integer(IntegerBitSize),
+}
+
+/// Binary operation on field elements
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum BinaryFieldOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    IntegerDiv,
+    Equals,
+    LessThan,
+    LessThanEquals,
+}
+
+/// Binary operation on fixed-width integers
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum BinaryIntOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Equals,
+    LessThan,
+    LessThanEquals,
+    And,
+    Or,
+    Xor,
+    Shl,
+    Shr,
+}
+
+/// Fixed-size array in Brillig memory: `size` values starting at `*pointer`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct HeapArray {
+    pub pointer: MemoryAddress,
+    pub size: usize,
+}
+
+/// Dynamically sized vector in Brillig memory: `*size` values starting at `*pointer`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct HeapVector {
+    pub pointer: MemoryAddress,
+    pub size: MemoryAddress,
+}
+
+/// Foreign call argument or destination
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ValueOrArray {
+    MemoryAddress(MemoryAddress),
+    HeapArray(HeapArray),
+    HeapVector(HeapVector),
+}
+
+/// Black box operation callable from Brillig
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "name")]
+pub enum BrilligBlackBoxOp {
+    /// Decompose `input` into `num_limbs` big-endian digits of `radix`
+    ToRadix {
+        input: MemoryAddress,
+        radix: MemoryAddress,
+        output_pointer: MemoryAddress,
+        num_limbs: MemoryAddress,
+        output_bits: MemoryAddress,
+    },
+    /// Catch-all for black boxes the interpreter does not implement
+    #[serde(other)]
+    Unsupported,
+}
+
+/// Brillig VM opcode
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum BrilligOpcode {
+    BinaryFieldOp {
+        destination: MemoryAddress,
+        op: BinaryFieldOp,
+        lhs: MemoryAddress,
+        rhs: MemoryAddress,
+    },
+    BinaryIntOp {
+        destination: MemoryAddress,
+        op: BinaryIntOp,
+        bit_size: IntegerBitSize,
+        lhs: MemoryAddress,
+        rhs: MemoryAddress,
+    },
+    Not {
+        destination: MemoryAddress,
+        source: MemoryAddress,
+        bit_size: IntegerBitSize,
+    },
+    Cast {
+        destination: MemoryAddress,
+        source: MemoryAddress,
+        bit_size: BitSize,
+    },
+    JumpIfNot {
+        condition: MemoryAddress,
+        location: usize,
+    },
+    JumpIf {
+        condition: MemoryAddress,
+        location: usize,
+    },
+    Jump {
+        location: usize,
+    },
+    CalldataCopy {
+        destination_address: MemoryAddress,
+        size_address: MemoryAddress,
+        offset_address: MemoryAddress,
+    },
+    Call {
+        location: usize,
+    },
+    Const {
+        destination: MemoryAddress,
+        bit_size: BitSize,
+        value: FieldElement,
+    },
+    IndirectConst {
+        destination_pointer: MemoryAddress,
+        bit_size: BitSize,
+        value: FieldElement,
+    },
+    Return,
+    ForeignCall {
+        function: String,
+        destinations: Vec<ValueOrArray>,
+        inputs: Vec<ValueOrArray>,
+    },
+    Mov {
+        destination: MemoryAddress,
+        source: MemoryAddress,
+    },
+    ConditionalMov {
+        destination: MemoryAddress,
+        source_a: MemoryAddress,
+        source_b: MemoryAddress,
+        condition: MemoryAddress,
+    },
+    Load {
+        destination: MemoryAddress,
+        source_pointer: MemoryAddress,
+    },
+    Store {
+        destination_pointer: MemoryAddress,
+        source: MemoryAddress,
+    },
+    BlackBox(BrilligBlackBoxOp),
+    Trap {
+        revert_data: HeapVector,
+    },
+    Stop {
+        return_data: HeapVector,
+    },
+}
+
 /// Call to another ACIR function
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AcirCall {
@@ -352,6 +928,14 @@ pub struct CompiledNoirCircuit {
     pub file_map: Option<serde_json::Value>,
 }
 
+impl CompiledNoirCircuit {
+    /// Source locations of the opcodes, if the circuit has debug symbols
+    pub fn debug_info(&self) -> Result<Option<DebugInfo>, ArkworksError> {
+        let debug_symbols = self.debug_symbols.clone().map(Value::String);
+        debug_info(debug_symbols.as_ref(), self.file_map.as_ref())
+    }
+}
+
 /// Circuit ABI (Application Binary Interface)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CircuitAbi {
@@ -359,7 +943,7 @@ pub struct CircuitAbi {
     pub parameters: Vec<AbiParameter>,
     /// Return type (if any)
     #[serde(default)]
-    pub return_type: Option<AbiType>,
+    pub return_type: Option<AbiReturnType>,
     /// Error types for assertions
     #[serde(default)]
     pub error_types: serde_json::Value,
@@ -377,6 +961,15 @@ pub struct AbiParameter {
     pub visibility: AbiVisibility,
 }
 
+/// ABI return type
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AbiReturnType {
+    /// Type of the returned value
+    pub abi_type: AbiType,
+    /// Visibility of the return values
+    pub visibility: AbiVisibility,
+}
+
 /// ABI type
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "kind")]
@@ -392,11 +985,49 @@ pub enum AbiType {
     #[serde(rename = "array")]
     Array { length: u32, #[serde(rename = "type")] typ: Box<AbiType> },
     #[serde(rename = "struct")]
-    Struct { path: String, fields: Vec<(String, AbiType)> },
+    Struct {
+        path: String,
+        #[serde(with = "struct_fields")]
+        fields: Vec<(String, AbiType)>,
+    },
     #[serde(rename = "tuple")]
     Tuple { fields: Vec<AbiType> },
 }
 
+/// Noir serializes struct fields as `{ "name": .., "type": .. }` objects
+mod struct_fields {
+    use super::AbiType;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    struct StructField {
+        name: String,
+        #[serde(rename = "type")]
+        typ: AbiType,
+    }
+
+    pub fn serialize<S: Serializer>(
+        fields: &[(String, AbiType)],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        fields
+            .iter()
+            .map(|(name, typ)| StructField {
+                name: name.clone(),
+                typ: typ.clone(),
+            })
+            .collect::<Vec<_>>()
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<(String, AbiType)>, D::Error> {
+        let fields = Vec::<StructField>::deserialize(deserializer)?;
+        Ok(fields.into_iter().map(|f| (f.name, f.typ)).collect())
+    }
+}
+
 /// ABI visibility
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
@@ -406,3 +1037,160 @@ pub enum AbiVisibility {
     #[serde(rename = "databus")]
     DataBus,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acir_to_r1cs::{acir_to_r1cs, WitnessMap};
+    use crate::error::ArkworksError;
+    use crate::solver::solve_witness;
+    use serde_json::json;
+
+    fn program(opcodes: serde_json::Value) -> AcirProgram {
+        serde_json::from_value(json!({
+            "functions": [{
+                "current_witness_index": 3,
+                "opcodes": opcodes,
+                "private_parameters": [1, 2],
+                "public_parameters": { "witnesses": [] },
+                "return_values": { "witnesses": [3] }
+            }]
+        }))
+        .unwrap()
+    }
+
+    fn black_box(opcode: serde_json::Value) -> BlackBoxFuncCall {
+        match program(json!([opcode])).functions[0].opcodes[0].clone() {
+            Opcode::BlackBoxFuncCall(bb) => bb,
+            other => panic!("expected a black box, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_function_input_revisions() {
+        // The same AND in the shapes of successive ACIR revisions
+        let shapes = [
+            json!({
+                "type": "BlackBoxFuncCall", "name": "AND",
+                "lhs": { "witness": 1, "num_bits": 8 }, "rhs": { "witness": 2, "num_bits": 8 }, "output": 3
+            }),
+            json!({
+                "type": "BlackBoxFuncCall", "name": "AND",
+                "lhs": { "input": { "Witness": 1 }, "num_bits": 8 },
+                "rhs": { "input": { "Witness": 2 }, "num_bits": 8 }, "output": 3
+            }),
+            json!({
+                "type": "BlackBoxFuncCall",
+                "AND": { "lhs": { "Witness": 1 }, "rhs": { "Witness": 2 }, "num_bits": 8, "output": 3 }
+            }),
+        ];
+        for shape in shapes {
+            match black_box(shape) {
+                BlackBoxFuncCall::And { lhs, rhs, output } => {
+                    assert_eq!(lhs.input, ConstantOrWitness::Witness(1));
+                    assert_eq!(rhs.input, ConstantOrWitness::Witness(2));
+                    assert_eq!((lhs.num_bits, rhs.num_bits, output), (8, 8, 3));
+                }
+                other => panic!("expected AND, got {:?}", other),
+            }
+        }
+
+        // Bare byte inputs take their width from the black box
+        let bb = black_box(json!({
+            "type": "BlackBoxFuncCall",
+            "Blake2s": { "inputs": [{ "Witness": 1 }, { "Constant": "0x61" }], "outputs": [] }
+        }));
+        match bb {
+            BlackBoxFuncCall::Blake2s { inputs, .. } => {
+                assert!(inputs.iter().all(|input| input.num_bits == 8));
+                assert_eq!(inputs[1].input, ConstantOrWitness::Constant(Fr::from(0x61u64)));
+            }
+            other => panic!("expected Blake2s, got {:?}", other),
+        }
+
+        // Newer Poseidon2 calls leave out the length
+        let bb = black_box(json!({
+            "type": "BlackBoxFuncCall", "name": "Poseidon2Permutation",
+            "inputs": [{ "Witness": 1 }], "outputs": [2]
+        }));
+        assert!(matches!(bb, BlackBoxFuncCall::Poseidon2Permutation { len: None, .. }));
+    }
+
+    #[test]
+    fn test_constant_inputs() {
+        // w3 = w1 XOR 0x0f, with the constant range-checked when parsed
+        let program = program(json!([
+            { "type": "BlackBoxFuncCall", "name": "RANGE", "input": { "input": { "Constant": "0xff" }, "num_bits": 8 } },
+            {
+                "type": "BlackBoxFuncCall", "name": "XOR",
+                "lhs": { "witness": 1, "num_bits": 8 },
+                "rhs": { "input": { "Constant": "0x0f" }, "num_bits": 8 }, "output": 3
+            }
+        ]));
+        let r1cs = acir_to_r1cs(&program).unwrap();
+        let initial: WitnessMap = [(1, Fr::from(0x35u64)), (2, Fr::from(0u64))].into();
+        let solved = solve_witness(&program, &initial).unwrap();
+        assert_eq!(solved.witness[&3], Fr::from(0x3au64));
+
+        let mut witness = solved.into_r1cs_witness(&r1cs).unwrap();
+        r1cs.compute_aux_witnesses(&mut witness).unwrap();
+        assert!(r1cs.constraints.iter().all(|c| c.is_satisfied(&witness).unwrap()));
+
+        let too_wide = json!({
+            "type": "BlackBoxFuncCall", "name": "RANGE",
+            "input": { "input": { "Constant": "0x100" }, "num_bits": 8 }
+        });
+        let result: Result<Opcode, _> = serde_json::from_value(too_wide);
+        assert!(result.unwrap_err().to_string().contains("does not fit in 8 bits"));
+    }
+
+    #[test]
+    fn test_expression_without_constant() {
+        // w1 * w2 - w3 = 0, with q_c left out as for a zero constant
+        let program = program(json!([{
+            "type": "AssertZero",
+            "value": { "mul_terms": [["0x1", 1, 2]], "linear_combinations": [["-0x1", 3]] }
+        }]));
+        match &program.functions[0].opcodes[0] {
+            Opcode::AssertZero { value } => assert_eq!(value.q_c, "0x0"),
+            other => panic!("expected AssertZero, got {:?}", other),
+        }
+
+        let r1cs = acir_to_r1cs(&program).unwrap();
+        let initial: WitnessMap = [(1, Fr::from(6u64)), (2, Fr::from(7u64))].into();
+        let solved = solve_witness(&program, &initial).unwrap();
+        assert_eq!(solved.witness[&3], Fr::from(42u64));
+
+        let witness = solved.into_r1cs_witness(&r1cs).unwrap();
+        assert!(r1cs.constraints.iter().all(|c| c.is_satisfied(&witness).unwrap()));
+    }
+
+    #[test]
+    fn test_unknown_black_box_keeps_name() {
+        let bb = black_box(json!({ "type": "BlackBoxFuncCall", "name": "FancyHash", "inputs": [] }));
+        assert_eq!(bb.name(), "FancyHash");
+
+        let unknown = program(json!([
+            { "type": "BlackBoxFuncCall", "name": "FancyHash", "inputs": [] }
+        ]));
+        match acir_to_r1cs(&unknown).as_ref().map_err(ArkworksError::inner) {
+            Err(ArkworksError::UnsupportedOpcode(message)) => assert!(message.contains("FancyHash")),
+            other => panic!("expected an unsupported opcode, got {:?}", other.err()),
+        }
+
+        // AES128Encrypt is recognized, but has no gadget
+        let aes = program(json!([{
+            "type": "BlackBoxFuncCall",
+            "AES128Encrypt": {
+                "inputs": [{ "Witness": 1 }],
+                "iv": (0..16).map(|_| json!({ "Constant": "0x0" })).collect::<Vec<_>>(),
+                "key": (0..16).map(|_| json!({ "Witness": 2 })).collect::<Vec<_>>(),
+                "outputs": [3]
+            }
+        }]));
+        match acir_to_r1cs(&aes).as_ref().map_err(ArkworksError::inner) {
+            Err(ArkworksError::UnsupportedOpcode(message)) => assert!(message.contains("AES128Encrypt")),
+            other => panic!("expected an unsupported opcode, got {:?}", other.err()),
+        }
+    }
+}
@@ -0,0 +1,721 @@
+//! Brillig VM interpreter.
+//!
+//! Executes unconstrained Noir functions natively, so that the witnesses
+//! they compute (inverses, quotients, decompositions) no longer have to
+//! come from noir_js.
+
+use ark_bn254::Fr;
+use ark_ff::{PrimeField, Zero};
+use num_bigint::BigUint;
+use std::collections::HashMap;
+
+use crate::acir_to_r1cs::{evaluate_expression, parse_field_element, WitnessMap};
+use crate::acir_types::{
+    AcirProgram, BinaryFieldOp, BinaryIntOp, BitSize, BrilligBlackBoxOp, BrilligCall,
+    BrilligInputs, BrilligOpcode, BrilligOutputs, HeapVector, IntegerBitSize, MemoryAddress,
+};
+use crate::error::ArkworksError;
+
+/// Value stored in Brillig memory
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MemoryValue {
+    Field(Fr),
+    Integer(u128, IntegerBitSize),
+}
+
+impl Default for MemoryValue {
+    fn default() -> Self {
+        MemoryValue::Field(Fr::zero())
+    }
+}
+
+impl MemoryValue {
+    /// The value as a field element
+    pub fn to_field(self) -> Fr {
+        match self {
+            MemoryValue::Field(value) => value,
+            MemoryValue::Integer(value, _) => Fr::from(value),
+        }
+    }
+
+    /// The value as an unsigned integer (fields must fit in 128 bits)
+    fn to_u128(self) -> Result<u128, ArkworksError> {
+        match self {
+            MemoryValue::Integer(value, _) => Ok(value),
+            MemoryValue::Field(value) => {
+                let big: BigUint = value.into();
+                u128::try_from(big).map_err(|_| {
+                    ArkworksError::BrilligError(format!("Expected an integer, found field {}", value))
+                })
+            }
+        }
+    }
+
+    /// The value as a memory address or length
+    fn to_usize(self) -> Result<usize, ArkworksError> {
+        let value = self.to_u128()?;
+        usize::try_from(value)
+            .map_err(|_| ArkworksError::BrilligError(format!("Address {} out of range", value)))
+    }
+
+    fn is_truthy(self) -> bool {
+        !self.to_field().is_zero()
+    }
+
+    fn bool(value: bool) -> Self {
+        MemoryValue::Integer(value as u128, IntegerBitSize::U1)
+    }
+}
+
+/// Most memory a Brillig function may use, in values
+pub const MAX_MEMORY_SIZE: usize = 1 << 20;
+
+/// Most opcodes a Brillig function may execute before it is assumed not
+/// to terminate
+pub const MAX_STEPS: usize = 10_000_000;
+
+/// `base + offset` as a memory address, failing instead of overflowing
+fn add_address(base: usize, offset: usize) -> Result<usize, ArkworksError> {
+    base.checked_add(offset).ok_or_else(|| {
+        ArkworksError::BrilligError(format!("Address {} + {} overflows", base, offset))
+    })
+}
+
+/// Mask selecting the low `bits` bits of a u128
+fn mask(bit_size: IntegerBitSize) -> u128 {
+    match bit_size.bits() {
+        128 => u128::MAX,
+        bits => (1u128 << bits) - 1,
+    }
+}
+
+/// Interpreter state for a single Brillig function execution
+pub struct BrilligVm<'a> {
+    bytecode: &'a [BrilligOpcode],
+    calldata: Vec<Fr>,
+    memory: Vec<MemoryValue>,
+    pc: usize,
+    call_stack: Vec<usize>,
+}
+
+impl<'a> BrilligVm<'a> {
+    pub fn new(bytecode: &'a [BrilligOpcode], calldata: Vec<Fr>) -> Self {
+        Self {
+            bytecode,
+            calldata,
+            memory: Vec::new(),
+            pc: 0,
+            call_stack: Vec::new(),
+        }
+    }
+
+    /// Run until `Stop` and return the values it points at
+    ///
+    /// Fails after `MAX_STEPS` opcodes, so that a function that never stops
+    /// is reported instead of hanging the solver.
+    pub fn run(mut self) -> Result<Vec<Fr>, ArkworksError> {
+        for _ in 0..MAX_STEPS {
+            let opcode = self.bytecode.get(self.pc).ok_or_else(|| {
+                ArkworksError::BrilligError(format!("Program counter {} out of bounds", self.pc))
+            })?;
+            if let Some(return_data) = self.step(opcode)? {
+                return Ok(return_data);
+            }
+        }
+
+        Err(ArkworksError::BrilligError(format!(
+            "Execution did not stop within {} opcodes",
+            MAX_STEPS
+        )))
+    }
+
+    /// Resolve an address, applying the stack pointer to relative ones
+    fn resolve(&self, address: MemoryAddress) -> Result<usize, ArkworksError> {
+        match address {
+            MemoryAddress::Direct(addr) => Ok(addr),
+            MemoryAddress::Relative(offset) => add_address(self.read_at(0).to_usize()?, offset),
+        }
+    }
+
+    fn read_at(&self, addr: usize) -> MemoryValue {
+        self.memory.get(addr).copied().unwrap_or_default()
+    }
+
+    fn read(&self, address: MemoryAddress) -> Result<MemoryValue, ArkworksError> {
+        Ok(self.read_at(self.resolve(address)?))
+    }
+
+    fn write_at(&mut self, addr: usize, value: MemoryValue) -> Result<(), ArkworksError> {
+        if addr >= MAX_MEMORY_SIZE {
+            return Err(ArkworksError::BrilligError(format!(
+                "Write to address {} exceeds the {} value memory limit",
+                addr, MAX_MEMORY_SIZE
+            )));
+        }
+        if addr >= self.memory.len() {
+            self.memory.resize(addr + 1, MemoryValue::default());
+        }
+        self.memory[addr] = value;
+        Ok(())
+    }
+
+    fn write(&mut self, address: MemoryAddress, value: MemoryValue) -> Result<(), ArkworksError> {
+        let addr = self.resolve(address)?;
+        self.write_at(addr, value)
+    }
+
+    /// Read the values of a heap vector
+    fn read_vector(&self, vector: &HeapVector) -> Result<Vec<Fr>, ArkworksError> {
+        let start = self.read(vector.pointer)?.to_usize()?;
+        let size = self.read(vector.size)?.to_usize()?;
+        let end = add_address(start, size)?;
+        if end > MAX_MEMORY_SIZE {
+            return Err(ArkworksError::BrilligError(format!(
+                "Vector of {} values at address {} exceeds the {} value memory limit",
+                size, start, MAX_MEMORY_SIZE
+            )));
+        }
+        Ok((start..end).map(|addr| self.read_at(addr).to_field()).collect())
+    }
+
+    /// Execute one opcode, returning the return data on `Stop`
+    fn step(&mut self, opcode: &BrilligOpcode) -> Result<Option<Vec<Fr>>, ArkworksError> {
+        let mut next = self.pc + 1;
+
+        match opcode {
+            BrilligOpcode::BinaryFieldOp { destination, op, lhs, rhs } => {
+                let lhs = self.read(*lhs)?.to_field();
+                let rhs = self.read(*rhs)?.to_field();
+                let result = binary_field_op(*op, lhs, rhs)?;
+                self.write(*destination, result)?;
+            }
+            BrilligOpcode::BinaryIntOp { destination, op, bit_size, lhs, rhs } => {
+                let lhs = self.read(*lhs)?.to_u128()?;
+                let rhs = self.read(*rhs)?.to_u128()?;
+                let result = binary_int_op(*op, *bit_size, lhs, rhs)?;
+                self.write(*destination, result)?;
+            }
+            BrilligOpcode::Not { destination, source, bit_size } => {
+                let value = self.read(*source)?.to_u128()?;
+                self.write(*destination, MemoryValue::Integer(!value & mask(*bit_size), *bit_size))?;
+            }
+            BrilligOpcode::Cast { destination, source, bit_size } => {
+                let value = self.read(*source)?;
+                self.write(*destination, cast(value, *bit_size))?;
+            }
+            BrilligOpcode::JumpIfNot { condition, location } => {
+                if !self.read(*condition)?.is_truthy() {
+                    next = *location;
+                }
+            }
+            BrilligOpcode::JumpIf { condition, location } => {
+                if self.read(*condition)?.is_truthy() {
+                    next = *location;
+                }
+            }
+            BrilligOpcode::Jump { location } => {
+                next = *location;
+            }
+            BrilligOpcode::CalldataCopy { destination_address, size_address, offset_address } => {
+                let size = self.read(*size_address)?.to_usize()?;
+                let offset = self.read(*offset_address)?.to_usize()?;
+                let end = add_address(offset, size)?;
+                let values = self.calldata.get(offset..end).ok_or_else(|| {
+                    ArkworksError::BrilligError(format!(
+                        "Calldata copy of {} values at offset {} exceeds {} inputs",
+                        size,
+                        offset,
+                        self.calldata.len()
+                    ))
+                })?;
+                let values: Vec<Fr> = values.to_vec();
+                let start = self.resolve(*destination_address)?;
+                for (i, value) in values.into_iter().enumerate() {
+                    self.write_at(add_address(start, i)?, MemoryValue::Field(value))?;
+                }
+            }
+            BrilligOpcode::Call { location } => {
+                self.call_stack.push(next);
+                next = *location;
+            }
+            BrilligOpcode::Return => {
+                next = self.call_stack.pop().ok_or_else(|| {
+                    ArkworksError::BrilligError("Return with an empty call stack".to_string())
+                })?;
+            }
+            BrilligOpcode::Const { destination, bit_size, value } => {
+                let value = constant(value, *bit_size)?;
+                self.write(*destination, value)?;
+            }
+            BrilligOpcode::IndirectConst { destination_pointer, bit_size, value } => {
+                let value = constant(value, *bit_size)?;
+                let addr = self.read(*destination_pointer)?.to_usize()?;
+                self.write_at(addr, value)?;
+            }
+            BrilligOpcode::ForeignCall { function, destinations, .. } => {
+                // Printing is the only oracle that needs no response
+                if function != "print" || !destinations.is_empty() {
+                    return Err(ArkworksError::BrilligError(format!(
+                        "Unsupported foreign call '{}'",
+                        function
+                    )));
+                }
+            }
+            BrilligOpcode::Mov { destination, source } => {
+                let value = self.read(*source)?;
+                self.write(*destination, value)?;
+            }
+            BrilligOpcode::ConditionalMov { destination, source_a, source_b, condition } => {
+                let source = if self.read(*condition)?.is_truthy() { source_a } else { source_b };
+                let value = self.read(*source)?;
+                self.write(*destination, value)?;
+            }
+            BrilligOpcode::Load { destination, source_pointer } => {
+                let addr = self.read(*source_pointer)?.to_usize()?;
+                let value = self.read_at(addr);
+                self.write(*destination, value)?;
+            }
+            BrilligOpcode::Store { destination_pointer, source } => {
+                let addr = self.read(*destination_pointer)?.to_usize()?;
+                let value = self.read(*source)?;
+                self.write_at(addr, value)?;
+            }
+            BrilligOpcode::BlackBox(op) => {
+                self.black_box(op)?;
+            }
+            BrilligOpcode::Trap { revert_data } => {
+                let data = self.read_vector(revert_data)?;
+                return Err(ArkworksError::BrilligError(format!(
+                    "Trap at opcode {} with revert data {:?}",
+                    self.pc,
+                    data.iter().map(|v| v.to_string()).collect::<Vec<_>>()
+                )));
+            }
+            BrilligOpcode::Stop { return_data } => {
+                return Ok(Some(self.read_vector(return_data)?));
+            }
+        }
+
+        self.pc = next;
+        Ok(None)
+    }
+
+    fn black_box(&mut self, op: &BrilligBlackBoxOp) -> Result<(), ArkworksError> {
+        match op {
+            BrilligBlackBoxOp::ToRadix { input, radix, output_pointer, num_limbs, output_bits } => {
+                let mut value: BigUint = self.read(*input)?.to_field().into();
+                let radix = self.read(*radix)?.to_u128()?;
+                let num_limbs = self.read(*num_limbs)?.to_usize()?;
+                let output_bits = self.read(*output_bits)?.is_truthy();
+                if !(2..=256).contains(&radix) {
+                    return Err(ArkworksError::BrilligError(format!(
+                        "ToRadix radix {} must be between 2 and 256",
+                        radix
+                    )));
+                }
+
+                // Digits are written most significant first
+                let start = self.read(*output_pointer)?.to_usize()?;
+                let radix = BigUint::from(radix);
+                for i in (0..num_limbs).rev() {
+                    let digit = u128::try_from(&value % &radix).unwrap_or_default();
+                    value /= &radix;
+                    let limb = if output_bits {
+                        MemoryValue::Integer(digit, IntegerBitSize::U1)
+                    } else {
+                        MemoryValue::Integer(digit, IntegerBitSize::U8)
+                    };
+                    self.write_at(add_address(start, i)?, limb)?;
+                }
+                Ok(())
+            }
+            BrilligBlackBoxOp::Unsupported => Err(ArkworksError::BrilligError(
+                "Unsupported Brillig black box".to_string(),
+            )),
+        }
+    }
+}
+
+/// Parse a `Const` value into a memory value of the given type
+fn constant(value: &str, bit_size: BitSize) -> Result<MemoryValue, ArkworksError> {
+    let value = parse_field_element(value)?;
+    Ok(cast(MemoryValue::Field(value), bit_size))
+}
+
+/// Convert a value to the given type, truncating to the integer width
+fn cast(value: MemoryValue, bit_size: BitSize) -> MemoryValue {
+    match bit_size {
+        BitSize::Field => MemoryValue::Field(value.to_field()),
+        BitSize::Integer(bits) => {
+            let truncated = match value {
+                MemoryValue::Integer(v, _) => v & mask(bits),
+                MemoryValue::Field(v) => {
+                    let big: BigUint = v.into();
+                    let low = big & BigUint::from(mask(bits));
+                    u128::try_from(low).unwrap_or_default()
+                }
+            };
+            MemoryValue::Integer(truncated, bits)
+        }
+    }
+}
+
+fn binary_field_op(op: BinaryFieldOp, lhs: Fr, rhs: Fr) -> Result<MemoryValue, ArkworksError> {
+    let divide_by_zero = || ArkworksError::BrilligError("Attempted to divide by zero".to_string());
+    Ok(match op {
+        BinaryFieldOp::Add => MemoryValue::Field(lhs + rhs),
+        BinaryFieldOp::Sub => MemoryValue::Field(lhs - rhs),
+        BinaryFieldOp::Mul => MemoryValue::Field(lhs * rhs),
+        BinaryFieldOp::Div => {
+            let inverse = ark_ff::Field::inverse(&rhs).ok_or_else(divide_by_zero)?;
+            MemoryValue::Field(lhs * inverse)
+        }
+        BinaryFieldOp::IntegerDiv => {
+            if rhs.is_zero() {
+                return Err(divide_by_zero());
+            }
+            let quotient: BigUint = BigUint::from(lhs) / BigUint::from(rhs);
+            MemoryValue::Field(Fr::from(quotient))
+        }
+        // Field elements are ordered by their canonical integer representation
+        BinaryFieldOp::Equals => MemoryValue::bool(lhs == rhs),
+        BinaryFieldOp::LessThan => MemoryValue::bool(lhs.into_bigint() < rhs.into_bigint()),
+        BinaryFieldOp::LessThanEquals => MemoryValue::bool(lhs.into_bigint() <= rhs.into_bigint()),
+    })
+}
+
+fn binary_int_op(
+    op: BinaryIntOp,
+    bit_size: IntegerBitSize,
+    lhs: u128,
+    rhs: u128,
+) -> Result<MemoryValue, ArkworksError> {
+    let bits = bit_size.bits() as u128;
+    let m = mask(bit_size);
+    let value = match op {
+        BinaryIntOp::Add => lhs.wrapping_add(rhs) & m,
+        BinaryIntOp::Sub => lhs.wrapping_sub(rhs) & m,
+        BinaryIntOp::Mul => lhs.wrapping_mul(rhs) & m,
+        BinaryIntOp::Div => {
+            if rhs == 0 {
+                return Err(ArkworksError::BrilligError("Attempted to divide by zero".to_string()));
+            }
+            lhs / rhs
+        }
+        BinaryIntOp::Equals => return Ok(MemoryValue::bool(lhs == rhs)),
+        BinaryIntOp::LessThan => return Ok(MemoryValue::bool(lhs < rhs)),
+        BinaryIntOp::LessThanEquals => return Ok(MemoryValue::bool(lhs <= rhs)),
+        BinaryIntOp::And => lhs & rhs,
+        BinaryIntOp::Or => lhs | rhs,
+        BinaryIntOp::Xor => lhs ^ rhs,
+        BinaryIntOp::Shl => {
+            if rhs >= bits {
+                0
+            } else {
+                (lhs << rhs) & m
+            }
+        }
+        BinaryIntOp::Shr => {
+            if rhs >= bits {
+                0
+            } else {
+                lhs >> rhs
+            }
+        }
+    };
+    Ok(MemoryValue::Integer(value, bit_size))
+}
+
+/// Execute a `BrilligCall` opcode and write its outputs into the witness map
+///
+/// `memory_blocks` holds the current values of each ACIR memory block, for
+/// `MemoryArray` inputs. When the predicate is zero the function is not run
+/// and its outputs are set to zero, as in ACVM.
+pub fn solve_brillig_call(
+    call: &BrilligCall,
+    program: &AcirProgram,
+    witness: &mut WitnessMap,
+    memory_blocks: &HashMap<u32, Vec<Fr>>,
+) -> Result<(), ArkworksError> {
+    let outputs: Vec<u32> = call
+        .outputs
+        .iter()
+        .flat_map(|output| match output {
+            BrilligOutputs::Simple(w) => vec![*w],
+            BrilligOutputs::Array(ws) => ws.clone(),
+        })
+        .collect();
+
+    if let Some(predicate) = &call.predicate {
+        if evaluate_expression(predicate, witness)?.is_zero() {
+            for w in outputs {
+                witness.insert(w, Fr::zero());
+            }
+            return Ok(());
+        }
+    }
+
+    let function = program.unconstrained_functions.get(call.id as usize).ok_or_else(|| {
+        ArkworksError::InvalidInput(format!("No unconstrained function {} in ACIR", call.id))
+    })?;
+
+    let mut calldata = Vec::new();
+    for input in &call.inputs {
+        match input {
+            BrilligInputs::Single(expr) => calldata.push(evaluate_expression(expr, witness)?),
+            BrilligInputs::Array(exprs) => {
+                for expr in exprs {
+                    calldata.push(evaluate_expression(expr, witness)?);
+                }
+            }
+            BrilligInputs::MemoryArray(block_id) => {
+                let block = memory_blocks.get(block_id).ok_or_else(|| {
+                    ArkworksError::InvalidInput(format!(
+                        "Brillig input reads uninitialized memory block {}",
+                        block_id
+                    ))
+                })?;
+                calldata.extend_from_slice(block);
+            }
+        }
+    }
+
+    let return_data = BrilligVm::new(&function.bytecode, calldata).run()?;
+    if return_data.len() < outputs.len() {
+        return Err(ArkworksError::BrilligError(format!(
+            "Brillig function {} returned {} values, expected {}",
+            call.id,
+            return_data.len(),
+            outputs.len()
+        )));
+    }
+    for (w, value) in outputs.into_iter().zip(return_data) {
+        witness.insert(w, value);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acir_types::AcirProgram;
+    use serde_json::json;
+
+    fn program(bytecode: serde_json::Value) -> AcirProgram {
+        serde_json::from_value(json!({
+            "functions": [],
+            "unconstrained_functions": [{ "bytecode": bytecode }]
+        }))
+        .unwrap()
+    }
+
+    fn call(inputs: serde_json::Value, outputs: serde_json::Value, predicate: Option<u32>) -> BrilligCall {
+        let predicate = predicate.map(|w| json!({ "linear_combinations": [["0x1", w]], "q_c": "0x0" }));
+        serde_json::from_value(json!({
+            "id": 0,
+            "inputs": inputs,
+            "outputs": outputs,
+            "predicate": predicate
+        }))
+        .unwrap()
+    }
+
+    fn single(w: u32) -> serde_json::Value {
+        json!({ "Single": { "mul_terms": [], "linear_combinations": [["0x1", w]], "q_c": "0x0" } })
+    }
+
+    /// fn inverse(x) -> 1 / x
+    fn inverse_program() -> AcirProgram {
+        program(json!([
+            { "type": "Const", "destination": { "Direct": 10 }, "bit_size": { "Integer": "U32" }, "value": "0x1" },
+            { "type": "Const", "destination": { "Direct": 11 }, "bit_size": { "Integer": "U32" }, "value": "0x0" },
+            { "type": "CalldataCopy", "destination_address": { "Direct": 20 }, "size_address": { "Direct": 10 }, "offset_address": { "Direct": 11 } },
+            { "type": "Const", "destination": { "Direct": 1 }, "bit_size": "Field", "value": "0x1" },
+            { "type": "BinaryFieldOp", "destination": { "Direct": 21 }, "op": "Div", "lhs": { "Direct": 1 }, "rhs": { "Direct": 20 } },
+            { "type": "Const", "destination": { "Direct": 2 }, "bit_size": { "Integer": "U32" }, "value": "0x15" },
+            { "type": "Stop", "return_data": { "pointer": { "Direct": 2 }, "size": { "Direct": 10 } } }
+        ]))
+    }
+
+    #[test]
+    fn test_brillig_inverse() {
+        let program = inverse_program();
+        let call = call(json!([single(1)]), json!([{ "Simple": 2 }]), None);
+
+        let mut witness = WitnessMap::new();
+        witness.insert(1, Fr::from(7u64));
+        solve_brillig_call(&call, &program, &mut witness, &HashMap::new()).unwrap();
+
+        assert_eq!(witness[&2] * Fr::from(7u64), Fr::from(1u64));
+
+        // Division by zero traps
+        witness.insert(1, Fr::zero());
+        let result = solve_brillig_call(&call, &program, &mut witness, &HashMap::new());
+        assert!(matches!(result, Err(ArkworksError::BrilligError(_))));
+    }
+
+    #[test]
+    fn test_brillig_legacy_input_shape() {
+        // Internally tagged, as earlier releases read single inputs
+        let program = inverse_program();
+        let legacy = json!({ "type": "Single", "linear_combinations": [["0x1", 1]] });
+        let call = call(json!([legacy]), json!([{ "Simple": 2 }]), None);
+        assert!(matches!(call.inputs[0], BrilligInputs::Single(_)));
+
+        let mut witness = WitnessMap::new();
+        witness.insert(1, Fr::from(7u64));
+        solve_brillig_call(&call, &program, &mut witness, &HashMap::new()).unwrap();
+
+        assert_eq!(witness[&2] * Fr::from(7u64), Fr::from(1u64));
+    }
+
+    #[test]
+    fn test_brillig_predicate_false_skips_call() {
+        let program = inverse_program();
+        let call = call(json!([single(1)]), json!([{ "Simple": 2 }]), Some(3));
+
+        let mut witness = WitnessMap::new();
+        witness.insert(1, Fr::zero());
+        witness.insert(3, Fr::zero());
+        solve_brillig_call(&call, &program, &mut witness, &HashMap::new()).unwrap();
+
+        assert_eq!(witness[&2], Fr::zero());
+    }
+
+    #[test]
+    fn test_brillig_loop_and_call() {
+        // fn sum_below(n: u32) -> u32 { let mut s = 0; for i in 0..n { s = add(s, i) } s }
+        // where add is an internal Call using relative addresses off the stack pointer
+        let u32_size = json!({ "Integer": "U32" });
+        let program = program(json!([
+            // 0: stack pointer = 100, n = calldata[0] as u32
+            { "type": "Const", "destination": { "Direct": 0 }, "bit_size": u32_size, "value": "0x64" },
+            { "type": "Const", "destination": { "Direct": 10 }, "bit_size": u32_size, "value": "0x1" },
+            { "type": "Const", "destination": { "Direct": 11 }, "bit_size": u32_size, "value": "0x0" },
+            { "type": "CalldataCopy", "destination_address": { "Direct": 12 }, "size_address": { "Direct": 10 }, "offset_address": { "Direct": 11 } },
+            { "type": "Cast", "destination": { "Direct": 12 }, "source": { "Direct": 12 }, "bit_size": u32_size },
+            // 5: s = 0, i = 0
+            { "type": "Const", "destination": { "Direct": 13 }, "bit_size": u32_size, "value": "0x0" },
+            { "type": "Const", "destination": { "Direct": 14 }, "bit_size": u32_size, "value": "0x0" },
+            // 7: loop header: if !(i < n) goto 13
+            { "type": "BinaryIntOp", "destination": { "Direct": 15 }, "op": "LessThan", "bit_size": "U32", "lhs": { "Direct": 14 }, "rhs": { "Direct": 12 } },
+            { "type": "JumpIfNot", "condition": { "Direct": 15 }, "location": 13 },
+            // 9: s = add(s, i) via the stack frame
+            { "type": "Mov", "destination": { "Relative": 1 }, "source": { "Direct": 13 } },
+            { "type": "Mov", "destination": { "Relative": 2 }, "source": { "Direct": 14 } },
+            { "type": "Call", "location": 16 },
+            { "type": "Jump", "location": 19 },
+            // 13: return s
+            { "type": "Const", "destination": { "Direct": 16 }, "bit_size": u32_size, "value": "0xd" },
+            { "type": "Stop", "return_data": { "pointer": { "Direct": 16 }, "size": { "Direct": 10 } } },
+            { "type": "Return" },
+            // 16: add(a, b) = a + b stored back into s
+            { "type": "BinaryIntOp", "destination": { "Relative": 3 }, "op": "Add", "bit_size": "U32", "lhs": { "Relative": 1 }, "rhs": { "Relative": 2 } },
+            { "type": "Mov", "destination": { "Direct": 13 }, "source": { "Relative": 3 } },
+            { "type": "Return" },
+            // 19: i += 1, back to loop header
+            { "type": "BinaryIntOp", "destination": { "Direct": 14 }, "op": "Add", "bit_size": "U32", "lhs": { "Direct": 14 }, "rhs": { "Direct": 10 } },
+            { "type": "Jump", "location": 7 }
+        ]));
+        let call = call(json!([single(1)]), json!([{ "Simple": 2 }]), None);
+
+        let mut witness = WitnessMap::new();
+        witness.insert(1, Fr::from(10u64));
+        solve_brillig_call(&call, &program, &mut witness, &HashMap::new()).unwrap();
+
+        assert_eq!(witness[&2], Fr::from(45u64));
+    }
+
+    #[test]
+    fn test_brillig_to_radix_and_memory_array() {
+        // fn bytes(block) -> [u8; 2] of block[0], big-endian
+        let u32_size = json!({ "Integer": "U32" });
+        let program = program(json!([
+            { "type": "Const", "destination": { "Direct": 1 }, "bit_size": u32_size, "value": "0x1" },
+            { "type": "Const", "destination": { "Direct": 2 }, "bit_size": u32_size, "value": "0x0" },
+            { "type": "CalldataCopy", "destination_address": { "Direct": 10 }, "size_address": { "Direct": 1 }, "offset_address": { "Direct": 2 } },
+            { "type": "Const", "destination": { "Direct": 3 }, "bit_size": u32_size, "value": "0x100" },
+            { "type": "Const", "destination": { "Direct": 4 }, "bit_size": u32_size, "value": "0x14" },
+            { "type": "Const", "destination": { "Direct": 5 }, "bit_size": u32_size, "value": "0x2" },
+            { "type": "Const", "destination": { "Direct": 6 }, "bit_size": { "Integer": "U1" }, "value": "0x0" },
+            { "type": "BlackBox", "name": "ToRadix", "input": { "Direct": 10 }, "radix": { "Direct": 3 }, "output_pointer": { "Direct": 4 }, "num_limbs": { "Direct": 5 }, "output_bits": { "Direct": 6 } },
+            { "type": "Stop", "return_data": { "pointer": { "Direct": 4 }, "size": { "Direct": 5 } } }
+        ]));
+        let call = call(
+            json!([{ "MemoryArray": 0 }]),
+            json!([{ "Array": [1, 2] }]),
+            None,
+        );
+
+        let mut memory = HashMap::new();
+        memory.insert(0, vec![Fr::from(0x1234u64)]);
+        let mut witness = WitnessMap::new();
+        solve_brillig_call(&call, &program, &mut witness, &memory).unwrap();
+
+        assert_eq!(witness[&1], Fr::from(0x12u64));
+        assert_eq!(witness[&2], Fr::from(0x34u64));
+    }
+
+    /// Run a Brillig function without inputs or outputs, returning its error
+    fn run_error(bytecode: serde_json::Value) -> String {
+        let program = program(bytecode);
+        let call = call(json!([]), json!([]), None);
+        match solve_brillig_call(&call, &program, &mut WitnessMap::new(), &HashMap::new()) {
+            Err(ArkworksError::BrilligError(message)) => message,
+            other => panic!("expected a Brillig error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_brillig_address_overflow() {
+        let u64_size = json!({ "Integer": "U64" });
+        let max = format!("0x{:x}", usize::MAX);
+
+        // Copying one calldata value from offset usize::MAX
+        let message = run_error(json!([
+            { "type": "Const", "destination": { "Direct": 1 }, "bit_size": u64_size, "value": "0x1" },
+            { "type": "Const", "destination": { "Direct": 2 }, "bit_size": u64_size, "value": max },
+            { "type": "CalldataCopy", "destination_address": { "Direct": 10 }, "size_address": { "Direct": 1 }, "offset_address": { "Direct": 2 } }
+        ]));
+        assert!(message.contains("overflows"), "{}", message);
+
+        // Returning two values from address usize::MAX
+        let message = run_error(json!([
+            { "type": "Const", "destination": { "Direct": 1 }, "bit_size": u64_size, "value": "0x2" },
+            { "type": "Const", "destination": { "Direct": 2 }, "bit_size": u64_size, "value": max },
+            { "type": "Stop", "return_data": { "pointer": { "Direct": 2 }, "size": { "Direct": 1 } } }
+        ]));
+        assert!(message.contains("overflows"), "{}", message);
+
+        // A relative address past the end of the address space
+        let message = run_error(json!([
+            { "type": "Const", "destination": { "Direct": 0 }, "bit_size": u64_size, "value": max },
+            { "type": "Const", "destination": { "Relative": 1 }, "bit_size": "Field", "value": "0x1" }
+        ]));
+        assert!(message.contains("overflows"), "{}", message);
+    }
+
+    #[test]
+    fn test_brillig_memory_limit() {
+        let u64_size = json!({ "Integer": "U64" });
+        let message = run_error(json!([
+            { "type": "Const", "destination": { "Direct": 1 }, "bit_size": u64_size, "value": "0x10000000000" },
+            { "type": "Store", "destination_pointer": { "Direct": 1 }, "source": { "Direct": 1 } }
+        ]));
+        assert!(message.contains("memory limit"), "{}", message);
+
+        // Return data is read into memory too
+        let message = run_error(json!([
+            { "type": "Const", "destination": { "Direct": 1 }, "bit_size": u64_size, "value": "0x10000000000" },
+            { "type": "Stop", "return_data": { "pointer": { "Direct": 2 }, "size": { "Direct": 1 } } }
+        ]));
+        assert!(message.contains("memory limit"), "{}", message);
+    }
+
+    #[test]
+    fn test_brillig_step_limit() {
+        let message = run_error(json!([{ "type": "Jump", "location": 0 }]));
+        assert!(message.contains("did not stop"), "{}", message);
+    }
+}
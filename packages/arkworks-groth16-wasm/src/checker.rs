@@ -0,0 +1,262 @@
+//! Witness checker.
+//!
+//! Evaluates every constraint of a circuit against a witness before
+//! proving. The first constraint that fails is reported with the ACIR
+//! opcode it came from and its evaluated A, B and C, so a bad witness can
+//! be traced back to the circuit rather than surfacing as a proving error.
+
+use ark_bn254::Fr;
+use ark_ff::Zero;
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::acir_to_r1cs::{AcirR1cs, OpcodeOrigin, WitnessMap};
+use crate::error::ArkworksError;
+
+/// Outcome of checking a witness against a circuit
+#[derive(Clone, Debug)]
+pub struct WitnessReport {
+    /// Number of constraints checked
+    pub num_constraints: usize,
+    /// Witnesses that constraints need but that have no value, in the
+    /// caller's numbering
+    pub missing_witnesses: Vec<u32>,
+    /// Auxiliary witness computations that failed for a reason other than
+    /// a missing witness, such as a value out of range
+    pub aux_errors: Vec<String>,
+    /// First constraint the witness violates
+    pub failure: Option<ConstraintFailure>,
+}
+
+impl WitnessReport {
+    /// Whether the witness is complete and satisfies every constraint
+    pub fn is_satisfied(&self) -> bool {
+        self.missing_witnesses.is_empty() && self.aux_errors.is_empty() && self.failure.is_none()
+    }
+}
+
+/// A constraint A * B = C that does not hold
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConstraintFailure {
+    /// Index of the constraint
+    pub constraint: usize,
+    /// ACIR opcode the constraint was converted from, if known
+    pub origin: Option<OpcodeOrigin>,
+    /// Assertion message of that opcode, if it has one
+    pub assert_message: Option<String>,
+    pub a: Fr,
+    pub b: Fr,
+    pub c: Fr,
+}
+
+impl ConstraintFailure {
+    fn new(r1cs: &AcirR1cs, constraint: usize, (a, b, c): (Fr, Fr, Fr)) -> Self {
+        let origin = r1cs.origins.get(constraint).copied();
+        Self {
+            constraint,
+            origin,
+            assert_message: origin.and_then(|o| r1cs.assert_messages.get(&o).cloned()),
+            a,
+            b,
+            c,
+        }
+    }
+}
+
+impl fmt::Display for ConstraintFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Constraint {}", self.constraint)?;
+        if let Some(origin) = self.origin {
+            write!(f, " (opcode {} of function {})", origin.opcode, origin.function)?;
+        }
+        write!(f, " is not satisfied")?;
+        if let Some(message) = &self.assert_message {
+            write!(f, " \"{}\"", message)?;
+        }
+        write!(f, ": A = {}, B = {}, C = {}", self.a, self.b, self.c)
+    }
+}
+
+/// Check a witness against every constraint of a circuit
+///
+/// `witness` is numbered like the ACIR witnesses, as for proving; the
+/// auxiliary witnesses are computed as far as it allows. Unlike proving,
+/// checking carries on past missing values and failed computations, so
+/// the report lists everything the witness lacks.
+pub fn check_witness(r1cs: &AcirR1cs, witness: &WitnessMap) -> WitnessReport {
+    let mut witness = witness.clone();
+    let mut aux_errors = Vec::new();
+    for computation in &r1cs.aux_witnesses {
+        match computation.compute(&mut witness) {
+            Ok(()) | Err(ArkworksError::MissingWitness(_)) => {}
+            Err(err) => aux_errors.push(err.to_string()),
+        }
+    }
+
+    // Number the values like the constraints
+    let witness: WitnessMap = match &r1cs.renumbering {
+        None => witness,
+        Some(original) => (1..original.len())
+            .filter_map(|idx| witness.get(&original[idx]).map(|&value| (idx as u32, value)))
+            .collect(),
+    };
+
+    let mut missing = BTreeSet::new();
+    let mut failure = None;
+    for (i, constraint) in r1cs.constraints.iter().enumerate() {
+        let a = evaluate(&constraint.a, &witness, &mut missing);
+        let b = evaluate(&constraint.b, &witness, &mut missing);
+        let c = evaluate(&constraint.c, &witness, &mut missing);
+        if let (Some(a), Some(b), Some(c), None) = (a, b, c, &failure) {
+            if a * b != c {
+                failure = Some(ConstraintFailure::new(r1cs, i, (a, b, c)));
+            }
+        }
+    }
+
+    let missing_witnesses = match &r1cs.renumbering {
+        None => missing.into_iter().collect(),
+        Some(original) => missing.into_iter().map(|idx| original[idx as usize]).collect(),
+    };
+    WitnessReport {
+        num_constraints: r1cs.constraints.len(),
+        missing_witnesses,
+        aux_errors,
+        failure,
+    }
+}
+
+/// First constraint a complete witness, numbered like the constraints,
+/// violates
+pub fn first_failure(
+    r1cs: &AcirR1cs,
+    witness: &WitnessMap,
+) -> Result<Option<ConstraintFailure>, ArkworksError> {
+    for (i, constraint) in r1cs.constraints.iter().enumerate() {
+        let (a, b, c) = constraint.evaluate(witness)?;
+        if a * b != c {
+            return Ok(Some(ConstraintFailure::new(r1cs, i, (a, b, c))));
+        }
+    }
+    Ok(None)
+}
+
+/// Evaluate a linear combination, noting the witnesses it lacks
+fn evaluate(terms: &[(Fr, u32)], witness: &WitnessMap, missing: &mut BTreeSet<u32>) -> Option<Fr> {
+    let mut sum = Fr::zero();
+    let mut complete = true;
+    for &(coeff, idx) in terms {
+        match witness.get(&idx) {
+            _ if idx == 0 => sum += coeff,
+            Some(value) => sum += coeff * value,
+            None => {
+                missing.insert(idx);
+                complete = false;
+            }
+        }
+    }
+    complete.then_some(sum)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acir_to_r1cs::acir_to_r1cs;
+    use crate::acir_types::AcirProgram;
+    use crate::optimizer::optimize;
+    use ark_ff::One;
+    use serde_json::json;
+
+    fn minus_one() -> String {
+        format!("0x{}", hex::encode(crate::gnark_compat::fr_to_be_bytes(&-Fr::one())))
+    }
+
+    /// main: w3 = w1 * w2; w3 = w4 + 5, with a message; w5 = double(w3)
+    /// double: w2 = 2 * w1, with an error selector
+    fn program() -> AcirProgram {
+        serde_json::from_value(json!({
+            "functions": [
+                {
+                    "current_witness_index": 5,
+                    "opcodes": [
+                        { "type": "AssertZero", "value": { "mul_terms": [["0x1", 1, 2]], "linear_combinations": [[minus_one(), 3]], "q_c": "0x0" } },
+                        { "type": "AssertZero", "value": { "linear_combinations": [["0x1", 3], [minus_one(), 4]], "q_c": "-0x5" } },
+                        {
+                            "type": "Call", "id": 1,
+                            "inputs": [{ "linear_combinations": [["0x1", 3]], "q_c": "0x0" }],
+                            "outputs": [5]
+                        }
+                    ],
+                    "private_parameters": [1, 2],
+                    "public_parameters": { "witnesses": [] },
+                    "return_values": { "witnesses": [5] },
+                    "assert_messages": [[{ "Acir": 1 }, { "StaticString": "w3 is w4 + 5" }]]
+                },
+                {
+                    "current_witness_index": 2,
+                    "opcodes": [
+                        { "type": "AssertZero", "value": { "linear_combinations": [["0x2", 1], [minus_one(), 2]], "q_c": "0x0" } }
+                    ],
+                    "private_parameters": [1],
+                    "public_parameters": { "witnesses": [] },
+                    "return_values": { "witnesses": [2] },
+                    "assert_messages": [["0", { "error_selector": 7, "payload": [] }]]
+                }
+            ],
+            "unconstrained_functions": []
+        }))
+        .unwrap()
+    }
+
+    fn witness(values: &[(u32, u64)]) -> WitnessMap {
+        values.iter().map(|&(w, v)| (w, Fr::from(v))).collect()
+    }
+
+    #[test]
+    fn test_check_witness_reports_first_failure() {
+        let r1cs = acir_to_r1cs(&program()).unwrap();
+        assert_eq!(r1cs.origins.len(), r1cs.constraints.len());
+
+        let good = witness(&[(1, 3), (2, 4), (3, 12), (4, 7), (5, 24)]);
+        let report = check_witness(&r1cs, &good);
+        assert!(report.is_satisfied(), "{:?}", report);
+        assert_eq!(report.num_constraints, r1cs.constraints.len());
+
+        // w4 is off by one: opcode 1 fails with its message
+        let report = check_witness(&r1cs, &witness(&[(1, 3), (2, 4), (3, 12), (4, 8), (5, 24)]));
+        let failure = report.failure.unwrap();
+        assert_eq!(failure.origin, Some(OpcodeOrigin { function: 0, opcode: 1 }));
+        assert_eq!(failure.assert_message.as_deref(), Some("w3 is w4 + 5"));
+        assert_eq!((failure.a, failure.b, failure.c), (-Fr::one(), Fr::one(), Fr::from(0u64)));
+        assert_eq!(
+            failure.to_string(),
+            format!("Constraint {} (opcode 1 of function 0) is not satisfied \"w3 is w4 + 5\": A = {}, B = 1, C = 0", failure.constraint, -Fr::one())
+        );
+
+        // Failures inside an inlined call point at the callee
+        let report = check_witness(&r1cs, &witness(&[(1, 3), (2, 4), (3, 12), (4, 7), (5, 25)]));
+        let failure = report.failure.unwrap();
+        assert_eq!(failure.origin.map(|o| o.function), Some(1));
+        assert_eq!(failure.assert_message.as_deref(), Some("assertion with error selector 7"));
+        assert!(report.missing_witnesses.is_empty());
+
+        // Missing witnesses are all listed, and what can be checked still is
+        let report = check_witness(&r1cs, &witness(&[(1, 3), (2, 5), (3, 12), (5, 24)]));
+        assert_eq!(report.missing_witnesses, vec![4]);
+        assert_eq!(report.failure.unwrap().origin, Some(OpcodeOrigin { function: 0, opcode: 0 }));
+    }
+
+    #[test]
+    fn test_check_optimized_witness() {
+        let r1cs = optimize(&acir_to_r1cs(&program()).unwrap());
+        assert_eq!(r1cs.origins.len(), r1cs.constraints.len());
+
+        let report = check_witness(&r1cs, &witness(&[(1, 3), (2, 4), (3, 12), (4, 7), (5, 24)]));
+        assert!(report.is_satisfied(), "{:?}", report);
+
+        // Missing witnesses keep the caller's numbering
+        let report = check_witness(&r1cs, &witness(&[(1, 3), (3, 12), (4, 7), (5, 24)]));
+        assert_eq!(report.missing_witnesses, vec![2]);
+        assert!(report.failure.is_none());
+    }
+}
@@ -0,0 +1,354 @@
+//! Noir debug symbols.
+//!
+//! Maps ACIR opcodes back to the Noir source that produced them, using the
+//! `debug_symbols` and `file_map` nargo writes next to the bytecode. The
+//! debug symbols are base64-encoded, deflated JSON holding one entry per
+//! ACIR function; both the older per-opcode `locations` lists and the newer
+//! `acir_locations` into a shared `location_tree` are read.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use flate2::read::DeflateDecoder;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Read;
+
+use crate::acir_to_r1cs::OpcodeOrigin;
+use crate::error::ArkworksError;
+
+/// Source locations of the opcodes of a program
+#[derive(Clone, Debug, Default)]
+pub struct DebugInfo {
+    /// Call stack of each ACIR opcode, outermost call first, by function
+    functions: Vec<BTreeMap<usize, Vec<Span>>>,
+    /// Source files by file id
+    files: BTreeMap<u64, SourceFile>,
+}
+
+/// Start of a byte range of a source file
+#[derive(Clone, Copy, Debug)]
+struct Span {
+    file: u64,
+    start: usize,
+}
+
+#[derive(Clone, Debug)]
+struct SourceFile {
+    path: String,
+    source: String,
+}
+
+/// A position in a Noir source file
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct SourceLocation {
+    pub path: String,
+    /// 1-based line
+    pub line: usize,
+    /// 1-based column, in characters
+    pub column: usize,
+    /// The source line, trimmed
+    pub snippet: String,
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.path, self.line, self.column)
+    }
+}
+
+impl DebugInfo {
+    /// Read the debug symbols and file map of a compiled program
+    ///
+    /// `debug_symbols` is either the compressed string nargo writes or the
+    /// JSON it decompresses to.
+    pub fn parse(debug_symbols: &Value, file_map: &Value) -> Result<Self, ArkworksError> {
+        let debug_symbols = match debug_symbols {
+            Value::String(encoded) => {
+                let compressed = STANDARD.decode(encoded)?;
+                let mut json = String::new();
+                DeflateDecoder::new(&compressed[..])
+                    .read_to_string(&mut json)
+                    .map_err(|e| {
+                        ArkworksError::ParseError(format!("Cannot inflate debug symbols: {}", e))
+                    })?;
+                serde_json::from_str(&json)?
+            }
+            other => other.clone(),
+        };
+
+        let functions = debug_symbols
+            .get("debug_infos")
+            .and_then(Value::as_array)
+            .ok_or_else(|| ArkworksError::ParseError("Debug symbols have no debug_infos".into()))?
+            .iter()
+            .map(opcode_locations)
+            .collect();
+
+        let files = file_map
+            .as_object()
+            .into_iter()
+            .flatten()
+            .filter_map(|(id, file)| {
+                let file = SourceFile {
+                    path: file.get("path")?.as_str()?.to_string(),
+                    source: file.get("source")?.as_str()?.to_string(),
+                };
+                Some((id.parse().ok()?, file))
+            })
+            .collect();
+
+        Ok(DebugInfo { functions, files })
+    }
+
+    /// Noir call stack of an opcode, innermost call first
+    pub fn call_stack(&self, origin: OpcodeOrigin) -> Vec<SourceLocation> {
+        self.functions
+            .get(origin.function as usize)
+            .and_then(|opcodes| opcodes.get(&origin.opcode))
+            .into_iter()
+            .flatten()
+            .rev()
+            .filter_map(|span| self.resolve(span))
+            .collect()
+    }
+
+    /// Noir source an opcode was compiled from
+    pub fn locate(&self, origin: OpcodeOrigin) -> Option<SourceLocation> {
+        self.call_stack(origin).into_iter().next()
+    }
+
+    /// Message of an error followed by the Noir call stack of the opcode it
+    /// arose at, if known
+    pub fn describe(&self, err: &ArkworksError) -> String {
+        let mut message = err.to_string();
+        for location in err.origin().map(|o| self.call_stack(o)).unwrap_or_default() {
+            message.push_str(&format!("\n  at {}: {}", location, location.snippet));
+        }
+        message
+    }
+
+    fn resolve(&self, span: &Span) -> Option<SourceLocation> {
+        let file = self.files.get(&span.file)?;
+        let before = file.source.get(..span.start)?;
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        let line_end = file.source[span.start..]
+            .find('\n')
+            .map_or(file.source.len(), |i| span.start + i);
+        Some(SourceLocation {
+            path: file.path.clone(),
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            snippet: file.source[line_start..line_end].trim().to_string(),
+        })
+    }
+}
+
+/// Call stacks of the ACIR opcodes of one function's debug info
+///
+/// Brillig locations ("3.5") are skipped, as are entries that don't parse.
+fn opcode_locations(debug_info: &Value) -> BTreeMap<usize, Vec<Span>> {
+    // Older debug info lists each opcode's call stack directly
+    if let Some(locations) = debug_info.get("locations").and_then(Value::as_object) {
+        return locations
+            .iter()
+            .filter_map(|(opcode, stack)| {
+                let stack = stack.as_array()?.iter().filter_map(span).collect();
+                Some((opcode.parse().ok()?, stack))
+            })
+            .collect();
+    }
+
+    // Newer debug info points each opcode at a node of a location tree,
+    // whose parents are the enclosing calls; the root holds no location
+    let nodes = debug_info
+        .pointer("/location_tree/locations")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let call_stack = |node: usize| {
+        let mut stack = Vec::new();
+        let mut node = Some(node);
+        // Bounded, in case malformed parents form a cycle
+        for _ in 0..nodes.len() {
+            let Some(entry) = node.and_then(|id| nodes.get(id)) else {
+                break;
+            };
+            node = entry.get("parent").and_then(index);
+            if node.is_some() {
+                stack.extend(entry.get("value").and_then(span));
+            }
+        }
+        stack.reverse();
+        stack
+    };
+    // Either a map or a list of [opcode, node] pairs
+    let entries: Vec<(Option<usize>, Option<usize>)> = match debug_info.get("acir_locations") {
+        Some(Value::Object(map)) => map
+            .iter()
+            .map(|(opcode, node)| (opcode.parse().ok(), index(node)))
+            .collect(),
+        Some(Value::Array(pairs)) => pairs
+            .iter()
+            .filter_map(|pair| match pair.as_array()?.as_slice() {
+                [opcode, node] => Some((index(opcode), index(node))),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    entries
+        .into_iter()
+        .filter_map(|(opcode, node)| Some((opcode?, call_stack(node?))))
+        .collect()
+}
+
+/// Parse a `{ span: { start, end }, file }` location
+fn span(location: &Value) -> Option<Span> {
+    let range = location.get("span")?;
+    Some(Span {
+        file: location.get("file")?.as_u64()?,
+        start: index(range.get("start")?)?,
+    })
+}
+
+/// An index written as a number or a string
+fn index(value: &Value) -> Option<usize> {
+    match value {
+        Value::Number(n) => n.as_u64().map(|n| n as usize),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acir_to_r1cs::{acir_to_r1cs, WitnessMap};
+    use crate::acir_types::AcirProgram;
+    use crate::checker;
+    use ark_bn254::Fr;
+    use flate2::write::DeflateEncoder;
+    use flate2::Compression;
+    use serde_json::json;
+    use std::io::Write;
+
+    const SOURCE: &str = "fn main(x: Field, y: pub Field) {\n    check(x, y);\n}\n\nfn check(a: Field, b: Field) {\n    assert(a * a == b, \"not a square\");\n}\n";
+
+    fn location(text: &str) -> Value {
+        let start = SOURCE.find(text).unwrap();
+        json!({ "span": { "start": start, "end": start + text.len() }, "file": 3 })
+    }
+
+    fn file_map() -> Value {
+        json!({ "3": { "source": SOURCE, "path": "src/main.nr" } })
+    }
+
+    /// Compress debug symbols the way nargo does
+    fn compress(debug_symbols: &Value) -> Value {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(debug_symbols.to_string().as_bytes()).unwrap();
+        Value::String(STANDARD.encode(encoder.finish().unwrap()))
+    }
+
+    /// Opcode 0 is the assertion, reached through the call to check
+    fn old_symbols() -> Value {
+        json!({ "debug_infos": [{
+            "locations": {
+                "0": [location("check(x, y)"), location("assert(a * a == b")],
+                "0.1": [location("fn main")]
+            }
+        }]})
+    }
+
+    fn new_symbols() -> Value {
+        json!({ "debug_infos": [{
+            "acir_locations": [[0, 2]],
+            "location_tree": { "locations": [
+                { "parent": null, "value": { "span": { "start": 0, "end": 0 }, "file": 0 } },
+                { "parent": 0, "value": location("check(x, y)") },
+                { "parent": 1, "value": location("assert(a * a == b") }
+            ]}
+        }]})
+    }
+
+    fn expected_stack() -> Vec<SourceLocation> {
+        vec![
+            SourceLocation {
+                path: "src/main.nr".into(),
+                line: 6,
+                column: 5,
+                snippet: "assert(a * a == b, \"not a square\");".into(),
+            },
+            SourceLocation {
+                path: "src/main.nr".into(),
+                line: 2,
+                column: 5,
+                snippet: "check(x, y);".into(),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_parse_debug_symbols() {
+        let origin = OpcodeOrigin { function: 0, opcode: 0 };
+        for symbols in [old_symbols(), new_symbols()] {
+            for symbols in [compress(&symbols), symbols] {
+                let debug = DebugInfo::parse(&symbols, &file_map()).unwrap();
+                assert_eq!(debug.call_stack(origin), expected_stack());
+                assert_eq!(debug.locate(origin).unwrap().to_string(), "src/main.nr:6:5");
+                assert!(debug.locate(OpcodeOrigin { function: 0, opcode: 1 }).is_none());
+                assert!(debug.locate(OpcodeOrigin { function: 1, opcode: 0 }).is_none());
+            }
+        }
+
+        assert!(DebugInfo::parse(&json!("not base64!"), &file_map()).is_err());
+        assert!(DebugInfo::parse(&json!({}), &file_map()).is_err());
+    }
+
+    fn program(opcode: Value) -> AcirProgram {
+        serde_json::from_value(json!({
+            "functions": [{
+                "current_witness_index": 2,
+                "opcodes": [opcode],
+                "private_parameters": [1],
+                "public_parameters": { "witnesses": [2] },
+                "return_values": { "witnesses": [] }
+            }],
+            "unconstrained_functions": [],
+            "debug_symbols": compress(&old_symbols()),
+            "file_map": file_map()
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_errors_point_at_source() {
+        // w1 * w1 - w2 = 0
+        let square = program(json!({
+            "type": "AssertZero",
+            "value": { "mul_terms": [["0x1", 1, 1]], "linear_combinations": [["-0x1", 2]], "q_c": "0x0" }
+        }));
+        let debug = square.debug_info().unwrap().unwrap();
+        let r1cs = acir_to_r1cs(&square).unwrap();
+        let witness: WitnessMap = [(1, Fr::from(3u64)), (2, Fr::from(10u64))].into_iter().collect();
+        let failure = checker::first_failure(&r1cs, &witness).unwrap().unwrap();
+        let err = ArkworksError::UnsatisfiedConstraint(Box::new(failure));
+        assert_eq!(
+            debug.describe(&err),
+            format!(
+                "{}\n  at src/main.nr:6:5: assert(a * a == b, \"not a square\");\n  at src/main.nr:2:5: check(x, y);",
+                err
+            )
+        );
+
+        let unsupported = program(json!({ "type": "BlackBoxFuncCall", "name": "FancyHash", "inputs": [] }));
+        let err = acir_to_r1cs(&unsupported).err().unwrap();
+        assert!(matches!(err.inner(), ArkworksError::UnsupportedOpcode(_)));
+        assert!(debug.describe(&err).ends_with("\n  at src/main.nr:2:5: check(x, y);"));
+
+        // Errors without an opcode are left as they are
+        let err = ArkworksError::InvalidInput("bad".into());
+        assert_eq!(debug.describe(&err), err.to_string());
+    }
+}
@@ -3,6 +3,10 @@
 use thiserror::Error;
 use wasm_bindgen::JsValue;
 
+use crate::acir_to_r1cs::OpcodeOrigin;
+use crate::checker::ConstraintFailure;
+use crate::linter::LintWarning;
+
 /// Errors that can occur during proof generation/verification
 #[derive(Error, Debug)]
 pub enum ArkworksError {
@@ -30,10 +34,68 @@ pub enum ArkworksError {
     #[error("Missing witness value for index {0}")]
     MissingWitness(u32),
 
+    #[error("Cannot solve opcode {opcode} of function {function}: {reason}")]
+    UnsolvableOpcode {
+        function: u32,
+        opcode: usize,
+        reason: String,
+    },
+
+    #[error("Opcode {opcode} of function {function}: {source}")]
+    AtOpcode {
+        function: u32,
+        opcode: usize,
+        source: Box<ArkworksError>,
+    },
+
+    #[error("{0}")]
+    UnsatisfiedConstraint(Box<ConstraintFailure>),
+
+    #[error("Circuit is under-constrained: {}", crate::linter::summary(.0))]
+    UnderConstrained(Vec<LintWarning>),
+
+    #[error("Brillig execution error: {0}")]
+    BrilligError(String),
+
     #[error("WASM error: {0}")]
     WasmError(String),
 }
 
+impl ArkworksError {
+    /// Attach the ACIR opcode the error arose at, unless it already names one
+    pub(crate) fn at_opcode(self, origin: OpcodeOrigin) -> Self {
+        if self.origin().is_some() {
+            return self;
+        }
+        ArkworksError::AtOpcode {
+            function: origin.function,
+            opcode: origin.opcode,
+            source: Box::new(self),
+        }
+    }
+
+    /// ACIR opcode the error arose at, if known
+    pub fn origin(&self) -> Option<OpcodeOrigin> {
+        match self {
+            ArkworksError::AtOpcode { function, opcode, .. }
+            | ArkworksError::UnsolvableOpcode { function, opcode, .. } => Some(OpcodeOrigin {
+                function: *function,
+                opcode: *opcode,
+            }),
+            ArkworksError::UnsatisfiedConstraint(failure) => failure.origin,
+            _ => None,
+        }
+    }
+
+    /// The error without the opcode location `at_opcode` attached
+    pub fn inner(&self) -> &ArkworksError {
+        match self {
+            ArkworksError::AtOpcode { source, .. } => source.inner(),
+            _ => self,
+        }
+    }
+}
+
 impl From<ArkworksError> for JsValue {
     fn from(err: ArkworksError) -> Self {
         JsValue::from_str(&err.to_string())
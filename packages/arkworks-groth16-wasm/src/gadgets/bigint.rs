@@ -0,0 +1,354 @@
+//! BigInt black boxes: integers modulo a prime other than the native field.
+//!
+//! `BigIntFromLeBytes` gives an id to the integer read from little-endian
+//! bytes, together with the modulus of its operations; the integer itself
+//! is kept as is. `BigIntAdd/Sub/Mul/Div` combine two ids of the same
+//! modulus into a new id holding the residue in [0, modulus), and
+//! `BigIntToLeBytes` writes an id out as 32 little-endian bytes. Ids are
+//! local to an ACIR function, as in ACVM.
+//!
+//! Moduli must have between 192 and 256 bits: below that, the quotient of
+//! a product of reduced elements is too wide for the `nonnative` column
+//! equations. Division uses Fermat inverses, so it needs a prime modulus,
+//! and division by 0 gives 0 as in ACVM. Each operation costs about 2,000
+//! constraints and division about twice that.
+
+use ark_ff::Zero;
+use num_bigint::BigUint;
+use std::collections::HashMap;
+
+use super::bits;
+use super::nonnative::{self, Element};
+use crate::acir_to_r1cs::R1csBuilder;
+use crate::acir_types::FunctionInput;
+use crate::error::ArkworksError;
+
+/// Bytes written by `BigIntToLeBytes`, and the most read by
+/// `BigIntFromLeBytes`
+pub const BYTES: usize = 32;
+
+/// Supported modulus sizes, in bits
+const MIN_MODULUS_BITS: u64 = 192;
+const MAX_MODULUS_BITS: u64 = 256;
+
+/// Arithmetic operation of a BigInt black box
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operation {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+/// Read a modulus from little-endian bytes and check it is supported
+fn parse_modulus(bytes: &[u8]) -> Result<BigUint, ArkworksError> {
+    let modulus = BigUint::from_bytes_le(bytes);
+    if !(MIN_MODULUS_BITS..=MAX_MODULUS_BITS).contains(&modulus.bits()) {
+        return Err(ArkworksError::UnsupportedOpcode(format!(
+            "BigInt modulus of {} bits, only {} to {} bits are supported",
+            modulus.bits(),
+            MIN_MODULUS_BITS,
+            MAX_MODULUS_BITS
+        )));
+    }
+    Ok(modulus)
+}
+
+fn check_input_len(len: usize) -> Result<(), ArkworksError> {
+    if len > BYTES {
+        return Err(ArkworksError::InvalidInput(format!(
+            "BigIntFromLeBytes takes at most {} bytes, got {}",
+            BYTES, len
+        )));
+    }
+    Ok(())
+}
+
+fn undefined(id: u32) -> ArkworksError {
+    ArkworksError::InvalidInput(format!("BigInt {} is not defined", id))
+}
+
+fn mismatched_moduli(lhs: u32, rhs: u32) -> ArkworksError {
+    ArkworksError::InvalidInput(format!("BigInts {} and {} have different moduli", lhs, rhs))
+}
+
+/// BigInt values by id, with their moduli, as ACVM tracks them while
+/// solving a function
+#[derive(Clone, Debug, Default)]
+pub struct BigIntTable {
+    values: HashMap<u32, (BigUint, BigUint)>,
+}
+
+impl BigIntTable {
+    /// `BigIntFromLeBytes`: define `output` as the integer of the bytes
+    pub fn from_le_bytes(
+        &mut self,
+        bytes: &[u8],
+        modulus: &[u8],
+        output: u32,
+    ) -> Result<(), ArkworksError> {
+        check_input_len(bytes.len())?;
+        let modulus = parse_modulus(modulus)?;
+        self.values.insert(output, (BigUint::from_bytes_le(bytes), modulus));
+        Ok(())
+    }
+
+    /// `BigIntAdd/Sub/Mul/Div`: define `output` as the residue of lhs op rhs
+    pub fn operate(
+        &mut self,
+        op: Operation,
+        lhs: u32,
+        rhs: u32,
+        output: u32,
+    ) -> Result<(), ArkworksError> {
+        let (a, modulus) = self.values.get(&lhs).ok_or_else(|| undefined(lhs))?;
+        let (b, rhs_modulus) = self.values.get(&rhs).ok_or_else(|| undefined(rhs))?;
+        if modulus != rhs_modulus {
+            return Err(mismatched_moduli(lhs, rhs));
+        }
+        let result = match op {
+            Operation::Add => a + b,
+            Operation::Sub => a + modulus - b % modulus,
+            Operation::Mul => a * b,
+            Operation::Div => match nonnative::inverse(&(b % modulus), modulus) {
+                Some(inverse) => a * inverse,
+                None => BigUint::zero(),
+            },
+        } % modulus;
+        let modulus = modulus.clone();
+        self.values.insert(output, (result, modulus));
+        Ok(())
+    }
+
+    /// `BigIntToLeBytes`: the 32 little-endian bytes of an id
+    pub fn to_le_bytes(&self, input: u32) -> Result<Vec<u8>, ArkworksError> {
+        let (value, _) = self.values.get(&input).ok_or_else(|| undefined(input))?;
+        let mut bytes = value.to_bytes_le();
+        bytes.resize(BYTES, 0);
+        Ok(bytes)
+    }
+}
+
+/// A BigInt id in the circuit
+#[derive(Clone, Debug)]
+pub(crate) struct BigIntVar {
+    value: Element,
+    modulus: BigUint,
+}
+
+/// Constrain a `BigIntFromLeBytes` black box
+///
+/// The bytes are range-checked to their bit sizes, at most 8.
+pub(crate) fn from_le_bytes_gadget(
+    builder: &mut R1csBuilder,
+    inputs: &[FunctionInput],
+    modulus: &[u8],
+    output: u32,
+) -> Result<(), ArkworksError> {
+    check_input_len(inputs.len())?;
+    if let Some(input) = inputs.iter().find(|input| input.num_bits > 8) {
+        return Err(ArkworksError::InvalidInput(format!(
+            "BigIntFromLeBytes input {} has {} bits, bytes have at most 8",
+            input.input, input.num_bits
+        )));
+    }
+    let modulus = parse_modulus(modulus)?;
+    let bits: Vec<_> = bits::input_bytes(builder, inputs).concat();
+    let value = Element::from_bits(&bits);
+    builder.define_bigint(output, BigIntVar { value, modulus });
+    Ok(())
+}
+
+/// Constrain a `BigIntAdd/Sub/Mul/Div` black box
+///
+/// The result is reduced to its canonical residue, so that
+/// `BigIntToLeBytes` gives the bytes ACVM computes.
+pub(crate) fn operation_gadget(
+    builder: &mut R1csBuilder,
+    op: Operation,
+    lhs: u32,
+    rhs: u32,
+    output: u32,
+) -> Result<(), ArkworksError> {
+    let a = builder.bigint(lhs).ok_or_else(|| undefined(lhs))?;
+    let b = builder.bigint(rhs).ok_or_else(|| undefined(rhs))?;
+    if a.modulus != b.modulus {
+        return Err(mismatched_moduli(lhs, rhs));
+    }
+    let modulus = a.modulus;
+    let value = match op {
+        Operation::Add => a.value.add(&b.value),
+        Operation::Sub => a.value.sub(&b.value),
+        Operation::Mul => nonnative::mul(builder, &a.value, &b.value),
+        Operation::Div => {
+            // A zero divisor is replaced by 1, and its quotient by 0
+            let divisor = nonnative::canonical(builder, &b.value, &modulus);
+            let zero = nonnative::is_zero(builder, &divisor);
+            let one = Element::constant(&BigUint::from(1u32));
+            let divisor = nonnative::select(builder, &zero, &divisor, &one);
+            let quotient = nonnative::divide(builder, &a.value, &divisor, &modulus);
+            nonnative::select(builder, &zero, &quotient, &Element::default())
+        }
+    };
+    let value = nonnative::canonical(builder, &value, &modulus);
+    builder.define_bigint(output, BigIntVar { value, modulus });
+    Ok(())
+}
+
+/// Constrain a `BigIntToLeBytes` black box: the outputs are the 32
+/// little-endian bytes of the id
+pub(crate) fn to_le_bytes_gadget(
+    builder: &mut R1csBuilder,
+    input: u32,
+    outputs: &[u32],
+) -> Result<(), ArkworksError> {
+    if outputs.len() != BYTES {
+        return Err(ArkworksError::InvalidInput(format!(
+            "BigIntToLeBytes has {} outputs, expected {}",
+            outputs.len(),
+            BYTES
+        )));
+    }
+    let var = builder.bigint(input).ok_or_else(|| undefined(input))?;
+    nonnative::assign_bytes(builder, &var.value, outputs);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acir_to_r1cs::{acir_to_r1cs, WitnessMap};
+    use crate::acir_types::AcirProgram;
+    use crate::solver::solve_witness;
+    use crate::test_utils;
+    use ark_bn254::Fr;
+    use serde_json::json;
+
+    /// The secp256k1 base field modulus
+    fn secp256k1_p() -> BigUint {
+        BigUint::parse_bytes(
+            b"fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2f",
+            16,
+        )
+        .unwrap()
+    }
+
+    fn le_bytes(value: &BigUint) -> Vec<u8> {
+        let mut bytes = value.to_bytes_le();
+        bytes.resize(BYTES, 0);
+        bytes
+    }
+
+    fn byte_inputs(witnesses: std::ops::RangeInclusive<u32>) -> Vec<serde_json::Value> {
+        witnesses.map(|w| json!({ "witness": w, "num_bits": 8 })).collect()
+    }
+
+    /// a = w1..w32, b = w33..w64; writes `op(a, b)` to w65..w96
+    fn program(op: &str, modulus: &BigUint) -> AcirProgram {
+        test_utils::program(
+            json!([
+                {
+                    "type": "BlackBoxFuncCall", "name": "BigIntFromLeBytes",
+                    "inputs": byte_inputs(1..=32), "modulus": modulus.to_bytes_le(), "output": 0
+                },
+                {
+                    "type": "BlackBoxFuncCall", "name": "BigIntFromLeBytes",
+                    "inputs": byte_inputs(33..=64), "modulus": modulus.to_bytes_le(), "output": 1
+                },
+                { "type": "BlackBoxFuncCall", "name": op, "lhs": 0, "rhs": 1, "output": 2 },
+                {
+                    "type": "BlackBoxFuncCall", "name": "BigIntToLeBytes",
+                    "input": 2, "outputs": (65..=96).collect::<Vec<u32>>()
+                }
+            ]),
+            96,
+            1..=64,
+        )
+    }
+
+    /// Solve the program and check the R1CS, returning the result
+    fn run(program: &AcirProgram, a: &BigUint, b: &BigUint) -> BigUint {
+        let r1cs = acir_to_r1cs(program).unwrap();
+        let values = le_bytes(a).into_iter().chain(le_bytes(b));
+        let initial = (1..).zip(values.map(Fr::from)).collect();
+        let solved = solve_witness(program, &initial).unwrap();
+        let mut witness = solved.into_r1cs_witness(&r1cs).unwrap();
+        r1cs.compute_aux_witnesses(&mut witness).unwrap();
+        assert!(r1cs
+            .constraints
+            .iter()
+            .all(|c| c.is_satisfied(&witness).unwrap()));
+
+        let bytes: Vec<u8> = (65..=96)
+            .map(|w| BigUint::from(witness[&w]).try_into().unwrap())
+            .collect();
+        BigUint::from_bytes_le(&bytes)
+    }
+
+    #[test]
+    fn test_native_operations() {
+        let p = secp256k1_p();
+        let mut table = BigIntTable::default();
+        table.from_le_bytes(&le_bytes(&(&p - 2u32)), &p.to_bytes_le(), 0).unwrap();
+        table.from_le_bytes(&[5], &p.to_bytes_le(), 1).unwrap();
+
+        let result = |table: &mut BigIntTable, op| {
+            table.operate(op, 0, 1, 2).unwrap();
+            BigUint::from_bytes_le(&table.to_le_bytes(2).unwrap())
+        };
+        assert_eq!(result(&mut table, Operation::Add), BigUint::from(3u32));
+        assert_eq!(result(&mut table, Operation::Sub), &p - 7u32);
+        assert_eq!(result(&mut table, Operation::Mul), &p - 10u32);
+        let quotient = result(&mut table, Operation::Div);
+        assert_eq!(quotient * 5u32 % &p, &p - 2u32);
+
+        // Unknown ids, mixed moduli and unsupported moduli are rejected
+        assert!(table.operate(Operation::Add, 0, 7, 3).is_err());
+        table.from_le_bytes(&[1], &[7], 4).unwrap_err();
+        table.from_le_bytes(&[1], &(&p - 2u32).to_bytes_le(), 4).unwrap();
+        assert!(table.operate(Operation::Mul, 0, 4, 5).is_err());
+    }
+
+    #[test]
+    fn test_bigint_gadgets() {
+        let p = secp256k1_p();
+        // a is above p, as from_le_bytes keeps the integer unreduced
+        let a = &p + 12345u32;
+        let b = BigUint::parse_bytes(b"1234567890abcdef1234567890abcdef1234567890abcdef", 16).unwrap();
+
+        assert_eq!(run(&program("BigIntAdd", &p), &a, &b), (&a + &b) % &p);
+        assert_eq!(run(&program("BigIntSub", &p), &b, &a), &b - 12345u32);
+        assert_eq!(run(&program("BigIntMul", &p), &a, &b), &a * &b % &p);
+        let div = program("BigIntDiv", &p);
+        assert_eq!(run(&div, &a, &b) * &b % &p, &a % &p);
+        assert_eq!(run(&div, &a, &p), BigUint::zero());
+    }
+
+    #[test]
+    fn test_bigint_rejects_wrong_bytes() {
+        let p = secp256k1_p();
+        let r1cs = acir_to_r1cs(&program("BigIntMul", &p)).unwrap();
+
+        // 3 * 4 with the given product bytes
+        let accepts = |product: &BigUint| {
+            let values = [3u32, 4].iter().flat_map(|&v| le_bytes(&BigUint::from(v)));
+            let mut witness: WitnessMap = (1..).zip(values.map(Fr::from)).collect();
+            witness.extend((65..).zip(le_bytes(product).into_iter().map(Fr::from)));
+            r1cs.compute_aux_witnesses(&mut witness).is_ok()
+                && r1cs
+                    .constraints
+                    .iter()
+                    .all(|c| c.is_satisfied(&witness).unwrap())
+        };
+        assert!(accepts(&BigUint::from(12u32)));
+        assert!(!accepts(&BigUint::from(13u32)));
+        // 12 + p has the same residue, but is not the canonical one
+        assert!(!accepts(&(&p + 12u32)));
+    }
+
+    #[test]
+    fn test_unsupported_modulus() {
+        let err = acir_to_r1cs(&program("BigIntAdd", &BigUint::from(7u32))).err().unwrap();
+        assert!(matches!(err.inner(), ArkworksError::UnsupportedOpcode(_)));
+    }
+}
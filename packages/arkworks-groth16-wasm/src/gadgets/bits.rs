@@ -0,0 +1,167 @@
+//! Bit-level building blocks for the hash gadgets.
+//!
+//! A bit is a linear combination that evaluates to 0 or 1. Keeping bits as
+//! combinations makes shifts, rotations and NOT free, and operations with a
+//! constant bit (padding, initial values, shifted-in zeros) cost nothing.
+//! Words are little-endian vectors of bits.
+
+use ark_bn254::Fr;
+use ark_ff::{AdditiveGroup, BigInteger, One, PrimeField, Zero};
+
+use super::{combine, Lc};
+use crate::acir_to_r1cs::R1csBuilder;
+use crate::acir_types::FunctionInput;
+
+/// A constant bit
+pub(crate) fn constant(bit: bool) -> Lc {
+    if bit {
+        vec![(Fr::one(), 0)]
+    } else {
+        vec![]
+    }
+}
+
+/// Little-endian bits of a constant word
+pub(crate) fn constant_word(value: u64, width: usize) -> Vec<Lc> {
+    (0..width).map(|i| constant((value >> i) & 1 == 1)).collect()
+}
+
+/// Value of a combination that only involves w_0
+pub(crate) fn constant_value(lc: &Lc) -> Option<Fr> {
+    lc.iter()
+        .all(|&(_, w)| w == 0)
+        .then(|| lc.iter().map(|&(coeff, _)| coeff).sum())
+}
+
+/// x AND y, one constraint unless either bit is constant
+pub(crate) fn and(builder: &mut R1csBuilder, x: &Lc, y: &Lc) -> Lc {
+    match (constant_value(x), constant_value(y)) {
+        (Some(c), _) => combine(&[(c, y)]),
+        (_, Some(c)) => combine(&[(c, x)]),
+        _ => vec![(Fr::one(), builder.product_lc(x.clone(), y.clone()))],
+    }
+}
+
+/// x XOR y = (x - y)^2
+///
+/// The result is a fresh witness, so chained XORs keep their combinations
+/// short; with a constant operand it is y or 1 - y instead.
+pub(crate) fn xor(builder: &mut R1csBuilder, x: &Lc, y: &Lc) -> Lc {
+    let (c, other) = match (constant_value(x), constant_value(y)) {
+        (Some(c), _) => (c, y),
+        (_, Some(c)) => (c, x),
+        _ => {
+            let diff = combine(&[(Fr::one(), x), (-Fr::one(), y)]);
+            return vec![(Fr::one(), builder.product_lc(diff.clone(), diff))];
+        }
+    };
+    if c.is_zero() {
+        other.clone()
+    } else {
+        combine(&[(Fr::one(), &constant(true)), (-Fr::one(), other)])
+    }
+}
+
+/// Bitwise XOR of two words
+pub(crate) fn xor_words(builder: &mut R1csBuilder, x: &[Lc], y: &[Lc]) -> Vec<Lc> {
+    x.iter().zip(y).map(|(a, b)| xor(builder, a, b)).collect()
+}
+
+/// Word rotated right by `n` bits
+pub(crate) fn rotate_right(word: &[Lc], n: usize) -> Vec<Lc> {
+    (0..word.len()).map(|i| word[(i + n) % word.len()].clone()).collect()
+}
+
+/// Word shifted right by `n` bits
+pub(crate) fn shift_right(word: &[Lc], n: usize) -> Vec<Lc> {
+    (0..word.len())
+        .map(|i| word.get(i + n).cloned().unwrap_or_default())
+        .collect()
+}
+
+/// The value of a word, sum(2^i * b_i)
+pub(crate) fn pack(word: &[Lc]) -> Lc {
+    let mut power = Fr::one();
+    let parts: Vec<(Fr, &Lc)> = word
+        .iter()
+        .map(|bit| {
+            let part = (power, bit);
+            power.double_in_place();
+            part
+        })
+        .collect();
+    combine(&parts)
+}
+
+/// Decompose a combination known to fit in `num_bits` bits
+pub(crate) fn unpack(builder: &mut R1csBuilder, lc: Lc, num_bits: usize) -> Vec<Lc> {
+    if let Some(value) = constant_value(&lc) {
+        let value = value.into_bigint();
+        return (0..num_bits).map(|i| constant(value.get_bit(i))).collect();
+    }
+    let source = builder.materialize(lc);
+    builder
+        .decompose_bits(source, num_bits as u32)
+        .into_iter()
+        .map(|bit| vec![(Fr::one(), bit)])
+        .collect()
+}
+
+/// Sum of words (plus a constant) modulo 2^width
+///
+/// The full sum is decomposed with enough carry bits for the number of
+/// summands, which are then dropped.
+pub(crate) fn add_words(
+    builder: &mut R1csBuilder,
+    words: &[&[Lc]],
+    constant: u64,
+    width: usize,
+) -> Vec<Lc> {
+    let packed: Vec<Lc> = words.iter().map(|word| pack(word)).collect();
+    let constant_lc = vec![(Fr::from(constant), 0)];
+    let mut parts: Vec<(Fr, &Lc)> = packed.iter().map(|lc| (Fr::one(), lc)).collect();
+    parts.push((Fr::one(), &constant_lc));
+
+    let summands = words.len() + (constant != 0) as usize;
+    let carry_bits = usize::BITS - summands.saturating_sub(1).leading_zeros();
+    let mut bits = unpack(builder, combine(&parts), width + carry_bits as usize);
+    bits.truncate(width);
+    bits
+}
+
+/// Range-check a black box input to its bit size and decompose it
+pub(crate) fn input_bits(builder: &mut R1csBuilder, input: &FunctionInput) -> Vec<Lc> {
+    unpack(builder, input.lc(), input.num_bits as usize)
+}
+
+/// Bytes of black box inputs, each input contributing the big-endian
+/// bytes of its bit size as ACVM does for hash inputs
+pub(crate) fn input_bytes(builder: &mut R1csBuilder, inputs: &[FunctionInput]) -> Vec<Vec<Lc>> {
+    let mut bytes = Vec::new();
+    for input in inputs {
+        let mut bits = input_bits(builder, input);
+        bits.resize(bits.len().div_ceil(8) * 8, constant(false));
+        bytes.extend(bits.chunks(8).rev().map(|byte| byte.to_vec()));
+    }
+    bytes
+}
+
+/// Canonical decomposition of a field element into 254 bits
+///
+/// 254 bits can also encode value + r, so the bits are compared with those
+/// of r - 1 from the top: while the prefixes are equal, a bit may only be
+/// set where r - 1 has a 1.
+pub(crate) fn field_bits(builder: &mut R1csBuilder, lc: Lc) -> Vec<Lc> {
+    let num_bits = Fr::MODULUS_BIT_SIZE as usize;
+    let bits = unpack(builder, lc, num_bits);
+    let max = (-Fr::one()).into_bigint();
+    let mut equal = constant(true);
+    for i in (0..num_bits).rev() {
+        if max.get_bit(i) {
+            equal = and(builder, &equal, &bits[i]);
+        } else if constant_value(&bits[i]).is_none() {
+            builder.add_constraint(equal.clone(), bits[i].clone(), vec![]);
+        }
+    }
+    bits
+}
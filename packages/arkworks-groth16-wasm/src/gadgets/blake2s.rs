@@ -0,0 +1,244 @@
+//! BLAKE2s-256 hash.
+//!
+//! The gadget works on bits (see `bits`). Each G call costs four modular
+//! additions and four word XORs, about 270 constraints; a block runs 80 of
+//! them, about 22k constraints. BLAKE3 reuses G and the IV.
+
+use super::{bits, Lc};
+use crate::acir_to_r1cs::R1csBuilder;
+use crate::acir_types::FunctionInput;
+use crate::error::ArkworksError;
+
+/// Initialization vector, shared with SHA-256 and BLAKE3
+pub(crate) const IV: [u32; 8] = super::sha256::IV;
+
+/// Message word schedule of each round
+const SIGMA: [[usize; 16]; 10] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+    [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+    [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+    [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+    [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+    [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+    [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+    [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+    [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0],
+];
+
+/// Block size in bytes
+const BLOCK: usize = 64;
+
+/// State words mixed by the four column and four diagonal G calls
+pub(crate) const MIXES: [[usize; 4]; 8] = [
+    [0, 4, 8, 12],
+    [1, 5, 9, 13],
+    [2, 6, 10, 14],
+    [3, 7, 11, 15],
+    [0, 5, 10, 15],
+    [1, 6, 11, 12],
+    [2, 7, 8, 13],
+    [3, 4, 9, 14],
+];
+
+/// The G mixing function
+pub(crate) fn g(v: &mut [u32; 16], [a, b, c, d]: [usize; 4], x: u32, y: u32) {
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(x);
+    v[d] = (v[d] ^ v[a]).rotate_right(16);
+    v[c] = v[c].wrapping_add(v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(12);
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(y);
+    v[d] = (v[d] ^ v[a]).rotate_right(8);
+    v[c] = v[c].wrapping_add(v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(7);
+}
+
+/// Constrain the G mixing function on 32-bit words
+pub(crate) fn g_gadget(
+    builder: &mut R1csBuilder,
+    v: &mut [Vec<Lc>],
+    [a, b, c, d]: [usize; 4],
+    x: &[Lc],
+    y: &[Lc],
+) {
+    for (m, (r1, r2)) in [(x, (16, 12)), (y, (8, 7))] {
+        v[a] = bits::add_words(builder, &[&v[a], &v[b], m], 0, 32);
+        v[d] = bits::rotate_right(&bits::xor_words(builder, &v[d], &v[a]), r1);
+        v[c] = bits::add_words(builder, &[&v[c], &v[d]], 0, 32);
+        v[b] = bits::rotate_right(&bits::xor_words(builder, &v[b], &v[c]), r2);
+    }
+}
+
+/// Little-endian 32-bit words of a block of bytes (8 little-endian bits each)
+pub(crate) fn block_words(block: &[Vec<Lc>]) -> Vec<Vec<Lc>> {
+    let mut block = block.to_vec();
+    block.resize(BLOCK, bits::constant_word(0, 8));
+    block.chunks(4).map(|word| word.concat()).collect()
+}
+
+/// Compress a block into the state; `t` counts the bytes hashed so far
+fn compress(h: &[u32; 8], m: &[u32; 16], t: u64, last: bool) -> [u32; 8] {
+    let mut v = [0u32; 16];
+    v[..8].copy_from_slice(h);
+    v[8..].copy_from_slice(&IV);
+    v[12] ^= t as u32;
+    v[13] ^= (t >> 32) as u32;
+    if last {
+        v[14] = !v[14];
+    }
+    for s in SIGMA {
+        for (i, mix) in MIXES.into_iter().enumerate() {
+            g(&mut v, mix, m[s[2 * i]], m[s[2 * i + 1]]);
+        }
+    }
+    std::array::from_fn(|i| h[i] ^ v[i] ^ v[i + 8])
+}
+
+/// Initial state for an unkeyed 32-byte digest
+fn initial_state() -> [u32; 8] {
+    let mut h = IV;
+    h[0] ^= 0x0101_0000 | 32;
+    h
+}
+
+/// Blocks of a message with the byte count up to and including each
+/// block, and whether it is the last; an empty message is one empty block
+fn blocks<T>(message: &[T]) -> Vec<(&[T], u64, bool)> {
+    let mut blocks: Vec<&[T]> = message.chunks(BLOCK).collect();
+    if blocks.is_empty() {
+        blocks.push(&message[..0]);
+    }
+    let count = blocks.len();
+    let mut t = 0;
+    blocks
+        .into_iter()
+        .enumerate()
+        .map(|(i, block)| {
+            t += block.len() as u64;
+            (block, t, i + 1 == count)
+        })
+        .collect()
+}
+
+/// Hash a message
+pub fn blake2s(message: &[u8]) -> [u8; 32] {
+    let mut h = initial_state();
+    for (chunk, t, last) in blocks(message) {
+        let mut block = [0u8; BLOCK];
+        block[..chunk.len()].copy_from_slice(chunk);
+        let m: [u32; 16] = std::array::from_fn(|j| {
+            u32::from_le_bytes(block[4 * j..4 * j + 4].try_into().expect("4-byte word"))
+        });
+        h = compress(&h, &m, t, last);
+    }
+
+    let mut output = [0u8; 32];
+    for (bytes, word) in output.chunks_mut(4).zip(h) {
+        bytes.copy_from_slice(&word.to_le_bytes());
+    }
+    output
+}
+
+/// Constrain the digest of a message of bytes (8 little-endian bits each)
+pub(crate) fn digest_gadget(builder: &mut R1csBuilder, message: &[Vec<Lc>]) -> Vec<Vec<Lc>> {
+    let mut h: Vec<Vec<Lc>> = initial_state()
+        .iter()
+        .map(|&word| bits::constant_word(word as u64, 32))
+        .collect();
+    for (block, t, last) in blocks(message) {
+        let m = block_words(block);
+
+        let mut v: Vec<Vec<Lc>> = h.clone();
+        v.extend(IV.iter().map(|&word| bits::constant_word(word as u64, 32)));
+        let counter = [t as u32, (t >> 32) as u32, if last { !0 } else { 0 }];
+        for (word, constant) in v[12..15].iter_mut().zip(counter) {
+            *word = bits::xor_words(builder, word, &bits::constant_word(constant as u64, 32));
+        }
+        for s in SIGMA {
+            for (j, mix) in MIXES.into_iter().enumerate() {
+                g_gadget(builder, &mut v, mix, &m[s[2 * j]], &m[s[2 * j + 1]]);
+            }
+        }
+        h = (0..8)
+            .map(|j| {
+                let partial = bits::xor_words(builder, &h[j], &v[j]);
+                bits::xor_words(builder, &partial, &v[j + 8])
+            })
+            .collect();
+    }
+    h.iter()
+        .flat_map(|word| word.chunks(8).map(|byte| byte.to_vec()))
+        .collect()
+}
+
+/// Constrain a `Blake2s` black box: `outputs` are the 32 digest bytes of
+/// the message formed by `inputs` (see `bits::input_bytes`)
+pub(crate) fn hash_gadget(
+    builder: &mut R1csBuilder,
+    inputs: &[FunctionInput],
+    outputs: &[u32],
+) -> Result<(), ArkworksError> {
+    if outputs.len() != 32 {
+        return Err(ArkworksError::InvalidInput(format!(
+            "Blake2s has 32 output bytes, got {}",
+            outputs.len()
+        )));
+    }
+    let message = bits::input_bytes(builder, inputs);
+    let digest = digest_gadget(builder, &message);
+    for (byte, &output) in digest.iter().zip(outputs) {
+        builder.assign_output(output, bits::pack(byte));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acir_to_r1cs::WitnessMap;
+    use crate::test_utils::convert_opcode;
+    use ark_bn254::Fr;
+    use serde_json::json;
+
+    #[test]
+    fn test_native_vectors() {
+        assert_eq!(
+            hex::encode(blake2s(b"")),
+            "69217a3079908094e11121d042354a7c1f55b6482ca1a51e1b250dfd1ed0eef9"
+        );
+        assert_eq!(
+            hex::encode(blake2s(b"abc")),
+            "508c5e8c327c14e2e1a72ba34eeb452f37458b209ed63a294d999b4c86675982"
+        );
+        let message: Vec<u8> = (0..200).collect();
+        assert_eq!(
+            hex::encode(blake2s(&message)),
+            "6d244e1a06ce4ef578dd0f63aff0936706735119ca9c8d22d86c801414ab9741"
+        );
+    }
+
+    #[test]
+    fn test_hash_gadget() {
+        // Two blocks, the second one partial
+        let message: Vec<u8> = (0..70).collect();
+        let n = message.len() as u32;
+        let r1cs = convert_opcode(
+            json!({
+                "type": "BlackBoxFuncCall", "name": "Blake2s",
+                "inputs": (1..=n).map(|w| json!({ "witness": w, "num_bits": 8 })).collect::<Vec<_>>(),
+                "outputs": (n + 1..=n + 32).collect::<Vec<u32>>()
+            }),
+            n + 32,
+            1..=n,
+        );
+
+        let mut witness: WitnessMap = (1..=n).zip(message.iter().map(|&b| Fr::from(b))).collect();
+        r1cs.compute_aux_witnesses(&mut witness).unwrap();
+        assert!(r1cs
+            .constraints
+            .iter()
+            .all(|c| c.is_satisfied(&witness).unwrap()));
+        let outputs: Vec<Fr> = (n + 1..=n + 32).map(|w| witness[&w]).collect();
+        assert_eq!(outputs, blake2s(&message).map(Fr::from).to_vec());
+    }
+}
@@ -0,0 +1,299 @@
+//! BLAKE3 hash with a 32-byte output.
+//!
+//! Messages are split into 1024-byte chunks of 64-byte blocks, and chunk
+//! chaining values are merged in a binary tree of parent nodes. The input
+//! length is fixed by the circuit, so the tree shape and every flag and
+//! counter are constants. Each compression runs 56 G calls (see
+//! `blake2s`), about 15k constraints.
+
+use super::blake2s::{block_words, g, g_gadget, IV, MIXES};
+use super::{bits, Lc};
+use crate::acir_to_r1cs::R1csBuilder;
+use crate::acir_types::FunctionInput;
+use crate::error::ArkworksError;
+
+const CHUNK_LEN: usize = 1024;
+const BLOCK_LEN: usize = 64;
+const ROUNDS: usize = 7;
+
+const CHUNK_START: u32 = 1;
+const CHUNK_END: u32 = 2;
+const PARENT: u32 = 4;
+const ROOT: u32 = 8;
+
+/// Message word permutation applied between rounds
+const MSG_PERMUTATION: [usize; 16] = [2, 6, 3, 10, 7, 0, 4, 13, 1, 11, 12, 5, 9, 14, 15, 8];
+
+/// Constant inputs of one compression besides the chaining value and block
+#[derive(Clone, Copy)]
+struct Params {
+    counter: u64,
+    block_len: u32,
+    flags: u32,
+}
+
+impl Params {
+    /// State words 8..16: IV, counter, block length and flags
+    fn tail(&self) -> [u32; 8] {
+        [
+            IV[0],
+            IV[1],
+            IV[2],
+            IV[3],
+            self.counter as u32,
+            (self.counter >> 32) as u32,
+            self.block_len,
+            self.flags,
+        ]
+    }
+}
+
+/// The compression function, truncated to the 8-word chaining value
+fn compress(cv: &[u32; 8], block: &[u32; 16], params: Params) -> [u32; 8] {
+    let mut v = [0u32; 16];
+    v[..8].copy_from_slice(cv);
+    v[8..].copy_from_slice(&params.tail());
+    let mut m = *block;
+    for round in 0..ROUNDS {
+        for (i, mix) in MIXES.into_iter().enumerate() {
+            g(&mut v, mix, m[2 * i], m[2 * i + 1]);
+        }
+        if round + 1 < ROUNDS {
+            m = MSG_PERMUTATION.map(|j| m[j]);
+        }
+    }
+    std::array::from_fn(|i| v[i] ^ v[i + 8])
+}
+
+/// Blocks of a chunk with their length and flags; an empty chunk is one
+/// empty block
+fn chunk_blocks<T>(chunk: &[T], counter: u64, root: bool) -> Vec<(&[T], Params)> {
+    let mut blocks: Vec<&[T]> = chunk.chunks(BLOCK_LEN).collect();
+    if blocks.is_empty() {
+        blocks.push(&chunk[..0]);
+    }
+    let count = blocks.len();
+    blocks
+        .into_iter()
+        .enumerate()
+        .map(|(i, block)| {
+            let mut flags = 0;
+            if i == 0 {
+                flags |= CHUNK_START;
+            }
+            if i + 1 == count {
+                flags |= CHUNK_END | if root { ROOT } else { 0 };
+            }
+            let params = Params {
+                counter,
+                block_len: block.len() as u32,
+                flags,
+            };
+            (block, params)
+        })
+        .collect()
+}
+
+/// Number of chunks in the left subtree of a node covering `chunks` chunks:
+/// the largest power of two that leaves at least one chunk on the right
+fn left_chunks(chunks: usize) -> usize {
+    1 << (usize::BITS - 1 - (chunks - 1).leading_zeros())
+}
+
+/// Chunks of a message; an empty message is one empty chunk
+fn chunks<T>(message: &[T]) -> Vec<&[T]> {
+    let mut chunks: Vec<&[T]> = message.chunks(CHUNK_LEN).collect();
+    if chunks.is_empty() {
+        chunks.push(&message[..0]);
+    }
+    chunks
+}
+
+/// Chaining value of the subtree over `chunks`, numbered from `first`
+fn subtree(chunks: &[&[u8]], first: u64, root: bool) -> [u32; 8] {
+    if let [chunk] = chunks {
+        let mut cv = IV;
+        for (block, params) in chunk_blocks(chunk, first, root) {
+            let mut bytes = [0u8; BLOCK_LEN];
+            bytes[..block.len()].copy_from_slice(block);
+            let m: [u32; 16] = std::array::from_fn(|j| {
+                u32::from_le_bytes(bytes[4 * j..4 * j + 4].try_into().expect("4-byte word"))
+            });
+            cv = compress(&cv, &m, params);
+        }
+        return cv;
+    }
+
+    let split = left_chunks(chunks.len());
+    let left = subtree(&chunks[..split], first, false);
+    let right = subtree(&chunks[split..], first + split as u64, false);
+    let block: [u32; 16] = std::array::from_fn(|j| if j < 8 { left[j] } else { right[j - 8] });
+    let flags = PARENT | if root { ROOT } else { 0 };
+    compress(
+        &IV,
+        &block,
+        Params {
+            counter: 0,
+            block_len: BLOCK_LEN as u32,
+            flags,
+        },
+    )
+}
+
+/// Hash a message
+pub fn blake3(message: &[u8]) -> [u8; 32] {
+    let cv = subtree(&chunks(message), 0, true);
+    let mut output = [0u8; 32];
+    for (bytes, word) in output.chunks_mut(4).zip(cv) {
+        bytes.copy_from_slice(&word.to_le_bytes());
+    }
+    output
+}
+
+/// Constrain the compression function on 32-bit words
+fn compress_gadget(
+    builder: &mut R1csBuilder,
+    cv: &[Vec<Lc>],
+    block: &[Vec<Lc>],
+    params: Params,
+) -> Vec<Vec<Lc>> {
+    let mut v: Vec<Vec<Lc>> = cv.to_vec();
+    v.extend(
+        params
+            .tail()
+            .iter()
+            .map(|&word| bits::constant_word(word as u64, 32)),
+    );
+    let mut m: Vec<Vec<Lc>> = block.to_vec();
+    for round in 0..ROUNDS {
+        for (i, mix) in MIXES.into_iter().enumerate() {
+            g_gadget(builder, &mut v, mix, &m[2 * i], &m[2 * i + 1]);
+        }
+        if round + 1 < ROUNDS {
+            m = MSG_PERMUTATION.iter().map(|&j| m[j].clone()).collect();
+        }
+    }
+    (0..8)
+        .map(|i| bits::xor_words(builder, &v[i], &v[i + 8]))
+        .collect()
+}
+
+/// Constrain the chaining value of the subtree over `chunks`
+fn subtree_gadget(
+    builder: &mut R1csBuilder,
+    chunks: &[&[Vec<Lc>]],
+    first: u64,
+    root: bool,
+) -> Vec<Vec<Lc>> {
+    let constant_iv = || {
+        IV.iter()
+            .map(|&word| bits::constant_word(word as u64, 32))
+            .collect()
+    };
+    if let [chunk] = chunks {
+        let mut cv: Vec<Vec<Lc>> = constant_iv();
+        for (block, params) in chunk_blocks(chunk, first, root) {
+            cv = compress_gadget(builder, &cv, &block_words(block), params);
+        }
+        return cv;
+    }
+
+    let split = left_chunks(chunks.len());
+    let left = subtree_gadget(builder, &chunks[..split], first, false);
+    let right = subtree_gadget(builder, &chunks[split..], first + split as u64, false);
+    let block = [left, right].concat();
+    let flags = PARENT | if root { ROOT } else { 0 };
+    let params = Params {
+        counter: 0,
+        block_len: BLOCK_LEN as u32,
+        flags,
+    };
+    compress_gadget(builder, &constant_iv(), &block, params)
+}
+
+/// Constrain a `Blake3` black box: `outputs` are the 32 digest bytes of
+/// the message formed by `inputs` (see `bits::input_bytes`)
+pub(crate) fn hash_gadget(
+    builder: &mut R1csBuilder,
+    inputs: &[FunctionInput],
+    outputs: &[u32],
+) -> Result<(), ArkworksError> {
+    if outputs.len() != 32 {
+        return Err(ArkworksError::InvalidInput(format!(
+            "Blake3 has 32 output bytes, got {}",
+            outputs.len()
+        )));
+    }
+    let message = bits::input_bytes(builder, inputs);
+    let cv = subtree_gadget(builder, &chunks(&message), 0, true);
+
+    let bytes: Vec<&[Lc]> = cv.iter().flat_map(|word| word.chunks(8)).collect();
+    for (byte, &output) in bytes.into_iter().zip(outputs) {
+        builder.assign_output(output, bits::pack(byte));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acir_to_r1cs::WitnessMap;
+    use crate::test_utils::convert_opcode;
+    use ark_bn254::Fr;
+    use serde_json::json;
+
+    fn pattern(len: usize) -> Vec<u8> {
+        (0..len).map(|i| i as u8).collect()
+    }
+
+    #[test]
+    fn test_native_vectors() {
+        let cases = [
+            (
+                pattern(0),
+                "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262",
+            ),
+            (
+                b"abc".to_vec(),
+                "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85",
+            ),
+            // Two chunks under one parent
+            (
+                pattern(1025),
+                "3e85e5a7ffcd07c23794c079d43ebb27372d06bb1f75e4b47732fcaaf1a8cf3d",
+            ),
+            // Three chunks: a parent of two, then the root
+            (
+                pattern(3072),
+                "a10998beb5193c47a0c1cf19aa8daaa8dede3d9e5c53f78ecfe5d22e20f7f9bd",
+            ),
+        ];
+        for (message, expected) in cases {
+            assert_eq!(hex::encode(blake3(&message)), expected);
+        }
+    }
+
+    #[test]
+    fn test_hash_gadget() {
+        let message = pattern(1025);
+        let n = message.len() as u32;
+        let r1cs = convert_opcode(
+            json!({
+                "type": "BlackBoxFuncCall", "name": "Blake3",
+                "inputs": (1..=n).map(|w| json!({ "witness": w, "num_bits": 8 })).collect::<Vec<_>>(),
+                "outputs": (n + 1..=n + 32).collect::<Vec<u32>>()
+            }),
+            n + 32,
+            1..=n,
+        );
+
+        let mut witness: WitnessMap = (1..=n).zip(message.iter().map(|&b| Fr::from(b))).collect();
+        r1cs.compute_aux_witnesses(&mut witness).unwrap();
+        assert!(r1cs
+            .constraints
+            .iter()
+            .all(|c| c.is_satisfied(&witness).unwrap()));
+        let outputs: Vec<Fr> = (n + 1..=n + 32).map(|w| witness[&w]).collect();
+        assert_eq!(outputs, blake3(&message).map(Fr::from).to_vec());
+    }
+}
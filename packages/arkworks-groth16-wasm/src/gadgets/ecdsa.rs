@@ -0,0 +1,609 @@
+//! ECDSA over secp256k1 and secp256r1, as ACVM verifies prehashed messages.
+//!
+//! A signature (r, s) of a hash z is valid for a public key Q when Q is on
+//! the curve with coordinates below p, r and s are in [1, n - 1] with
+//! s <= n / 2 ("low s"), and R = (z / s) * G + (r / s) * Q is finite with
+//! R.x = r modulo n. The hash is read big-endian and truncated to 32 bytes;
+//! hashes of fewer than 16 bytes are rejected.
+//!
+//! Coordinates live in a field other than the BN254 scalar field, so the
+//! gadget uses `nonnative` arithmetic. The prover supplies u1 = z / s and
+//! u2 = r / s, checked by one product modulo n each. A single accumulator,
+//! started at an offset point, adds u2 * Q in 4-bit windows from the top,
+//! doubling in between, and then u1 * G from constant tables; all additions
+//! are checked. As for Schnorr, every check is a flag, so an invalid
+//! signature gives 0. The gadget costs about 1.2M constraints.
+
+use ark_bn254::Fr;
+use ark_ff::{One, Zero};
+use num_bigint::BigUint;
+
+use super::nonnative::{self, Element};
+use super::{bits, combine, sha256, Lc};
+use crate::acir_to_r1cs::R1csBuilder;
+use crate::acir_types::FunctionInput;
+use crate::error::ArkworksError;
+
+/// Bits per window of the scalar multiplication
+const WINDOW: usize = 4;
+
+/// Bytes of the hash read into the scalar z, and the fewest accepted
+const HASH_BYTES: usize = 32;
+const MIN_HASH_BYTES: usize = 16;
+
+/// An affine point, `None` for the point at infinity
+type Affine = Option<(BigUint, BigUint)>;
+
+/// A short Weierstrass curve y^2 = x^3 + ax + b of prime order n over a
+/// field of p = 3 mod 4 elements
+pub struct Curve {
+    name: &'static str,
+    p: BigUint,
+    n: BigUint,
+    a: BigUint,
+    b: BigUint,
+    generator: (BigUint, BigUint),
+}
+
+fn hex(digits: &str) -> BigUint {
+    BigUint::parse_bytes(digits.as_bytes(), 16).expect("valid hex constant")
+}
+
+impl Curve {
+    pub fn secp256k1() -> Self {
+        Curve {
+            name: "secp256k1",
+            p: hex("fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2f"),
+            n: hex("fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141"),
+            a: BigUint::zero(),
+            b: BigUint::from(7u32),
+            generator: (
+                hex("79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"),
+                hex("483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8"),
+            ),
+        }
+    }
+
+    pub fn secp256r1() -> Self {
+        let p = hex("ffffffff00000001000000000000000000000000ffffffffffffffffffffffff");
+        Curve {
+            name: "secp256r1",
+            n: hex("ffffffff00000000ffffffffffffffffbce6faada7179e84f3b9cac2fc632551"),
+            a: &p - 3u32,
+            b: hex("5ac635d8aa3a93e7b3ebbd55769886bc651d06b0cc53b0f63bce3c3e27d2604b"),
+            generator: (
+                hex("6b17d1f2e12c4247f8bce6e563a440f277037d812deb33a0f4a13945d898c296"),
+                hex("4fe342e2fe1a7f9b8ee7eb4a7c0f9e162bce33576b315ececbb6406837bf51f5"),
+            ),
+            p,
+        }
+    }
+
+    /// x^3 + ax + b
+    fn curve_rhs(&self, x: &BigUint) -> BigUint {
+        (x * x * x + &self.a * x + &self.b) % &self.p
+    }
+
+    fn is_on_curve(&self, (x, y): &(BigUint, BigUint)) -> bool {
+        y * y % &self.p == self.curve_rhs(x)
+    }
+
+    fn add(&self, p: &Affine, q: &Affine) -> Affine {
+        let (Some((x1, y1)), Some((x2, y2))) = (p, q) else {
+            return p.clone().or_else(|| q.clone());
+        };
+        let m = &self.p;
+        let inverse = |value: BigUint| nonnative::inverse(&value, m).expect("non-zero");
+        let lambda = if x1 == x2 {
+            if ((y1 + y2) % m).is_zero() {
+                return None;
+            }
+            (x1 * x1 * 3u32 + &self.a) * inverse(y1 * 2u32) % m
+        } else {
+            (y2 + m - y1) * inverse(x2 + m - x1) % m
+        };
+        let x3 = (&lambda * &lambda + m * 2u32 - x1 - x2) % m;
+        let y3 = (lambda * ((x1 + m - &x3) % m) + m - y1) % m;
+        Some((x3, y3))
+    }
+
+    fn mul(&self, point: &Affine, scalar: &BigUint) -> Affine {
+        let mut result = None;
+        for i in (0..scalar.bits()).rev() {
+            result = self.add(&result, &result);
+            if scalar.bit(i) {
+                result = self.add(&result, point);
+            }
+        }
+        result
+    }
+
+    fn neg(&self, point: &Affine) -> Affine {
+        point
+            .as_ref()
+            .map(|(x, y)| (x.clone(), (&self.p - y) % &self.p))
+    }
+
+    /// The point the accumulator starts from, with x the SHA-256 digest of
+    /// "<curve>_msm_offset" and a counter
+    fn offset_generator(&self) -> (BigUint, BigUint) {
+        let domain = format!("{}_msm_offset", self.name);
+        (0..=u8::MAX)
+            .find_map(|counter| {
+                let digest = sha256::digest(&[domain.as_bytes(), &[counter]].concat());
+                let x = BigUint::from_bytes_be(&digest) % &self.p;
+                let rhs = self.curve_rhs(&x);
+                let y = rhs.modpow(&((&self.p + 1u32) >> 2), &self.p);
+                (&y * &y % &self.p == rhs).then_some((x, y))
+            })
+            .expect("a square within 256 attempts")
+    }
+}
+
+/// The hash bytes ACVM reduces into a scalar, if there are enough
+fn message_bytes(hashed_message: &[u8]) -> Option<&[u8]> {
+    (hashed_message.len() >= MIN_HASH_BYTES)
+        .then(|| &hashed_message[..hashed_message.len().min(HASH_BYTES)])
+}
+
+/// Verify a signature r || s of a prehashed message by the key (x, y)
+pub fn verify_signature(
+    curve: &Curve,
+    public_key_x: &[u8; 32],
+    public_key_y: &[u8; 32],
+    signature: &[u8; 64],
+    hashed_message: &[u8],
+) -> bool {
+    let key = (
+        BigUint::from_bytes_be(public_key_x),
+        BigUint::from_bytes_be(public_key_y),
+    );
+    if key.0 >= curve.p || key.1 >= curve.p || !curve.is_on_curve(&key) {
+        return false;
+    }
+    let r = BigUint::from_bytes_be(&signature[..32]);
+    let s = BigUint::from_bytes_be(&signature[32..]);
+    if r.is_zero() || r >= curve.n || s.is_zero() || s > &curve.n >> 1 {
+        return false;
+    }
+    let Some(message) = message_bytes(hashed_message) else {
+        return false;
+    };
+    let z = BigUint::from_bytes_be(message);
+    let s_inverse = nonnative::inverse(&s, &curve.n).expect("s is in [1, n - 1]");
+    let u1 = z * &s_inverse % &curve.n;
+    let u2 = &r * s_inverse % &curve.n;
+    let point = curve.add(
+        &curve.mul(&Some(curve.generator.clone()), &u1),
+        &curve.mul(&Some(key), &u2),
+    );
+    point.is_some_and(|(x, _)| x % &curve.n == r)
+}
+
+/// A finite point in the circuit
+#[derive(Clone, Debug)]
+struct PointVar {
+    x: Element,
+    y: Element,
+}
+
+impl PointVar {
+    fn constant((x, y): &(BigUint, BigUint)) -> Self {
+        PointVar {
+            x: Element::constant(x),
+            y: Element::constant(y),
+        }
+    }
+}
+
+/// 1 - bit
+fn not(bit: &Lc) -> Lc {
+    combine(&[(Fr::one(), &bits::constant(true)), (-Fr::one(), bit)])
+}
+
+/// Little-endian bits of big-endian bytes (8 little-endian bits each)
+fn le_bits(bytes: &[Vec<Lc>]) -> Vec<Lc> {
+    bytes.iter().rev().flatten().cloned().collect()
+}
+
+/// `if_one` when the bit is set, `if_zero` otherwise
+fn select(builder: &mut R1csBuilder, bit: &Lc, if_zero: &PointVar, if_one: &PointVar) -> PointVar {
+    PointVar {
+        x: nonnative::select(builder, bit, &if_zero.x, &if_one.x),
+        y: nonnative::select(builder, bit, &if_zero.y, &if_one.y),
+    }
+}
+
+/// Select `table[m]` for the little-endian value m of a window, with a
+/// tree of `select`s
+fn lookup_variable(builder: &mut R1csBuilder, window: &[Lc], table: &[PointVar]) -> PointVar {
+    let mut table = table.to_vec();
+    for bit in window {
+        table = table
+            .chunks(2)
+            .map(|pair| select(builder, bit, &pair[0], &pair[1]))
+            .collect();
+    }
+    table.swap_remove(0)
+}
+
+/// Select `table[m]` for the little-endian value m of a window of a
+/// constant table, with `nonnative::lookup` over the subset products
+fn lookup(builder: &mut R1csBuilder, window: &[Lc], table: &[(BigUint, BigUint)]) -> PointVar {
+    // monomials[mask] is the product of the bits in mask
+    let mut monomials: Vec<Lc> = vec![bits::constant(true)];
+    for (i, bit) in window.iter().enumerate() {
+        for mask in 0..1 << i {
+            let monomial = bits::and(builder, &monomials[mask], bit);
+            monomials.push(monomial);
+        }
+    }
+    let (xs, ys): (Vec<BigUint>, Vec<BigUint>) = table.iter().cloned().unzip();
+    PointVar {
+        x: nonnative::lookup(&monomials, &xs),
+        y: nonnative::lookup(&monomials, &ys),
+    }
+}
+
+impl Curve {
+    /// The sum of p and a point with x coordinate `qx` on a line of slope
+    /// `lambda` through p
+    fn chord(
+        &self,
+        builder: &mut R1csBuilder,
+        p: &PointVar,
+        qx: &Element,
+        lambda: &Element,
+    ) -> PointVar {
+        let lambda_squared = nonnative::mul(builder, lambda, lambda);
+        let x = nonnative::reduce(builder, &lambda_squared.sub(&p.x).sub(qx), &self.p);
+        let rise = nonnative::mul(builder, lambda, &p.x.sub(&x));
+        let y = nonnative::reduce(builder, &rise.sub(&p.y), &self.p);
+        PointVar { x, y }
+    }
+
+    /// p + q, unsatisfiable for equal x coordinates
+    fn add_checked(&self, builder: &mut R1csBuilder, p: &PointVar, q: &PointVar) -> PointVar {
+        let dx = q.x.sub(&p.x);
+        let one = Element::constant(&BigUint::one());
+        nonnative::divide(builder, &one, &dx, &self.p);
+        let lambda = nonnative::divide(builder, &q.y.sub(&p.y), &dx, &self.p);
+        self.chord(builder, p, &q.x, &lambda)
+    }
+
+    /// 2p; no finite point of a curve of odd order has y = 0
+    fn double(&self, builder: &mut R1csBuilder, p: &PointVar) -> PointVar {
+        let xx = nonnative::mul(builder, &p.x, &p.x);
+        let numerator = xx.scale(3).add(&Element::constant(&self.a));
+        let lambda = nonnative::divide(builder, &numerator, &p.y.scale(2), &self.p);
+        self.chord(builder, p, &p.x, &lambda)
+    }
+
+    /// The x coordinate of p + q for a constant q, and whether the sum is
+    /// the point at infinity
+    ///
+    /// As in `grumpkin::add_constant`, equal x coordinates must mean q = -p;
+    /// q = p makes the constraints unsatisfiable.
+    fn add_constant(&self, builder: &mut R1csBuilder, p: &PointVar, q: &Affine) -> (Element, Lc) {
+        let Some(q) = q else {
+            return (p.x.clone(), bits::constant(false));
+        };
+        let q = PointVar::constant(q);
+        let dx = q.x.sub(&p.x);
+        let dx_residue = nonnative::canonical(builder, &dx, &self.p);
+        let same_x = nonnative::is_zero(builder, &dx_residue);
+        let y_sum = nonnative::mul(
+            builder,
+            &Element::from_bits(std::slice::from_ref(&same_x)),
+            &q.y.add(&p.y),
+        );
+        nonnative::enforce_zero_mod(builder, &y_sum, &self.p);
+
+        // lambda * dx = distinct * dy, which leaves lambda free when dx = 0
+        let distinct = Element::from_bits(&[not(&same_x)]);
+        let numerator = nonnative::mul(builder, &distinct, &q.y.sub(&p.y));
+        let lambda = nonnative::divide(builder, &numerator, &dx, &self.p);
+        (self.chord(builder, p, &q.x, &lambda).x, same_x)
+    }
+
+    /// The x coordinate of u1 * G + u2 * q for scalars as little-endian
+    /// bits, and whether the sum is the point at infinity
+    ///
+    /// Windows of u2 add m * q from a table built in the circuit, with q
+    /// standing in for 0 * q and the addition skipped. Window j of u1 adds
+    /// (m + 1) * 16^j * G, so no table entry is the point at infinity. The
+    /// doubled offset and the extra multiples of G are removed at the end.
+    fn double_scalar_mul(
+        &self,
+        builder: &mut R1csBuilder,
+        u1: &[Lc],
+        q: &PointVar,
+        u2: &[Lc],
+    ) -> (Element, Lc) {
+        let mut table = vec![q.clone(), q.clone()];
+        for m in 2..1 << WINDOW {
+            let entry = if m == 2 {
+                self.double(builder, q)
+            } else {
+                self.add_checked(builder, &table[m - 1], q)
+            };
+            table.push(entry);
+        }
+
+        let mut offset = Some(self.offset_generator());
+        let mut acc = PointVar::constant(offset.as_ref().expect("finite offset"));
+        let windows: Vec<&[Lc]> = u2.chunks(WINDOW).collect();
+        for (j, window) in windows.iter().enumerate().rev() {
+            if j + 1 < windows.len() {
+                for _ in 0..WINDOW {
+                    acc = self.double(builder, &acc);
+                    offset = self.add(&offset, &offset);
+                }
+            }
+            let mut all_clear = bits::constant(true);
+            for bit in window.iter() {
+                all_clear = bits::and(builder, &all_clear, &not(bit));
+            }
+            let entry = lookup_variable(builder, window, &table[..1 << window.len()]);
+            let sum = self.add_checked(builder, &acc, &entry);
+            acc = select(builder, &not(&all_clear), &acc, &sum);
+        }
+
+        let mut window_base = Some(self.generator.clone());
+        for window in u1.chunks(WINDOW) {
+            let mut table = Vec::with_capacity(1 << window.len());
+            let mut entry = window_base.clone();
+            for _ in 0..1 << window.len() {
+                table.push(entry.clone().expect("n is prime and above 2^255"));
+                entry = self.add(&entry, &window_base);
+            }
+            offset = self.add(&offset, &window_base);
+
+            let point = lookup(builder, window, &table);
+            acc = self.add_checked(builder, &acc, &point);
+            for _ in 0..WINDOW {
+                window_base = self.add(&window_base, &window_base);
+            }
+        }
+        self.add_constant(builder, &acc, &self.neg(&offset))
+    }
+}
+
+/// Whether a value given by its bits is in [1, bound)
+fn in_range(builder: &mut R1csBuilder, value_bits: &[Lc], bound: &BigUint) -> Lc {
+    let zero = nonnative::is_zero(builder, &Element::from_bits(value_bits));
+    let below = nonnative::less_than(builder, value_bits, bound);
+    bits::and(builder, &not(&zero), &below)
+}
+
+/// Constrain an `EcdsaSecp256k1` or `EcdsaSecp256r1` black box: `output`
+/// is 1 when the signature is valid and 0 otherwise
+pub(crate) fn verify_gadget(
+    builder: &mut R1csBuilder,
+    curve: &Curve,
+    public_key_x: &[FunctionInput],
+    public_key_y: &[FunctionInput],
+    signature: &[FunctionInput],
+    hashed_message: &[FunctionInput],
+    output: u32,
+) -> Result<(), ArkworksError> {
+    if public_key_x.len() != 32 || public_key_y.len() != 32 || signature.len() != 64 {
+        return Err(ArkworksError::InvalidInput(format!(
+            "ECDSA takes 32-byte key coordinates and a 64-byte signature, got {}, {} and {}",
+            public_key_x.len(),
+            public_key_y.len(),
+            signature.len()
+        )));
+    }
+    if hashed_message.len() < MIN_HASH_BYTES {
+        builder.assign_output(output, bits::constant(false));
+        return Ok(());
+    }
+
+    // The key is valid when its coordinates are below p and
+    // y^2 - x^3 - ax - b = 0; otherwise it is replaced by the generator
+    let x_bits = le_bits(&bits::input_bytes(builder, public_key_x));
+    let y_bits = le_bits(&bits::input_bytes(builder, public_key_y));
+    let key = PointVar {
+        x: Element::from_bits(&x_bits),
+        y: Element::from_bits(&y_bits),
+    };
+    let xx = nonnative::mul(builder, &key.x, &key.x);
+    let xx = nonnative::reduce(builder, &xx, &curve.p);
+    let xxx = nonnative::mul(builder, &xx, &key.x);
+    let yy = nonnative::mul(builder, &key.y, &key.y);
+    let ax = nonnative::mul(builder, &key.x, &Element::constant(&curve.a));
+    let residual = yy.sub(&xxx).sub(&ax).sub(&Element::constant(&curve.b));
+    let residual = nonnative::canonical(builder, &residual, &curve.p);
+    let mut key_valid = nonnative::is_zero(builder, &residual);
+    for coordinate in [&x_bits, &y_bits] {
+        let below = nonnative::less_than(builder, coordinate, &curve.p);
+        key_valid = bits::and(builder, &key_valid, &below);
+    }
+    let generator = PointVar::constant(&curve.generator);
+    let key = select(builder, &key_valid, &generator, &key);
+
+    // r in [1, n - 1] and s in [1, n / 2]; an invalid s is replaced by 1 so
+    // that it stays invertible
+    let signature = bits::input_bytes(builder, signature);
+    let (r_bits, s_bits) = (le_bits(&signature[..32]), le_bits(&signature[32..]));
+    let r_valid = in_range(builder, &r_bits, &curve.n);
+    let s_valid = in_range(builder, &s_bits, &((&curve.n >> 1) + 1u32));
+    let (r, s) = (Element::from_bits(&r_bits), Element::from_bits(&s_bits));
+    let one = Element::constant(&BigUint::one());
+    let s = nonnative::select(builder, &s_valid, &one, &s);
+
+    let message = bits::input_bytes(builder, hashed_message);
+    let z = Element::from_bits(&le_bits(&message[..message.len().min(HASH_BYTES)]));
+    let u1 = nonnative::divide_bits(builder, &z, &s, &curve.n);
+    let u2 = nonnative::divide_bits(builder, &r, &s, &curve.n);
+    let (x, infinite) = curve.double_scalar_mul(builder, &u1, &key, &u2);
+    let x = nonnative::canonical(builder, &x, &curve.p);
+    let x = nonnative::canonical(builder, &x, &curve.n);
+    let matches = nonnative::equal(builder, &x, &r);
+
+    let mut valid = key_valid;
+    for flag in [r_valid, s_valid, not(&infinite), matches] {
+        valid = bits::and(builder, &valid, &flag);
+    }
+    builder.assign_output(output, valid);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acir_to_r1cs::{AcirR1cs, WitnessMap};
+    use crate::test_utils::convert_opcode;
+    use serde_json::json;
+
+    /// A key, signature and hash, as produced by the k256 and p256 crates
+    #[derive(Clone, Copy)]
+    struct Vector {
+        x: [u8; 32],
+        y: [u8; 32],
+        signature: [u8; 64],
+        hash: [u8; 32],
+    }
+
+    fn bytes<const N: usize>(digits: &str) -> [u8; N] {
+        hex::decode(digits).unwrap().try_into().unwrap()
+    }
+
+    /// SHA-256 of "izi-noir ecdsa", signed with the private key 0x1111..11
+    fn secp256k1_vector() -> Vector {
+        Vector {
+            x: bytes("4f355bdcb7cc0af728ef3cceb9615d90684bb5b2ca5f859ab0f0b704075871aa"),
+            y: bytes("385b6b1b8ead809ca67454d9683fcf2ba03456d6fe2c4abe2b07f0fbdbb2f1c1"),
+            signature: bytes(
+                "aec5fae78c5f5f30acaf6210b11033fbe25ebdb87e878ecaf7eb2ca29202ac0c\
+                 6f6c35ed6c048247250af4825910bdfa46383dd26d4d3706b9f4f9169e692d92",
+            ),
+            hash: bytes("602e9897d3e3e65db22dc569c9f773abd5393fdfc7d75e6b0743b10fe3a15fa5"),
+        }
+    }
+
+    /// The same hash signed with the private key 0x2222..22
+    fn secp256r1_vector() -> Vector {
+        Vector {
+            x: bytes("d65a93977caa3d1b081852ff57a79e465f1660577304baead505dd3a48589cf3"),
+            y: bytes("50185e895372df6221ea3a137557e473fddb6755f05bd507c3c533fce9c91285"),
+            signature: bytes(
+                "1099945313a00583184b6d2cb071936d5f9774f619891dd117ddd0651ee7a80b\
+                 077c67c2d732193e7f438dbfbd65890e314efbd8e1f1fe1d5685c5f2f3cabb0e",
+            ),
+            hash: bytes("602e9897d3e3e65db22dc569c9f773abd5393fdfc7d75e6b0743b10fe3a15fa5"),
+        }
+    }
+
+    /// The signature with s replaced by n - s
+    fn high_s(curve: &Curve, signature: &[u8; 64]) -> [u8; 64] {
+        let s = &curve.n - BigUint::from_bytes_be(&signature[32..]);
+        let mut flipped = *signature;
+        flipped[32..].copy_from_slice(&s.to_bytes_be());
+        flipped
+    }
+
+    fn verify(curve: &Curve, v: &Vector) -> bool {
+        verify_signature(curve, &v.x, &v.y, &v.signature, &v.hash)
+    }
+
+    fn convert(name: &str) -> AcirR1cs {
+        let bytes = |witnesses: std::ops::RangeInclusive<u32>| -> Vec<serde_json::Value> {
+            witnesses
+                .map(|w| json!({ "witness": w, "num_bits": 8 }))
+                .collect()
+        };
+        convert_opcode(
+            json!({
+                "type": "BlackBoxFuncCall", "name": name,
+                "public_key_x": bytes(1..=32),
+                "public_key_y": bytes(33..=64),
+                "signature": bytes(65..=128),
+                "hashed_message": bytes(129..=160),
+                "output": 161
+            }),
+            161,
+            1..161,
+        )
+    }
+
+    /// The output of the gadget, after checking every constraint
+    fn gadget_output(r1cs: &AcirR1cs, v: &Vector) -> Fr {
+        let values = v.x.iter().chain(&v.y).chain(&v.signature).chain(&v.hash);
+        let mut witness: WitnessMap = (1..).zip(values.map(|&b| Fr::from(b))).collect();
+        r1cs.compute_aux_witnesses(&mut witness).unwrap();
+        assert!(r1cs
+            .constraints
+            .iter()
+            .all(|c| c.is_satisfied(&witness).unwrap()));
+        witness[&161]
+    }
+
+    #[test]
+    fn test_native() {
+        for (curve, v) in [
+            (Curve::secp256k1(), secp256k1_vector()),
+            (Curve::secp256r1(), secp256r1_vector()),
+        ] {
+            assert!(verify(&curve, &v));
+            assert!(curve.is_on_curve(&curve.offset_generator()));
+
+            let mut wrong_hash = v;
+            wrong_hash.hash[0] ^= 1;
+            let mut off_curve = v;
+            off_curve.y[31] ^= 1;
+            let high = Vector {
+                signature: high_s(&curve, &v.signature),
+                ..v
+            };
+            for v in [wrong_hash, off_curve, high] {
+                assert!(!verify(&curve, &v));
+            }
+
+            // Hashes are truncated to 32 bytes and need at least 16
+            let long = [v.hash, [0xff; 32]].concat();
+            assert!(verify_signature(&curve, &v.x, &v.y, &v.signature, &long));
+            assert!(!verify_signature(
+                &curve,
+                &v.x,
+                &v.y,
+                &v.signature,
+                &v.hash[..15]
+            ));
+        }
+    }
+
+    #[test]
+    fn test_verify_gadget_secp256k1() {
+        let curve = Curve::secp256k1();
+        let r1cs = convert("EcdsaSecp256k1");
+        let v = secp256k1_vector();
+        assert_eq!(gadget_output(&r1cs, &v), Fr::one());
+
+        // A wrong hash, a high s and an off-curve key all verify to 0
+        let mut wrong_hash = v;
+        wrong_hash.hash[5] ^= 0x10;
+        let high = Vector {
+            signature: high_s(&curve, &v.signature),
+            ..v
+        };
+        let mut off_curve = v;
+        off_curve.x[0] ^= 1;
+        for v in [wrong_hash, high, off_curve] {
+            assert!(!verify(&curve, &v));
+            assert_eq!(gadget_output(&r1cs, &v), Fr::zero());
+        }
+    }
+
+    #[test]
+    fn test_verify_gadget_secp256r1() {
+        let r1cs = convert("EcdsaSecp256r1");
+        let v = secp256r1_vector();
+        assert_eq!(gadget_output(&r1cs, &v), Fr::one());
+        let mut wrong_signature = v;
+        wrong_signature.signature[3] ^= 1;
+        assert!(!verify(&Curve::secp256r1(), &wrong_signature));
+        assert_eq!(gadget_output(&r1cs, &wrong_signature), Fr::zero());
+    }
+}
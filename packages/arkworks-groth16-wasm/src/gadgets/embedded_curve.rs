@@ -0,0 +1,337 @@
+//! Grumpkin black boxes: `EmbeddedCurveAdd`, `MultiScalarMul` and the
+//! older `FixedBaseScalarMul`.
+//!
+//! Points cross the ACIR boundary as (x, y, is_infinite) and scalars as
+//! (low, high) limbs of 128 bits, as in ACVM. Input points must be on the
+//! curve unless flagged infinite. ACVM also rejects scalars of at least
+//! the curve order; the circuit does not, as such a scalar multiplies to
+//! the same point as its reduction.
+
+use ark_bn254::{Fq, Fr};
+use ark_ff::{One, PrimeField, Zero};
+use num_bigint::BigUint;
+
+use super::grumpkin::{self, Point, PointVar};
+use super::{bits, combine, Lc};
+use crate::acir_to_r1cs::R1csBuilder;
+use crate::acir_types::FunctionInput;
+use crate::error::ArkworksError;
+
+/// A point from ACIR coordinates and infinity flag
+pub fn point(x: Fr, y: Fr, is_infinite: Fr) -> Result<Point, ArkworksError> {
+    if is_infinite.is_one() {
+        return Ok(Point::INFINITY);
+    }
+    if !is_infinite.is_zero() {
+        return Err(ArkworksError::InvalidInput(format!(
+            "Infinity flag must be 0 or 1, got {}",
+            is_infinite
+        )));
+    }
+    let point = Point::new(x, y);
+    if !point.is_on_curve() {
+        return Err(ArkworksError::InvalidInput(format!(
+            "Point ({}, {}) is not on Grumpkin",
+            x, y
+        )));
+    }
+    Ok(point)
+}
+
+/// ACIR coordinates and infinity flag of a point
+pub fn to_acir(point: &Point) -> [Fr; 3] {
+    let (x, y) = point.coordinates();
+    [x, y, Fr::from(point.infinity)]
+}
+
+/// A scalar from its 128-bit limbs, which must be below the curve order
+pub fn scalar(low: Fr, high: Fr) -> Result<BigUint, ArkworksError> {
+    let (low, high): (BigUint, BigUint) = (low.into(), high.into());
+    if low.bits() > 128 || high.bits() > 128 {
+        return Err(ArkworksError::InvalidInput(
+            "Scalar limbs must fit in 128 bits".to_string(),
+        ));
+    }
+    let scalar = (high << 128) + low;
+    if scalar >= BigUint::from(Fq::MODULUS) {
+        return Err(ArkworksError::InvalidInput(format!(
+            "Scalar {} is not below the Grumpkin order",
+            scalar
+        )));
+    }
+    Ok(scalar)
+}
+
+/// Sum of the two points in ACIR form
+pub fn embedded_curve_add(input1: [Fr; 3], input2: [Fr; 3]) -> Result<[Fr; 3], ArkworksError> {
+    let p = point(input1[0], input1[1], input1[2])?;
+    let q = point(input2[0], input2[1], input2[2])?;
+    Ok(to_acir(&p.add(&q)))
+}
+
+/// sum(s_i * P_i) for points as (x, y, is_infinite) triples and scalars as
+/// (low, high) pairs
+pub fn multi_scalar_mul(points: &[Fr], scalars: &[Fr]) -> Result<[Fr; 3], ArkworksError> {
+    check_lengths(points.len(), scalars.len())?;
+    let mut result = Point::INFINITY;
+    for (p, s) in points.chunks(3).zip(scalars.chunks(2)) {
+        let term = point(p[0], p[1], p[2])?.mul(&scalar(s[0], s[1])?);
+        result = result.add(&term);
+    }
+    Ok(to_acir(&result))
+}
+
+/// s * G for the standard generator, (0, 0) for the point at infinity
+pub fn fixed_base_scalar_mul(low: Fr, high: Fr) -> Result<(Fr, Fr), ArkworksError> {
+    Ok(grumpkin::generator().mul(&scalar(low, high)?).coordinates())
+}
+
+fn check_lengths(points: usize, scalars: usize) -> Result<(), ArkworksError> {
+    if !scalars.is_multiple_of(2) || points * 2 != scalars * 3 {
+        return Err(ArkworksError::InvalidInput(format!(
+            "MultiScalarMul takes point triples and scalar pairs, got {} and {} inputs",
+            points, scalars
+        )));
+    }
+    Ok(())
+}
+
+/// Constrain a point input: the flag is boolean and a finite point is on
+/// the curve; returns the coordinates and the flag
+fn point_input(builder: &mut R1csBuilder, inputs: [&FunctionInput; 3]) -> (PointVar, Lc) {
+    let [x, y, infinite] = inputs.map(FunctionInput::lc);
+    builder.add_constraint(infinite.clone(), infinite.clone(), infinite.clone());
+
+    // (1 - infinite) * (y^2 - x^3 + 17) = 0
+    let xx = vec![(Fr::one(), builder.product_lc(x.clone(), x.clone()))];
+    let xxx = vec![(Fr::one(), builder.product_lc(xx, x.clone()))];
+    let yy = vec![(Fr::one(), builder.product_lc(y.clone(), y.clone()))];
+    let constant = vec![(Fr::from(17u64), 0)];
+    let residual = combine(&[(Fr::one(), &yy), (-Fr::one(), &xxx), (Fr::one(), &constant)]);
+    let finite = combine(&[(Fr::one(), &bits::constant(true)), (-Fr::one(), &infinite)]);
+    builder.add_constraint(finite, residual, vec![]);
+
+    (PointVar { x, y }, infinite)
+}
+
+/// Little-endian bits of a scalar from its range-checked 128-bit limbs
+fn scalar_bits(builder: &mut R1csBuilder, low: &FunctionInput, high: &FunctionInput) -> Vec<Lc> {
+    let mut scalar = Vec::with_capacity(256);
+    for limb in [low, high] {
+        let limb = FunctionInput {
+            num_bits: 128,
+            ..limb.clone()
+        };
+        scalar.extend(bits::input_bits(builder, &limb));
+    }
+    scalar
+}
+
+fn assign_point(
+    builder: &mut R1csBuilder,
+    (x, y, infinite): (Lc, Lc, Lc),
+    outputs: (u32, u32, u32),
+) {
+    builder.assign_output(outputs.0, x);
+    builder.assign_output(outputs.1, y);
+    builder.assign_output(outputs.2, infinite);
+}
+
+/// Constrain an `EmbeddedCurveAdd` black box
+pub(crate) fn add_gadget(
+    builder: &mut R1csBuilder,
+    input1: &(FunctionInput, FunctionInput, FunctionInput),
+    input2: &(FunctionInput, FunctionInput, FunctionInput),
+    outputs: (u32, u32, u32),
+) {
+    let (p, p_infinite) = point_input(builder, [&input1.0, &input1.1, &input1.2]);
+    let (q, q_infinite) = point_input(builder, [&input2.0, &input2.1, &input2.2]);
+    let sum = grumpkin::add_complete(builder, (&p, &p_infinite), (&q, &q_infinite));
+    assign_point(builder, sum, outputs);
+}
+
+/// Constrain a `MultiScalarMul` black box
+///
+/// An infinite point is replaced by the generator, with its term flagged
+/// as zero, so the multiplication only sees finite points.
+pub(crate) fn multi_scalar_mul_gadget(
+    builder: &mut R1csBuilder,
+    points: &[FunctionInput],
+    scalars: &[FunctionInput],
+    outputs: (u32, u32, u32),
+) -> Result<(), ArkworksError> {
+    check_lengths(points.len(), scalars.len())?;
+    let generator = PointVar::constant(&grumpkin::generator());
+    let mut terms = Vec::with_capacity(scalars.len() / 2);
+    for (p, s) in points.chunks(3).zip(scalars.chunks(2)) {
+        let (point, infinite) = point_input(builder, [&p[0], &p[1], &p[2]]);
+        let finite = grumpkin::select(builder, &infinite, &point, &generator);
+        terms.push((finite, infinite, scalar_bits(builder, &s[0], &s[1])));
+    }
+    let result = grumpkin::variable_base_msm(builder, &terms);
+    assign_point(builder, result, outputs);
+    Ok(())
+}
+
+/// Constrain a `FixedBaseScalarMul` black box
+pub(crate) fn fixed_base_scalar_mul_gadget(
+    builder: &mut R1csBuilder,
+    low: &FunctionInput,
+    high: &FunctionInput,
+    outputs: (u32, u32),
+) {
+    let scalar = scalar_bits(builder, low, high);
+    let terms = [(scalar, grumpkin::generator())];
+    let (x, y, _) = grumpkin::fixed_base_msm(builder, &terms, &Point::INFINITY);
+    builder.assign_output(outputs.0, x);
+    builder.assign_output(outputs.1, y);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acir_to_r1cs::{AcirR1cs, WitnessMap};
+    use crate::test_utils::convert_opcode;
+    use serde_json::json;
+
+    fn inputs(witnesses: std::ops::RangeInclusive<u32>) -> Vec<serde_json::Value> {
+        witnesses
+            .map(|w| json!({ "witness": w, "num_bits": 254 }))
+            .collect()
+    }
+
+    /// Solve the auxiliary witnesses from `values` on witnesses 1, 2, ...
+    /// and check every constraint
+    fn satisfied(r1cs: &AcirR1cs, values: &[Fr]) -> Option<WitnessMap> {
+        let mut witness: WitnessMap = (1..).zip(values.iter().copied()).collect();
+        r1cs.compute_aux_witnesses(&mut witness).unwrap();
+        r1cs.constraints
+            .iter()
+            .all(|c| c.is_satisfied(&witness).unwrap())
+            .then_some(witness)
+    }
+
+    fn multiple(k: u64) -> Point {
+        grumpkin::generator().mul(&BigUint::from(k))
+    }
+
+    #[test]
+    fn test_native() {
+        let g = grumpkin::generator();
+        assert!(g.is_on_curve());
+        let infinity = [Fr::zero(), Fr::zero(), Fr::one()];
+        assert_eq!(
+            embedded_curve_add(to_acir(&g), to_acir(&g.neg())).unwrap(),
+            infinity
+        );
+        assert_eq!(
+            embedded_curve_add(infinity, to_acir(&g)).unwrap(),
+            to_acir(&g)
+        );
+
+        let points = [to_acir(&g), infinity].concat();
+        let scalars = [3u64, 0, 5, 0].map(Fr::from);
+        assert_eq!(
+            multi_scalar_mul(&points, &scalars).unwrap(),
+            to_acir(&multiple(3))
+        );
+        assert_eq!(
+            fixed_base_scalar_mul(Fr::from(3u64), Fr::zero()).unwrap(),
+            multiple(3).coordinates()
+        );
+
+        // Out-of-range limbs and scalars, and points off the curve
+        assert!(scalar(Fr::from(BigUint::from(1u32) << 128), Fr::zero()).is_err());
+        let order = BigUint::from(Fq::MODULUS);
+        let low = Fr::from(&order % (BigUint::from(1u32) << 128));
+        let high = Fr::from(&order >> 128);
+        assert!(scalar(low, high).is_err());
+        assert!(scalar(low - Fr::one(), high).is_ok());
+        assert!(point(Fr::one(), Fr::one(), Fr::zero()).is_err());
+        assert!(point(Fr::one(), Fr::one(), Fr::from(2u64)).is_err());
+    }
+
+    #[test]
+    fn test_add_gadget() {
+        let r1cs = convert_opcode(
+            json!({
+                "type": "BlackBoxFuncCall", "name": "EmbeddedCurveAdd",
+                "input1": inputs(1..=3), "input2": inputs(4..=6), "outputs": [7, 8, 9]
+            }),
+            9,
+            1..=6,
+        );
+        let g = multiple(1);
+        let cases = [
+            (g, multiple(2)),
+            (g, g),
+            (g, g.neg()),
+            (Point::INFINITY, g),
+            (g, Point::INFINITY),
+            (Point::INFINITY, Point::INFINITY),
+        ];
+        for (p, q) in cases {
+            let (p, q) = (to_acir(&p), to_acir(&q));
+            let witness = satisfied(&r1cs, &[p, q].concat()).unwrap();
+            let outputs = [witness[&7], witness[&8], witness[&9]];
+            assert_eq!(outputs, embedded_curve_add(p, q).unwrap());
+        }
+
+        // A point off the curve, and a wrong infinity flag
+        let off_curve = [Fr::one(), Fr::one(), Fr::zero()];
+        assert!(satisfied(&r1cs, &[off_curve, to_acir(&g)].concat()).is_none());
+        let mut values = [to_acir(&g), to_acir(&g.neg())].concat();
+        values.extend([Fr::zero(), Fr::zero(), Fr::zero()]);
+        assert!(satisfied(&r1cs, &values).is_none());
+    }
+
+    #[test]
+    fn test_multi_scalar_mul_gadget() {
+        let r1cs = convert_opcode(
+            json!({
+                "type": "BlackBoxFuncCall", "name": "MultiScalarMul",
+                "points": inputs(1..=6), "scalars": inputs(7..=10), "outputs": [11, 12, 13]
+            }),
+            13,
+            1..=10,
+        );
+        let high = Fr::from(0x0123_4567_89ab_cdef_0123_4567_89ab_cdefu128);
+        let s1 = [Fr::from(u128::MAX), high];
+        let s2 = [Fr::from(7u64), Fr::zero()];
+        let (p, q) = (multiple(5), multiple(11));
+        let cases = [
+            ([p, q], [s1, s2]),
+            ([p, Point::INFINITY], [s1, s2]),
+            ([p, q], [[Fr::zero(), Fr::zero()], s2]),
+            // s * P + s * (-P) is the point at infinity
+            ([p, p.neg()], [s1, s1]),
+        ];
+        for (points, scalars) in cases {
+            let points = [to_acir(&points[0]), to_acir(&points[1])].concat();
+            let scalars = scalars.concat();
+            let witness = satisfied(&r1cs, &[points.clone(), scalars.clone()].concat()).unwrap();
+            let outputs = [witness[&11], witness[&12], witness[&13]];
+            assert_eq!(outputs, multi_scalar_mul(&points, &scalars).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_fixed_base_scalar_mul_gadget() {
+        let r1cs = convert_opcode(
+            json!({
+                "type": "BlackBoxFuncCall", "name": "FixedBaseScalarMul",
+                "low": { "witness": 1, "num_bits": 254 },
+                "high": { "witness": 2, "num_bits": 254 },
+                "outputs": [3, 4]
+            }),
+            4,
+            1..=2,
+        );
+        let (low, high) = (Fr::from(u128::MAX), Fr::from(42u64));
+        let witness = satisfied(&r1cs, &[low, high]).unwrap();
+        assert_eq!(
+            (witness[&3], witness[&4]),
+            fixed_base_scalar_mul(low, high).unwrap()
+        );
+    }
+}
@@ -0,0 +1,546 @@
+//! Grumpkin, the embedded curve of BN254.
+//!
+//! Grumpkin is y^2 = x^3 - 17 over the BN254 scalar field, so its points
+//! are pairs of native field elements. Generators are derived the way
+//! Barretenberg does, by hashing a domain separator and an index to the
+//! curve with BLAKE3.
+//!
+//! In the circuit, fixed-base scalar multiplication uses 3-bit windows:
+//! each window looks up one of 8 constant points (4 constraints) and adds
+//! it to an accumulator with the incomplete formula (3 constraints), about
+//! 600 constraints per 254-bit scalar. Variable bases use 2-bit windows
+//! over a shared accumulator: about 7 constraints per scalar bit and term,
+//! plus 4 per bit for the doublings.
+
+use ark_bn254::Fr;
+use ark_ff::{AdditiveGroup, BigInteger, Field, MontFp, One, PrimeField, Zero};
+use num_bigint::BigUint;
+
+use super::blake3::blake3;
+use super::{bits, combine, Lc};
+use crate::acir_to_r1cs::R1csBuilder;
+
+/// Bits per window of the fixed-base multiplication
+const WINDOW: usize = 3;
+
+/// Bits per window of the variable-base multiplication
+const VARIABLE_WINDOW: usize = 2;
+
+/// Domain of the generator the multiplication accumulator starts from
+const OFFSET_DOMAIN: &[u8] = b"grumpkin_msm_offset";
+
+/// An affine Grumpkin point
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Point {
+    pub x: Fr,
+    pub y: Fr,
+    pub infinity: bool,
+}
+
+impl Point {
+    /// The point at infinity
+    pub const INFINITY: Point = Point {
+        x: Fr::ZERO,
+        y: Fr::ZERO,
+        infinity: true,
+    };
+
+    /// A finite point, not checked to be on the curve
+    pub fn new(x: Fr, y: Fr) -> Self {
+        Point {
+            x,
+            y,
+            infinity: false,
+        }
+    }
+
+    /// Whether the point satisfies the curve equation
+    pub fn is_on_curve(&self) -> bool {
+        self.infinity || self.y.square() == self.x.square() * self.x - Fr::from(17u64)
+    }
+
+    pub fn neg(&self) -> Point {
+        Point {
+            y: -self.y,
+            ..*self
+        }
+    }
+
+    pub fn double(&self) -> Point {
+        if self.infinity || self.y.is_zero() {
+            return Point::INFINITY;
+        }
+        let lambda = self.x.square() * Fr::from(3u64) / self.y.double();
+        self.with_slope(lambda, self)
+    }
+
+    pub fn add(&self, other: &Point) -> Point {
+        if self.infinity {
+            return *other;
+        }
+        if other.infinity {
+            return *self;
+        }
+        if self.x == other.x {
+            return if self.y == other.y {
+                self.double()
+            } else {
+                Point::INFINITY
+            };
+        }
+        let lambda = (other.y - self.y) / (other.x - self.x);
+        self.with_slope(lambda, other)
+    }
+
+    /// Third intersection of the line through self and other, reflected
+    fn with_slope(&self, lambda: Fr, other: &Point) -> Point {
+        let x = lambda.square() - self.x - other.x;
+        Point::new(x, lambda * (self.x - x) - self.y)
+    }
+
+    /// Multiply by a non-negative scalar
+    pub fn mul(&self, scalar: &BigUint) -> Point {
+        let mut result = Point::INFINITY;
+        for i in (0..scalar.bits()).rev() {
+            result = result.double();
+            if scalar.bit(i) {
+                result = result.add(self);
+            }
+        }
+        result
+    }
+
+    /// Coordinates as ACIR returns them, (0, 0) for the point at infinity
+    pub fn coordinates(&self) -> (Fr, Fr) {
+        if self.infinity {
+            (Fr::zero(), Fr::zero())
+        } else {
+            (self.x, self.y)
+        }
+    }
+}
+
+/// The standard generator, (1, sqrt(-16))
+pub fn generator() -> Point {
+    Point::new(
+        Fr::one(),
+        MontFp!("17631683881184975370165255887551781615748388533673675138860"),
+    )
+}
+
+/// Hash a seed to a point, as Barretenberg's `hash_to_curve`
+///
+/// x is the 512-bit BLAKE3 output of seed || attempt || {0, 1} reduced
+/// into the field, retried with the next attempt until x^3 - 17 is a
+/// square; the parity of y follows the top bit of the first hash.
+pub fn hash_to_curve(seed: &[u8]) -> Point {
+    (0..=u8::MAX)
+        .find_map(|attempt| {
+            let mut target = [seed, &[attempt, 0]].concat();
+            let hi = blake3(&target);
+            *target.last_mut().expect("non-empty target") = 1;
+            let lo = blake3(&target);
+
+            let x = Fr::from_be_bytes_mod_order(&[hi, lo].concat());
+            let y = (x.square() * x - Fr::from(17u64)).sqrt()?;
+            let odd = hi[0] > 127;
+            let y = if y.into_bigint().is_odd() == odd {
+                y
+            } else {
+                -y
+            };
+            Some(Point::new(x, y))
+        })
+        .expect("a square within 256 attempts")
+}
+
+/// Generators `starting_index..starting_index + count` of a domain
+///
+/// Generator i hashes the BLAKE3 digest of the domain followed by i as
+/// 4 big-endian bytes, zero-padded to 64 bytes.
+pub fn derive_generators(domain: &[u8], count: usize, starting_index: u32) -> Vec<Point> {
+    let mut preimage = [0u8; 64];
+    preimage[..32].copy_from_slice(&blake3(domain));
+    (0..count as u32)
+        .map(|i| {
+            preimage[32..36].copy_from_slice(&(starting_index + i).to_be_bytes());
+            hash_to_curve(&preimage)
+        })
+        .collect()
+}
+
+/// A finite point in the circuit
+#[derive(Clone, Debug)]
+pub(crate) struct PointVar {
+    pub(crate) x: Lc,
+    pub(crate) y: Lc,
+}
+
+impl PointVar {
+    pub(crate) fn constant(point: &Point) -> Self {
+        PointVar {
+            x: vec![(point.x, 0)],
+            y: vec![(point.y, 0)],
+        }
+    }
+}
+
+/// p + q for points with distinct x coordinates, 3 constraints
+pub(crate) fn add_incomplete(builder: &mut R1csBuilder, p: &PointVar, q: &PointVar) -> PointVar {
+    let dx = combine(&[(Fr::one(), &q.x), (-Fr::one(), &p.x)]);
+    let dy = combine(&[(Fr::one(), &q.y), (-Fr::one(), &p.y)]);
+    let lambda = vec![(Fr::one(), builder.quotient(dy, dx))];
+    chord(builder, p, &q.x, lambda)
+}
+
+/// The sum of p and a point with x coordinate `qx` on a line of slope
+/// `lambda` through p, 2 constraints
+fn chord(builder: &mut R1csBuilder, p: &PointVar, qx: &Lc, lambda: Lc) -> PointVar {
+    let x = builder.quadratic(
+        lambda.clone(),
+        lambda.clone(),
+        combine(&[(-Fr::one(), &p.x), (-Fr::one(), qx)]),
+    );
+    let x = vec![(Fr::one(), x)];
+    let run = combine(&[(Fr::one(), &p.x), (-Fr::one(), &x)]);
+    let y = builder.quadratic(lambda, run, combine(&[(-Fr::one(), &p.y)]));
+    PointVar {
+        x,
+        y: vec![(Fr::one(), y)],
+    }
+}
+
+/// 1 - bit
+fn not(bit: &Lc) -> Lc {
+    combine(&[(Fr::one(), &bits::constant(true)), (-Fr::one(), bit)])
+}
+
+/// p + q, 4 constraints
+///
+/// Unlike `add_incomplete`, equal x coordinates make the constraints
+/// unsatisfiable instead of leaving the slope free, so this is sound for
+/// prover-chosen points.
+pub(crate) fn add_checked(builder: &mut R1csBuilder, p: &PointVar, q: &PointVar) -> PointVar {
+    let dx = combine(&[(Fr::one(), &q.x), (-Fr::one(), &p.x)]);
+    builder.quotient(bits::constant(true), dx);
+    add_incomplete(builder, p, q)
+}
+
+/// 2p, 4 constraints; no finite Grumpkin point has y = 0
+pub(crate) fn double(builder: &mut R1csBuilder, p: &PointVar) -> PointVar {
+    let xx = builder.product_lc(p.x.clone(), p.x.clone());
+    let lambda = builder.quotient(
+        vec![(Fr::from(3u64), xx)],
+        combine(&[(Fr::from(2u64), &p.y)]),
+    );
+    chord(builder, p, &p.x, vec![(Fr::one(), lambda)])
+}
+
+/// `if_one` when the bit is set, `if_zero` otherwise, 2 constraints
+pub(crate) fn select(
+    builder: &mut R1csBuilder,
+    bit: &Lc,
+    if_zero: &PointVar,
+    if_one: &PointVar,
+) -> PointVar {
+    let mut pick = |a: &Lc, b: &Lc| {
+        let diff = combine(&[(Fr::one(), b), (-Fr::one(), a)]);
+        let offset = bits::and(builder, bit, &diff);
+        combine(&[(Fr::one(), a), (Fr::one(), &offset)])
+    };
+    PointVar {
+        x: pick(&if_zero.x, &if_one.x),
+        y: pick(&if_zero.y, &if_one.y),
+    }
+}
+
+/// p + q for a constant q, as ACIR coordinates and infinity flag
+///
+/// Equal x coordinates must mean q = -p, giving (0, 0, 1); q = p makes the
+/// constraints unsatisfiable, which honest inputs only reach through a
+/// discrete log relation with the offset generator.
+pub(crate) fn add_constant(builder: &mut R1csBuilder, p: &PointVar, q: &Point) -> (Lc, Lc, Lc) {
+    if q.infinity {
+        return (p.x.clone(), p.y.clone(), bits::constant(false));
+    }
+    let q = PointVar::constant(q);
+    let dx = combine(&[(Fr::one(), &q.x), (-Fr::one(), &p.x)]);
+    let dy = combine(&[(Fr::one(), &q.y), (-Fr::one(), &p.y)]);
+    let same_x = builder.is_zero(dx.clone());
+    let y_sum = combine(&[(Fr::one(), &q.y), (Fr::one(), &p.y)]);
+    builder.add_constraint(same_x.clone(), y_sum, vec![]);
+    let distinct = not(&same_x);
+
+    // lambda * dx = distinct * dy, which leaves lambda free when dx = 0
+    let numerator = vec![(Fr::one(), builder.product_lc(distinct.clone(), dy))];
+    let lambda = vec![(Fr::one(), builder.quotient(numerator, dx))];
+    let sum = chord(builder, p, &q.x, lambda);
+    let x = builder.product_lc(distinct.clone(), sum.x);
+    let y = builder.product_lc(distinct, sum.y);
+    (vec![(Fr::one(), x)], vec![(Fr::one(), y)], same_x)
+}
+
+/// p + q for points flagged as infinite or not, as ACIR coordinates and
+/// infinity flag, about 20 constraints
+///
+/// The chord and tangent slopes are folded into one, picked by whether the
+/// x coordinates agree. The sum is infinite when both points are, or when
+/// both are finite with equal x and opposite y. Coordinates of an infinite
+/// point are not used.
+pub(crate) fn add_complete(
+    builder: &mut R1csBuilder,
+    (p, p_infinite): (&PointVar, &Lc),
+    (q, q_infinite): (&PointVar, &Lc),
+) -> (Lc, Lc, Lc) {
+    let dx = combine(&[(Fr::one(), &q.x), (-Fr::one(), &p.x)]);
+    let dy = combine(&[(Fr::one(), &q.y), (-Fr::one(), &p.y)]);
+    let same_x = builder.is_zero(dx.clone());
+    let finite = bits::and(builder, &not(p_infinite), &not(q_infinite));
+
+    // lambda * (dx + same_x * 2y1) = finite * (dy + same_x * (3x1^2 - dy))
+    let tangent_denominator = bits::and(builder, &same_x, &combine(&[(Fr::from(2u64), &p.y)]));
+    let denominator = combine(&[(Fr::one(), &dx), (Fr::one(), &tangent_denominator)]);
+    let xx = vec![(Fr::one(), builder.product_lc(p.x.clone(), p.x.clone()))];
+    let tangent = combine(&[(Fr::from(3u64), &xx), (-Fr::one(), &dy)]);
+    let tangent_numerator = bits::and(builder, &same_x, &tangent);
+    let numerator = combine(&[(Fr::one(), &dy), (Fr::one(), &tangent_numerator)]);
+    let numerator = bits::and(builder, &finite, &numerator);
+    let lambda = vec![(Fr::one(), builder.quotient(numerator, denominator))];
+    let sum = chord(builder, p, &q.x, lambda);
+
+    let y_sum = combine(&[(Fr::one(), &p.y), (Fr::one(), &q.y)]);
+    let opposite = builder.is_zero(y_sum);
+    let finite_same_x = bits::and(builder, &finite, &same_x);
+    let cancel = bits::and(builder, &finite_same_x, &opposite);
+    let both_infinite = bits::and(builder, p_infinite, q_infinite);
+
+    // The result is q when only p is infinite, p when only q is
+    let only_p = combine(&[(Fr::one(), p_infinite), (-Fr::one(), &both_infinite)]);
+    let only_q = combine(&[(Fr::one(), q_infinite), (-Fr::one(), &both_infinite)]);
+    let use_sum = combine(&[(Fr::one(), &finite), (-Fr::one(), &cancel)]);
+    let mut pick = |from_sum: &Lc, from_q: &Lc, from_p: &Lc| {
+        let parts = [
+            bits::and(builder, &use_sum, from_sum),
+            bits::and(builder, &only_p, from_q),
+            bits::and(builder, &only_q, from_p),
+        ];
+        combine(&[
+            (Fr::one(), &parts[0]),
+            (Fr::one(), &parts[1]),
+            (Fr::one(), &parts[2]),
+        ])
+    };
+    let x = pick(&sum.x, &q.x, &p.x);
+    let y = pick(&sum.y, &q.y, &p.y);
+    let infinite = combine(&[(Fr::one(), &both_infinite), (Fr::one(), &cancel)]);
+    (x, y, infinite)
+}
+
+/// Select `table[m]` for the little-endian value m of up to 3 bits
+///
+/// The coordinates are multilinear in the bits: the table is turned into
+/// coefficients of the products of every subset of bits.
+fn lookup(builder: &mut R1csBuilder, window: &[Lc], table: &[Point]) -> PointVar {
+    // monomials[mask] is the product of the bits in mask
+    let mut monomials: Vec<Lc> = vec![bits::constant(true)];
+    for (i, bit) in window.iter().enumerate() {
+        for mask in 0..1 << i {
+            let monomial = bits::and(builder, &monomials[mask], bit);
+            monomials.push(monomial);
+        }
+    }
+
+    let mut xs: Vec<Fr> = table.iter().map(|point| point.x).collect();
+    let mut ys: Vec<Fr> = table.iter().map(|point| point.y).collect();
+    for i in 0..window.len() {
+        for mask in 0..table.len() {
+            if mask & (1 << i) != 0 {
+                let (x, y) = (xs[mask ^ (1 << i)], ys[mask ^ (1 << i)]);
+                xs[mask] -= x;
+                ys[mask] -= y;
+            }
+        }
+    }
+    let evaluate = |coefficients: &[Fr]| {
+        let parts: Vec<(Fr, &Lc)> = coefficients.iter().copied().zip(&monomials).collect();
+        combine(&parts)
+    };
+    PointVar {
+        x: evaluate(&xs),
+        y: evaluate(&ys),
+    }
+}
+
+/// The generator the accumulator starts from
+fn offset_generator() -> Point {
+    derive_generators(OFFSET_DOMAIN, 1, 0)[0]
+}
+
+/// sum(s_i * G_i) + constant for scalars given as little-endian bits and
+/// constant bases, as ACIR coordinates and infinity flag
+///
+/// Window j of a scalar adds (m + 1) * 8^j * G for its value m, so no
+/// table entry is the point at infinity, and the accumulator starts from
+/// an independent generator so it never meets a table point. The offset
+/// and the extra 8^j * G are subtracted from the constant, which is added
+/// last with the complete formula.
+pub(crate) fn fixed_base_msm(
+    builder: &mut R1csBuilder,
+    terms: &[(Vec<Lc>, Point)],
+    constant: &Point,
+) -> (Lc, Lc, Lc) {
+    let offset = offset_generator();
+    let mut acc = PointVar::constant(&offset);
+    let mut remainder = constant.add(&offset.neg());
+    for (scalar, base) in terms {
+        let mut window_base = *base;
+        for window in scalar.chunks(WINDOW) {
+            let mut table = Vec::with_capacity(1 << window.len());
+            let mut entry = window_base;
+            for _ in 0..1 << window.len() {
+                table.push(entry);
+                entry = entry.add(&window_base);
+            }
+            remainder = remainder.add(&window_base.neg());
+
+            let point = lookup(builder, window, &table);
+            acc = add_incomplete(builder, &acc, &point);
+            for _ in 0..WINDOW {
+                window_base = window_base.double();
+            }
+        }
+    }
+    add_constant(builder, &acc, &remainder)
+}
+
+/// Select `table[m]` for the little-endian value m of some bits, with a
+/// tree of `select`s
+fn lookup_variable(builder: &mut R1csBuilder, window: &[Lc], table: &[PointVar]) -> PointVar {
+    let mut table = table.to_vec();
+    for bit in window {
+        table = table
+            .chunks(2)
+            .map(|pair| select(builder, bit, &pair[0], &pair[1]))
+            .collect();
+    }
+    table.swap_remove(0)
+}
+
+/// sum(s_i * P_i) for scalars given as little-endian bits and finite bases,
+/// as ACIR coordinates and infinity flag
+///
+/// A term whose `zero` flag is set contributes nothing. Windows are
+/// processed from the top: the accumulator, started at an offset generator,
+/// is doubled twice and then each term adds m * P_i from a table built in
+/// the circuit, unless m = 0 or the term is zero. Additions are checked, so
+/// a prover-chosen base cannot open up the slopes; the doubled offset is
+/// removed at the end with the complete formula.
+pub(crate) fn variable_base_msm(
+    builder: &mut R1csBuilder,
+    terms: &[(PointVar, Lc, Vec<Lc>)],
+) -> (Lc, Lc, Lc) {
+    // Tables of m * P, with P also standing in for 0 * P, which is skipped
+    let tables: Vec<Vec<PointVar>> = terms
+        .iter()
+        .map(|(base, _, _)| {
+            let mut table = vec![base.clone(), base.clone()];
+            for m in 2..1 << VARIABLE_WINDOW {
+                let entry = if m == 2 {
+                    double(builder, base)
+                } else {
+                    add_checked(builder, &table[m - 1], base)
+                };
+                table.push(entry);
+            }
+            table
+        })
+        .collect();
+    let windows = terms
+        .iter()
+        .map(|(_, _, scalar)| scalar.len().div_ceil(VARIABLE_WINDOW))
+        .max()
+        .unwrap_or(0);
+
+    let mut offset = offset_generator();
+    let mut acc = PointVar::constant(&offset);
+    for j in (0..windows).rev() {
+        if j + 1 < windows {
+            for _ in 0..VARIABLE_WINDOW {
+                acc = double(builder, &acc);
+                offset = offset.double();
+            }
+        }
+        for ((_, zero, scalar), table) in terms.iter().zip(&tables) {
+            let window: Vec<Lc> = (j * VARIABLE_WINDOW..(j + 1) * VARIABLE_WINDOW)
+                .map(|i| scalar.get(i).cloned().unwrap_or_default())
+                .collect();
+            let mut all_clear = bits::constant(true);
+            for bit in &window {
+                all_clear = bits::and(builder, &all_clear, &not(bit));
+            }
+            let adds = bits::and(builder, &not(&all_clear), &not(zero));
+
+            let entry = lookup_variable(builder, &window, table);
+            let sum = add_checked(builder, &acc, &entry);
+            acc = select(builder, &adds, &acc, &sum);
+        }
+    }
+    add_constant(builder, &acc, &offset.neg())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acir_to_r1cs::parse_field_element;
+
+    #[test]
+    fn test_group_law() {
+        let g = derive_generators(b"DEFAULT_DOMAIN_SEPARATOR", 1, 0)[0];
+        assert!(g.is_on_curve());
+        assert_eq!(g.add(&g), g.double());
+        assert_eq!(g.double().add(&g), g.mul(&BigUint::from(3u32)));
+        assert_eq!(g.add(&g.neg()), Point::INFINITY);
+        assert_eq!(Point::INFINITY.add(&g), g);
+        assert_eq!(Point::INFINITY.coordinates(), (Fr::zero(), Fr::zero()));
+
+        // The group order is the BN254 base field modulus
+        let order = BigUint::from(ark_bn254::Fq::MODULUS);
+        assert_eq!(g.mul(&order), Point::INFINITY);
+    }
+
+    #[test]
+    fn test_barretenberg_generators() {
+        let point = |x: &str, y: &str| {
+            Point::new(
+                parse_field_element(x).unwrap(),
+                parse_field_element(y).unwrap(),
+            )
+        };
+        let defaults = derive_generators(b"DEFAULT_DOMAIN_SEPARATOR", 2, 0);
+        assert_eq!(
+            defaults[0],
+            point(
+                "0x083e7911d835097629f0067531fc15cafd79a89beecb39903f69572c636f4a5a",
+                "0x1a7f5efaad7f315c25a918f30cc8d7333fccab7ad7c90f14de81bcc528f9935d",
+            )
+        );
+        assert_eq!(
+            defaults[1],
+            point(
+                "0x054aa86a73cb8a34525e5bbed6e43ba1198e860f5f3950268f71df4591bde402",
+                "0x209dcfbf2cfb57f9f6046f44d71ac6faf87254afc7407c04eb621a6287cac126",
+            )
+        );
+        assert_eq!(
+            derive_generators(b"DEFAULT_DOMAIN_SEPARATOR", 1, 1)[0],
+            defaults[1]
+        );
+        assert_eq!(
+            derive_generators(b"pedersen_hash_length", 1, 0)[0],
+            point(
+                "0x2df8b940e5890e4e1377e05373fae69a1d754f6935e6a780b666947431f2cdcd",
+                "0x2ecd88d15967bc53b885912e0d16866154acb6aac2d3f85e27ca7eefb2c19083",
+            )
+        );
+    }
+}
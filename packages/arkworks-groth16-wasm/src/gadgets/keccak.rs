@@ -0,0 +1,379 @@
+//! Keccak-f[1600] permutation and Keccak-256 hash.
+//!
+//! The gadget works on bits (see `bits`). Per round θ costs 3200
+//! constraints (four XORs per column bit, one for D and one per state
+//! bit), χ two per state bit and ρ, π, ι nothing, so a permutation is
+//! about 154k constraints.
+
+use ark_bn254::Fr;
+use ark_ff::One;
+
+use super::{bits, combine, Lc};
+use crate::acir_to_r1cs::R1csBuilder;
+use crate::acir_types::FunctionInput;
+use crate::error::ArkworksError;
+
+/// Sponge rate of Keccak-256 in bytes
+const RATE: usize = 136;
+
+/// Round constants for ι
+const ROUND_CONSTANTS: [u64; 24] = [
+    0x0000000000000001, 0x0000000000008082, 0x800000000000808a, 0x8000000080008000,
+    0x000000000000808b, 0x0000000080000001, 0x8000000080008081, 0x8000000000008009,
+    0x000000000000008a, 0x0000000000000088, 0x0000000080008009, 0x000000008000000a,
+    0x000000008000808b, 0x800000000000008b, 0x8000000000008089, 0x8000000000008003,
+    0x8000000000008002, 0x8000000000000080, 0x000000000000800a, 0x800000008000000a,
+    0x8000000080008081, 0x8000000000008080, 0x0000000080000001, 0x8000000080008008,
+];
+
+/// ρ rotation offsets, by lane index x + 5y
+const ROTATIONS: [u32; 25] = [
+    0, 1, 62, 28, 27, 36, 44, 6, 55, 20, 3, 10, 43, 25, 39, 41, 45, 15, 21, 8, 18, 2, 61, 56, 14,
+];
+
+/// Lane receiving lane x + 5y in π
+fn pi(lane: usize) -> usize {
+    let (x, y) = (lane % 5, lane / 5);
+    y + 5 * ((2 * x + 3 * y) % 5)
+}
+
+/// Apply Keccak-f[1600] to 25 lanes indexed x + 5y
+pub fn permutation(mut state: [u64; 25]) -> [u64; 25] {
+    for rc in ROUND_CONSTANTS {
+        let c: [u64; 5] =
+            std::array::from_fn(|x| (0..5).fold(0, |acc, y| acc ^ state[x + 5 * y]));
+        for (lane, a) in state.iter_mut().enumerate() {
+            let x = lane % 5;
+            *a ^= c[(x + 4) % 5] ^ c[(x + 1) % 5].rotate_left(1);
+        }
+
+        let mut b = [0u64; 25];
+        for (lane, a) in state.iter().enumerate() {
+            b[pi(lane)] = a.rotate_left(ROTATIONS[lane]);
+        }
+
+        for (lane, a) in state.iter_mut().enumerate() {
+            let (x, y) = (lane % 5, lane / 5);
+            *a = b[lane] ^ (!b[(x + 1) % 5 + 5 * y] & b[(x + 2) % 5 + 5 * y]);
+        }
+        state[0] ^= rc;
+    }
+    state
+}
+
+/// Hash a message with Keccak-256 (the original padding, as Ethereum uses)
+pub fn keccak256(message: &[u8]) -> [u8; 32] {
+    let mut state = [0u64; 25];
+    for block in pad(message).chunks(RATE) {
+        for (lane, bytes) in state.iter_mut().zip(block.chunks(8)) {
+            *lane ^= u64::from_le_bytes(bytes.try_into().expect("8-byte chunk"));
+        }
+        state = permutation(state);
+    }
+
+    let mut output = [0u8; 32];
+    for (bytes, lane) in output.chunks_mut(8).zip(state) {
+        bytes.copy_from_slice(&lane.to_le_bytes());
+    }
+    output
+}
+
+/// pad10*1 with Keccak's 0x01 domain byte
+fn pad(message: &[u8]) -> Vec<u8> {
+    let mut padded = message.to_vec();
+    padded.push(0x01);
+    padded.resize(padded.len().div_ceil(RATE) * RATE, 0);
+    *padded.last_mut().expect("non-empty padding") |= 0x80;
+    padded
+}
+
+/// Constrain 25 lanes of 64 bits to their Keccak-f[1600] permutation
+fn permutation_bits(builder: &mut R1csBuilder, mut state: Vec<Vec<Lc>>) -> Vec<Vec<Lc>> {
+    // Rotating left by r is rotating right by 64 - r
+    let rotate_left = |lane: &[Lc], r: u32| bits::rotate_right(lane, (64 - r as usize) % 64);
+
+    for rc in ROUND_CONSTANTS {
+        // θ
+        let c: Vec<Vec<Lc>> = (0..5)
+            .map(|x| {
+                (1..5).fold(state[x].clone(), |acc, y| {
+                    bits::xor_words(builder, &acc, &state[x + 5 * y])
+                })
+            })
+            .collect();
+        let d: Vec<Vec<Lc>> = (0..5)
+            .map(|x| bits::xor_words(builder, &c[(x + 4) % 5], &rotate_left(&c[(x + 1) % 5], 1)))
+            .collect();
+        for (lane, a) in state.iter_mut().enumerate() {
+            *a = bits::xor_words(builder, a, &d[lane % 5]);
+        }
+
+        // ρ and π
+        let mut b = vec![Vec::new(); 25];
+        for (lane, a) in state.iter().enumerate() {
+            b[pi(lane)] = rotate_left(a, ROTATIONS[lane]);
+        }
+
+        // χ: b0 XOR (NOT b1 AND b2), with NOT b1 AND b2 = b2 - b1 * b2
+        for (lane, a) in state.iter_mut().enumerate() {
+            let (x, y) = (lane % 5, lane / 5);
+            let (b1, b2) = (&b[(x + 1) % 5 + 5 * y], &b[(x + 2) % 5 + 5 * y]);
+            *a = (0..64)
+                .map(|i| {
+                    let product = bits::and(builder, &b1[i], &b2[i]);
+                    let masked = combine(&[(Fr::one(), &b2[i]), (-Fr::one(), &product)]);
+                    bits::xor(builder, &b[lane][i], &masked)
+                })
+                .collect();
+        }
+
+        // ι
+        state[0] = bits::xor_words(builder, &state[0], &bits::constant_word(rc, 64));
+    }
+    state
+}
+
+/// XOR a block of bytes (8 little-endian bits each) into the sponge state
+fn absorb(builder: &mut R1csBuilder, state: &mut [Vec<Lc>], block: &[Vec<Lc>]) {
+    for (lane, bytes) in state.iter_mut().zip(block.chunks(8)) {
+        let bits: Vec<Lc> = bytes.iter().flatten().cloned().collect();
+        *lane = bits::xor_words(builder, lane, &bits);
+    }
+}
+
+/// Constrain a `Keccakf1600` black box: 25 lanes, each range-checked to
+/// 64 bits, into 25 output lanes
+pub(crate) fn permutation_gadget(
+    builder: &mut R1csBuilder,
+    inputs: &[FunctionInput],
+    outputs: &[u32],
+) -> Result<(), ArkworksError> {
+    if inputs.len() != 25 || outputs.len() != 25 {
+        return Err(ArkworksError::InvalidInput(format!(
+            "Keccak-f[1600] takes 25 lanes into 25 outputs, got {} and {}",
+            inputs.len(),
+            outputs.len()
+        )));
+    }
+    let state: Vec<Vec<Lc>> = inputs
+        .iter()
+        .map(|input| {
+            let lane = FunctionInput { num_bits: 64, ..input.clone() };
+            bits::input_bits(builder, &lane)
+        })
+        .collect();
+
+    let state = permutation_bits(builder, state);
+    for (lane, &output) in state.iter().zip(outputs) {
+        builder.assign_output(output, bits::pack(lane));
+    }
+    Ok(())
+}
+
+/// Constrain a `Keccak256` black box: `outputs` are the 32 digest bytes of
+/// the message formed by `inputs` (see `bits::input_bytes`)
+///
+/// With `var_message_size`, only that many leading bytes are hashed. The
+/// length is selected one-hot over [0, inputs], every possible block is
+/// permuted, and the digest is taken from the state after the last block
+/// of the actual message.
+pub(crate) fn hash_gadget(
+    builder: &mut R1csBuilder,
+    inputs: &[FunctionInput],
+    var_message_size: Option<&FunctionInput>,
+    outputs: &[u32],
+) -> Result<(), ArkworksError> {
+    if outputs.len() != 32 {
+        return Err(ArkworksError::InvalidInput(format!(
+            "Keccak-256 has 32 output bytes, got {}",
+            outputs.len()
+        )));
+    }
+    let message = bits::input_bytes(builder, inputs);
+
+    let (padded, block_selectors) = match var_message_size {
+        None => {
+            // Fixed length: the padding is constant
+            let padding = pad(&vec![0u8; message.len()]);
+            let tail: Vec<Vec<Lc>> = padding[message.len()..]
+                .iter()
+                .map(|&byte| bits::constant_word(byte as u64, 8))
+                .collect();
+            ([message, tail].concat(), None)
+        }
+        Some(size) => {
+            let n = message.len();
+            let selectors: Vec<Lc> = builder
+                .one_hot(size.lc(), n + 1)
+                .into_iter()
+                .map(|s| vec![(Fr::one(), s)])
+                .collect();
+            let blocks = n / RATE + 1;
+            let block_selectors: Vec<Lc> = (0..blocks)
+                .map(|b| selectors[b * RATE..((b + 1) * RATE).min(n + 1)].concat())
+                .collect();
+
+            // kept[i] = [i < size] = kept[i + 1] + s_{i + 1}
+            let mut kept = vec![Vec::new(); n];
+            for i in (0..n).rev() {
+                let next = kept.get(i + 1).cloned().unwrap_or_default();
+                let sum = combine(&[(Fr::one(), &next), (Fr::one(), &selectors[i + 1])]);
+                kept[i] = vec![(Fr::one(), builder.materialize(sum))];
+            }
+
+            // Byte i keeps its input when i < size, gets 0x01 when i == size
+            // and 0x80 when it ends the block holding byte `size`. These never
+            // set the same bit, so they are simply added.
+            let mut padded = Vec::with_capacity(blocks * RATE);
+            for i in 0..blocks * RATE {
+                let mut byte: Vec<Lc> = match message.get(i) {
+                    Some(input) => input.iter().map(|bit| bits::and(builder, bit, &kept[i])).collect(),
+                    None => bits::constant_word(0, 8),
+                };
+                if let Some(selector) = selectors.get(i) {
+                    byte[0] = combine(&[(Fr::one(), &byte[0]), (Fr::one(), selector)]);
+                }
+                if i % RATE == RATE - 1 {
+                    let end = &block_selectors[i / RATE];
+                    byte[7] = combine(&[(Fr::one(), &byte[7]), (Fr::one(), end)]);
+                }
+                padded.push(byte);
+            }
+            (padded, Some(block_selectors))
+        }
+    };
+
+    let mut state: Vec<Vec<Lc>> = vec![bits::constant_word(0, 64); 25];
+    let mut digests = Vec::new();
+    for block in padded.chunks(RATE) {
+        absorb(builder, &mut state, block);
+        state = permutation_bits(builder, state);
+        digests.push(state[..4].concat());
+    }
+
+    let digest: Vec<Lc> = match block_selectors {
+        None => digests.pop().expect("at least one block"),
+        Some(selectors) => (0..256)
+            .map(|i| {
+                let terms: Vec<Lc> = digests
+                    .iter()
+                    .zip(&selectors)
+                    .map(|(digest, selector)| bits::and(builder, selector, &digest[i]))
+                    .collect();
+                let parts: Vec<(Fr, &Lc)> = terms.iter().map(|t| (Fr::one(), t)).collect();
+                combine(&parts)
+            })
+            .collect(),
+    };
+
+    for (byte, &output) in digest.chunks(8).zip(outputs) {
+        builder.assign_output(output, bits::pack(byte));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acir_to_r1cs::WitnessMap;
+    use crate::test_utils::convert_opcode;
+    use serde_json::json;
+
+    fn byte_inputs(witnesses: std::ops::RangeInclusive<u32>) -> Vec<serde_json::Value> {
+        witnesses.map(|w| json!({ "witness": w, "num_bits": 8 })).collect()
+    }
+
+    #[test]
+    fn test_native_vectors() {
+        assert_eq!(permutation([0; 25])[0], 0xf1258f7940e1dde7);
+        assert_eq!(
+            hex::encode(keccak256(b"")),
+            "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"
+        );
+        assert_eq!(
+            hex::encode(keccak256(b"abc")),
+            "4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45"
+        );
+        // Exactly one block of message, so the padding takes a second block
+        assert_eq!(pad(&[0; RATE]).len(), 2 * RATE);
+        assert_eq!(pad(&[0; RATE - 1])[RATE - 1], 0x81);
+    }
+
+    #[test]
+    fn test_permutation_gadget() {
+        let r1cs = convert_opcode(
+            json!({
+                "type": "BlackBoxFuncCall", "name": "Keccakf1600",
+                "inputs": (1..=25).map(|w| json!({ "witness": w, "num_bits": 64 })).collect::<Vec<_>>(),
+                "outputs": (26..=50).collect::<Vec<u32>>()
+            }),
+            50,
+            1..=25,
+        );
+        let state: [u64; 25] = std::array::from_fn(|i| (i as u64 + 1).wrapping_mul(0x9e3779b97f4a7c15));
+        let mut witness: WitnessMap = (1..=25).zip(state.map(Fr::from)).collect();
+        r1cs.compute_aux_witnesses(&mut witness).unwrap();
+        assert!(r1cs.constraints.iter().all(|c| c.is_satisfied(&witness).unwrap()));
+        let outputs: Vec<Fr> = (26..=50).map(|w| witness[&w]).collect();
+        assert_eq!(outputs, permutation(state).map(Fr::from).to_vec());
+    }
+
+    #[test]
+    fn test_hash_gadget_variable_length() {
+        // Five input bytes, of which only the first three are hashed
+        let r1cs = convert_opcode(
+            json!({
+                "type": "BlackBoxFuncCall", "name": "Keccak256",
+                "inputs": byte_inputs(1..=5),
+                "var_message_size": { "witness": 6, "num_bits": 32 },
+                "outputs": (7..=38).collect::<Vec<u32>>()
+            }),
+            38,
+            1..=6,
+        );
+        let mut witness: WitnessMap =
+            (1..=5).zip(b"abcde".iter().map(|&b| Fr::from(b))).collect();
+        witness.insert(6, Fr::from(3u64));
+        r1cs.compute_aux_witnesses(&mut witness).unwrap();
+        assert!(r1cs.constraints.iter().all(|c| c.is_satisfied(&witness).unwrap()));
+        let outputs: Vec<Fr> = (7..=38).map(|w| witness[&w]).collect();
+        assert_eq!(outputs, keccak256(b"abc").map(Fr::from).to_vec());
+
+        // A full-block message pads into a second block
+        let message: Vec<u8> = (0..=RATE as u8).collect();
+        let n = message.len() as u32;
+        let r1cs = convert_opcode(
+            json!({
+                "type": "BlackBoxFuncCall", "name": "Keccak256",
+                "inputs": byte_inputs(1..=n),
+                "var_message_size": { "witness": n + 1, "num_bits": 32 },
+                "outputs": (n + 2..n + 34).collect::<Vec<u32>>()
+            }),
+            n + 33,
+            1..=n + 1,
+        );
+        let mut witness: WitnessMap = (1..=n).zip(message.iter().map(|&b| Fr::from(b))).collect();
+        witness.insert(n + 1, Fr::from(RATE as u64));
+        r1cs.compute_aux_witnesses(&mut witness).unwrap();
+        assert!(r1cs.constraints.iter().all(|c| c.is_satisfied(&witness).unwrap()));
+        let outputs: Vec<Fr> = (n + 2..n + 34).map(|w| witness[&w]).collect();
+        assert_eq!(outputs, keccak256(&message[..RATE]).map(Fr::from).to_vec());
+
+        // Without a size, the whole input is hashed
+        let r1cs = convert_opcode(
+            json!({
+                "type": "BlackBoxFuncCall", "name": "Keccak256",
+                "inputs": byte_inputs(1..=5),
+                "outputs": (6..=37).collect::<Vec<u32>>()
+            }),
+            37,
+            1..=5,
+        );
+        let mut witness: WitnessMap =
+            (1..=5).zip(b"abcde".iter().map(|&b| Fr::from(b))).collect();
+        r1cs.compute_aux_witnesses(&mut witness).unwrap();
+        assert!(r1cs.constraints.iter().all(|c| c.is_satisfied(&witness).unwrap()));
+        let outputs: Vec<Fr> = (6..=37).map(|w| witness[&w]).collect();
+        assert_eq!(outputs, keccak256(b"abcde").map(Fr::from).to_vec());
+    }
+}
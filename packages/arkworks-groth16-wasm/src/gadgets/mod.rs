@@ -0,0 +1,40 @@
+//! R1CS gadgets for ACIR black box functions.
+//!
+//! Each gadget pairs the constraints added to an `R1csBuilder` with a
+//! native implementation, used by the witness solver and the wasm API.
+
+use ark_bn254::Fr;
+use ark_ff::Zero;
+use std::collections::BTreeMap;
+
+pub mod bigint;
+pub(crate) mod bits;
+pub mod blake2s;
+pub mod blake3;
+pub mod ecdsa;
+pub mod embedded_curve;
+pub mod grumpkin;
+pub mod keccak;
+pub(crate) mod nonnative;
+pub mod pedersen;
+pub mod poseidon2;
+pub mod schnorr;
+pub mod sha256;
+
+/// Linear combination over witnesses
+pub(crate) type Lc = Vec<(Fr, u32)>;
+
+/// Sum of scaled linear combinations, with terms merged by witness
+pub(crate) fn combine(parts: &[(Fr, &Lc)]) -> Lc {
+    let mut terms: BTreeMap<u32, Fr> = BTreeMap::new();
+    for (scale, lc) in parts {
+        for &(coeff, w) in lc.iter() {
+            *terms.entry(w).or_insert_with(Fr::zero) += *scale * coeff;
+        }
+    }
+    terms
+        .into_iter()
+        .filter(|(_, coeff)| !coeff.is_zero())
+        .map(|(w, coeff)| (coeff, w))
+        .collect()
+}
@@ -0,0 +1,460 @@
+//! Arithmetic modulo primes of up to 256 bits other than the native field.
+//!
+//! An element is an integer sum(2^(86 i) * l_i) of limbs, each a linear
+//! combination whose magnitude as an integer is tracked. Additions and
+//! constant multiples only combine limbs, and may leave them negative;
+//! a product costs one constraint per pair of limbs and has five columns.
+//!
+//! Values are brought back to three 86-bit limbs by a residue and quotient
+//! supplied by the prover: value - residue = q * m is checked column by
+//! column with range-checked carries, each column equation being far below
+//! the field modulus so that together they hold over the integers. A
+//! reduction costs about 900 constraints, nearly all range-check bits.
+
+use ark_bn254::Fr;
+use ark_ff::{Field, One, PrimeField, Zero};
+use num_bigint::{BigInt, BigUint, Sign};
+
+use super::{bits, combine, Lc};
+use crate::acir_to_r1cs::{AuxWitnessComputation, R1csBuilder};
+
+/// Bits per limb
+pub(crate) const LIMB_BITS: usize = 86;
+
+/// Limbs of a reduced element
+const LIMBS: usize = 3;
+
+/// Columns of a product of reduced elements
+const COLUMNS: usize = 2 * LIMBS - 1;
+
+/// Largest column, in bits, for the column equations to hold over the
+/// integers
+const MAX_COLUMN_BITS: u64 = 250;
+
+/// An integer sum(2^(86 i) * limbs[i]), with a bound on the magnitude of
+/// each limb
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Element {
+    limbs: Vec<Lc>,
+    bounds: Vec<BigUint>,
+}
+
+impl Element {
+    /// A non-negative constant
+    pub(crate) fn constant(value: &BigUint) -> Self {
+        let mask = (BigUint::one() << LIMB_BITS) - 1u32;
+        let mut element = Element::default();
+        let mut rest = value.clone();
+        while !rest.is_zero() {
+            let limb = &rest & &mask;
+            element.limbs.push(vec![(Fr::from(limb.clone()), 0)]);
+            element.bounds.push(limb);
+            rest >>= LIMB_BITS;
+        }
+        element
+    }
+
+    /// The value of little-endian bits
+    pub(crate) fn from_bits(bits: &[Lc]) -> Self {
+        let chunks = bits.chunks(LIMB_BITS);
+        Element {
+            bounds: chunks
+                .clone()
+                .map(|chunk| (BigUint::one() << chunk.len()) - 1u32)
+                .collect(),
+            limbs: chunks.map(bits::pack).collect(),
+        }
+    }
+
+    fn limb(&self, i: usize) -> (&[(Fr, u32)], BigUint) {
+        match self.limbs.get(i) {
+            Some(limb) => (limb, self.bounds[i].clone()),
+            None => (&[], BigUint::zero()),
+        }
+    }
+
+    pub(crate) fn add(&self, other: &Element) -> Element {
+        let len = self.limbs.len().max(other.limbs.len());
+        let (limbs, bounds) = (0..len)
+            .map(|i| {
+                let ((a, a_bound), (b, b_bound)) = (self.limb(i), other.limb(i));
+                let limb = combine(&[(Fr::one(), &a.to_vec()), (Fr::one(), &b.to_vec())]);
+                (limb, a_bound + b_bound)
+            })
+            .unzip();
+        Element { limbs, bounds }
+    }
+
+    pub(crate) fn neg(&self) -> Element {
+        self.scale_by(-Fr::one(), 1)
+    }
+
+    pub(crate) fn sub(&self, other: &Element) -> Element {
+        self.add(&other.neg())
+    }
+
+    /// k times the element
+    pub(crate) fn scale(&self, k: u64) -> Element {
+        self.scale_by(Fr::from(k), k)
+    }
+
+    fn scale_by(&self, coeff: Fr, magnitude: u64) -> Element {
+        Element {
+            limbs: self
+                .limbs
+                .iter()
+                .map(|limb| combine(&[(coeff, limb)]))
+                .collect(),
+            bounds: self.bounds.iter().map(|bound| bound * magnitude).collect(),
+        }
+    }
+
+    /// Bound on the magnitude of the integer
+    fn bound(&self) -> BigUint {
+        self.bounds
+            .iter()
+            .rev()
+            .fold(BigUint::zero(), |acc, bound| (acc << LIMB_BITS) + bound)
+    }
+}
+
+/// a * b over the integers, one constraint per pair of variable limbs
+pub(crate) fn mul(builder: &mut R1csBuilder, a: &Element, b: &Element) -> Element {
+    if a.limbs.is_empty() || b.limbs.is_empty() {
+        return Element::default();
+    }
+    let len = a.limbs.len() + b.limbs.len() - 1;
+    let mut product = Element {
+        limbs: vec![Lc::new(); len],
+        bounds: vec![BigUint::zero(); len],
+    };
+    for (i, (x, x_bound)) in a.limbs.iter().zip(&a.bounds).enumerate() {
+        for (j, (y, y_bound)) in b.limbs.iter().zip(&b.bounds).enumerate() {
+            let term = bits::and(builder, x, y);
+            product.limbs[i + j] =
+                combine(&[(Fr::one(), &product.limbs[i + j]), (Fr::one(), &term)]);
+            product.bounds[i + j] += x_bound * y_bound;
+        }
+    }
+    product
+}
+
+/// `if_one` when the bit is set, `if_zero` otherwise, one constraint per
+/// limb
+pub(crate) fn select(
+    builder: &mut R1csBuilder,
+    bit: &Lc,
+    if_zero: &Element,
+    if_one: &Element,
+) -> Element {
+    let len = if_zero.limbs.len().max(if_one.limbs.len());
+    let (limbs, bounds) = (0..len)
+        .map(|i| {
+            let ((a, a_bound), (b, b_bound)) = (if_zero.limb(i), if_one.limb(i));
+            let a = a.to_vec();
+            let diff = combine(&[(Fr::one(), &b.to_vec()), (-Fr::one(), &a)]);
+            let offset = bits::and(builder, bit, &diff);
+            (
+                combine(&[(Fr::one(), &a), (Fr::one(), &offset)]),
+                a_bound.max(b_bound),
+            )
+        })
+        .unzip();
+    Element { limbs, bounds }
+}
+
+/// Constrain an integer to be zero
+///
+/// Column i plus the carry from column i - 1 must be 2^86 times the carry
+/// out of column i, and the last column takes no carry out. Columns past
+/// the fifth are folded into it.
+fn enforce_zero(builder: &mut R1csBuilder, value: &Element) {
+    let shift = Fr::from(2u64).pow([LIMB_BITS as u64]);
+    let mut limbs = value.limbs.clone();
+    let mut bounds = value.bounds.clone();
+    while limbs.len() > COLUMNS {
+        let (limb, bound) = (limbs.pop(), bounds.pop());
+        let (limb, bound) = (limb.unwrap_or_default(), bound.unwrap_or_default());
+        let last = limbs.len() - 1;
+        limbs[last] = combine(&[(Fr::one(), &limbs[last]), (shift, &limb)]);
+        bounds[last] += bound << LIMB_BITS;
+    }
+
+    let shift_inverse = shift.inverse().expect("2^86 is invertible");
+    let mut carry = Lc::new();
+    let mut carry_bound = BigUint::zero();
+    for (i, (limb, bound)) in limbs.iter().zip(&bounds).enumerate() {
+        let total = bound + &carry_bound;
+        assert!(
+            total.bits() < MAX_COLUMN_BITS,
+            "non-native column exceeds {} bits",
+            MAX_COLUMN_BITS
+        );
+        let sum = combine(&[(Fr::one(), limb), (Fr::one(), &carry)]);
+        if i + 1 == limbs.len() {
+            builder.add_constraint(sum, bits::constant(true), vec![]);
+            break;
+        }
+        // The carry is range-checked as carry + 2^k in k + 1 bits
+        let offset_bits = (total >> LIMB_BITS).bits() as usize;
+        let offset = Fr::from(BigUint::one() << offset_bits);
+        let shifted = combine(&[(shift_inverse, &sum), (offset, &bits::constant(true))]);
+        let shifted = builder.materialize(shifted);
+        builder.decompose_bits(shifted, offset_bits as u32 + 1);
+        carry = vec![(Fr::one(), shifted), (-offset, 0)];
+        carry_bound = BigUint::one() << offset_bits;
+    }
+}
+
+/// Limb widths of a quotient of `width` bits, the last limb taking the rest
+fn quotient_widths(width: usize) -> Vec<usize> {
+    let count = width.div_ceil(LIMB_BITS).clamp(1, LIMBS);
+    let mut widths = vec![LIMB_BITS; count - 1];
+    widths.push(width - LIMB_BITS * (count - 1));
+    widths
+}
+
+/// Constrain `value` to be 0 modulo `modulus`
+///
+/// The prover supplies q = value / modulus, range-checked as q + offset
+/// with an offset from the bound on the value, and value - q * modulus must
+/// be zero over the integers.
+pub(crate) fn enforce_zero_mod(builder: &mut R1csBuilder, value: &Element, modulus: &BigUint) {
+    let offset = value.bound() / modulus + 1u32;
+    let widths = quotient_widths((&offset << 1u32).bits() as usize);
+    let limbs: Vec<u32> = widths.iter().map(|_| builder.new_witness()).collect();
+    builder.add_aux_witness(AuxWitnessComputation::NonNativeQuotient {
+        value: value.limbs.clone(),
+        modulus: modulus.clone(),
+        offset: offset.clone(),
+        limbs: limbs.clone(),
+    });
+    for (&limb, &width) in limbs.iter().zip(&widths) {
+        builder.decompose_bits(limb, width as u32);
+    }
+
+    let quotient = Element {
+        limbs: limbs.iter().map(|&limb| vec![(Fr::one(), limb)]).collect(),
+        bounds: widths
+            .iter()
+            .map(|&width| (BigUint::one() << width) - 1u32)
+            .collect(),
+    };
+    let multiple = mul(builder, &quotient, &Element::constant(modulus));
+    let difference = value
+        .sub(&multiple)
+        .add(&Element::constant(&(offset * modulus)));
+    enforce_zero(builder, &difference);
+}
+
+/// Little-endian bits of fresh 86-bit limbs holding the prover's
+/// numerator / denominator modulo `modulus`
+fn residue_bits(
+    builder: &mut R1csBuilder,
+    numerator: &Element,
+    denominator: &Element,
+    modulus: &BigUint,
+) -> Vec<Lc> {
+    let limbs: Vec<u32> = (0..LIMBS).map(|_| builder.new_witness()).collect();
+    builder.add_aux_witness(AuxWitnessComputation::NonNativeResidue {
+        numerator: numerator.limbs.clone(),
+        denominator: denominator.limbs.clone(),
+        modulus: modulus.clone(),
+        limbs: limbs.clone(),
+    });
+    limbs
+        .iter()
+        .flat_map(|&limb| builder.decompose_bits(limb, LIMB_BITS as u32))
+        .map(|bit| vec![(Fr::one(), bit)])
+        .collect()
+}
+
+/// A fresh element with 86-bit limbs congruent to `value`
+pub(crate) fn reduce(builder: &mut R1csBuilder, value: &Element, modulus: &BigUint) -> Element {
+    let one = Element::constant(&BigUint::one());
+    let residue = Element::from_bits(&residue_bits(builder, value, &one, modulus));
+    enforce_zero_mod(builder, &value.sub(&residue), modulus);
+    residue
+}
+
+/// Little-endian bits of a fresh element congruent to
+/// numerator / denominator
+///
+/// As with `R1csBuilder::quotient`, a denominator of 0 modulo `modulus`
+/// makes the constraints unsatisfiable unless the numerator is 0 too.
+pub(crate) fn divide_bits(
+    builder: &mut R1csBuilder,
+    numerator: &Element,
+    denominator: &Element,
+    modulus: &BigUint,
+) -> Vec<Lc> {
+    let bits = residue_bits(builder, numerator, denominator, modulus);
+    let product = mul(builder, &Element::from_bits(&bits), denominator);
+    enforce_zero_mod(builder, &product.sub(numerator), modulus);
+    bits
+}
+
+/// A fresh element congruent to numerator / denominator
+pub(crate) fn divide(
+    builder: &mut R1csBuilder,
+    numerator: &Element,
+    denominator: &Element,
+    modulus: &BigUint,
+) -> Element {
+    Element::from_bits(&divide_bits(builder, numerator, denominator, modulus))
+}
+
+/// The residue of `value` in [0, modulus)
+///
+/// The residue r and m - 1 - r both get 86-bit limbs, and their sum must be
+/// m - 1 over the integers.
+pub(crate) fn canonical(builder: &mut R1csBuilder, value: &Element, modulus: &BigUint) -> Element {
+    let residue = reduce(builder, value, modulus);
+    let max = Element::constant(&(modulus - 1u32));
+    let one = Element::constant(&BigUint::one());
+    let complement = residue_bits(builder, &max.sub(&residue), &one, modulus);
+    let sum = residue.add(&Element::from_bits(&complement));
+    enforce_zero(builder, &sum.sub(&max));
+    residue
+}
+
+/// Whether an element with non-negative limbs, such as a canonical one, is
+/// zero
+pub(crate) fn is_zero(builder: &mut R1csBuilder, value: &Element) -> Lc {
+    let limbs: Vec<(Fr, &Lc)> = value.limbs.iter().map(|limb| (Fr::one(), limb)).collect();
+    builder.is_zero(combine(&limbs))
+}
+
+/// Constrain `outputs` to the little-endian bytes of a non-negative
+/// element, which must fit in them
+///
+/// The prover fills in the bytes unless the caller already supplied them;
+/// each is range-checked to 8 bits, and together they must equal the
+/// element over the integers.
+pub(crate) fn assign_bytes(builder: &mut R1csBuilder, value: &Element, outputs: &[u32]) {
+    builder.add_aux_witness(AuxWitnessComputation::IntegerBytes {
+        value: value.limbs.clone(),
+        bytes: outputs.to_vec(),
+    });
+    let bits: Vec<Lc> = outputs
+        .iter()
+        .flat_map(|&byte| builder.decompose_bits(byte, 8))
+        .map(|bit| vec![(Fr::one(), bit)])
+        .collect();
+    enforce_zero(builder, &Element::from_bits(&bits).sub(value));
+}
+
+/// Whether two elements with limbs in [0, 2^86) are the same integer
+pub(crate) fn equal(builder: &mut R1csBuilder, a: &Element, b: &Element) -> Lc {
+    let mut equal = bits::constant(true);
+    for i in 0..a.limbs.len().max(b.limbs.len()) {
+        let ((x, _), (y, _)) = (a.limb(i), b.limb(i));
+        let diff = combine(&[(Fr::one(), &x.to_vec()), (-Fr::one(), &y.to_vec())]);
+        let same = builder.is_zero(diff);
+        equal = bits::and(builder, &equal, &same);
+    }
+    equal
+}
+
+/// Whether little-endian bits encode a value below a constant, one
+/// constraint per bit
+///
+/// From the top, `equal` tracks whether the prefixes agree and `less`
+/// whether a bit already fell below the constant's.
+pub(crate) fn less_than(builder: &mut R1csBuilder, bits: &[Lc], constant: &BigUint) -> Lc {
+    let one = bits::constant(true);
+    let mut equal = one.clone();
+    let mut less = bits::constant(false);
+    for i in (0..bits.len().max(constant.bits() as usize)).rev() {
+        let bit = bits.get(i).cloned().unwrap_or_default();
+        let both = bits::and(builder, &equal, &bit);
+        if constant.bit(i as u64) {
+            // A clear bit under a set one makes the value smaller
+            less = combine(&[(Fr::one(), &less), (Fr::one(), &equal), (-Fr::one(), &both)]);
+            equal = both;
+        } else {
+            equal = combine(&[(Fr::one(), &equal), (-Fr::one(), &both)]);
+        }
+    }
+    less
+}
+
+/// A constant table indexed by bits, as sum(c_mask * monomials[mask])
+///
+/// `monomials[mask]` is the product of the bits in mask, so with the
+/// coefficients of each limb taken from the Moebius transform of the
+/// table, the element is table[m] for the value m of the bits.
+pub(crate) fn lookup(monomials: &[Lc], table: &[BigUint]) -> Element {
+    let mut element = Element::default();
+    for i in 0..LIMBS {
+        let values: Vec<BigUint> = table.iter().map(|v| limb_value(v, i)).collect();
+        let mut coefficients: Vec<BigInt> = values.iter().cloned().map(BigInt::from).collect();
+        for bit in 0..table.len().trailing_zeros() {
+            for mask in 0..table.len() {
+                if mask & (1 << bit) != 0 {
+                    let lower = coefficients[mask ^ (1 << bit)].clone();
+                    coefficients[mask] -= lower;
+                }
+            }
+        }
+        let coefficients: Vec<Fr> = coefficients.iter().map(to_field).collect();
+        let parts: Vec<(Fr, &Lc)> = coefficients.iter().copied().zip(monomials).collect();
+        element.limbs.push(combine(&parts));
+        element
+            .bounds
+            .push(values.into_iter().max().unwrap_or_default());
+    }
+    element
+}
+
+/// A field element as a signed integer, negative above (r - 1) / 2
+pub(crate) fn signed(value: Fr) -> BigInt {
+    let value = BigUint::from(value.into_bigint());
+    let modulus = BigUint::from(Fr::MODULUS);
+    if value > &modulus >> 1 {
+        BigInt::from(value) - BigInt::from(modulus)
+    } else {
+        BigInt::from(value)
+    }
+}
+
+/// A signed integer as a field element
+fn to_field(value: &BigInt) -> Fr {
+    let magnitude = Fr::from(value.magnitude().clone());
+    match value.sign() {
+        Sign::Minus => -magnitude,
+        _ => magnitude,
+    }
+}
+
+/// The residue of an integer in [0, modulus)
+pub(crate) fn modulo(value: &BigInt, modulus: &BigUint) -> BigUint {
+    let residue = value % BigInt::from(modulus.clone());
+    match residue.sign() {
+        Sign::Minus => modulus - residue.magnitude(),
+        _ => residue.magnitude().clone(),
+    }
+}
+
+/// The inverse of a value modulo a prime, if it has one
+pub(crate) fn inverse(value: &BigUint, modulus: &BigUint) -> Option<BigUint> {
+    if (value % modulus).is_zero() {
+        return None;
+    }
+    Some(value.modpow(&(modulus - 2u32), modulus))
+}
+
+/// Limb i of a value
+fn limb_value(value: &BigUint, i: usize) -> BigUint {
+    (value >> (LIMB_BITS * i)) & ((BigUint::one() << LIMB_BITS) - 1u32)
+}
+
+/// The 86-bit limbs of a value, the last one taking the high bits
+pub(crate) fn limb_values(value: &BigUint, count: usize) -> Vec<Fr> {
+    (0..count)
+        .map(|i| match i + 1 < count {
+            true => Fr::from(limb_value(value, i)),
+            false => Fr::from(value >> (LIMB_BITS * i)),
+        })
+        .collect()
+}
@@ -0,0 +1,183 @@
+//! Pedersen commitment and hash over Grumpkin, as in Barretenberg.
+//!
+//! The commitment to inputs x_i is sum(x_i * G_i), with generators derived
+//! from "DEFAULT_DOMAIN_SEPARATOR" starting at index `domain_separator`.
+//! The hash is the x coordinate of n * H plus the commitment, where n is
+//! the number of inputs and H is derived from "pedersen_hash_length".
+//! Inputs are decomposed canonically (see `bits::field_bits`), so the
+//! gadgets cost about 1,100 constraints per input.
+
+use ark_bn254::Fr;
+use num_bigint::BigUint;
+
+use super::grumpkin::{self, Point};
+use super::{bits, Lc};
+use crate::acir_to_r1cs::R1csBuilder;
+use crate::acir_types::FunctionInput;
+
+const DEFAULT_DOMAIN: &[u8] = b"DEFAULT_DOMAIN_SEPARATOR";
+const LENGTH_DOMAIN: &[u8] = b"pedersen_hash_length";
+
+/// n * H for a hash of n inputs
+fn length_point(len: usize) -> Point {
+    grumpkin::derive_generators(LENGTH_DOMAIN, 1, 0)[0].mul(&BigUint::from(len))
+}
+
+fn commitment_point(inputs: &[Fr], domain_separator: u32) -> Point {
+    let generators = grumpkin::derive_generators(DEFAULT_DOMAIN, inputs.len(), domain_separator);
+    inputs
+        .iter()
+        .zip(generators)
+        .fold(Point::INFINITY, |acc, (&input, generator)| {
+            acc.add(&generator.mul(&input.into()))
+        })
+}
+
+/// Pedersen commitment, (0, 0) for the point at infinity
+pub fn commitment(inputs: &[Fr], domain_separator: u32) -> (Fr, Fr) {
+    commitment_point(inputs, domain_separator).coordinates()
+}
+
+/// Pedersen hash
+pub fn hash(inputs: &[Fr], domain_separator: u32) -> Fr {
+    let point = commitment_point(inputs, domain_separator).add(&length_point(inputs.len()));
+    point.coordinates().0
+}
+
+/// Constrain the commitment to `inputs` plus a constant point
+fn commitment_lc(
+    builder: &mut R1csBuilder,
+    inputs: &[Lc],
+    domain_separator: u32,
+    constant: &Point,
+) -> (Lc, Lc, Lc) {
+    let generators = grumpkin::derive_generators(DEFAULT_DOMAIN, inputs.len(), domain_separator);
+    let terms: Vec<(Vec<Lc>, Point)> = inputs
+        .iter()
+        .zip(generators)
+        .map(|(input, generator)| (bits::field_bits(builder, input.clone()), generator))
+        .collect();
+    grumpkin::fixed_base_msm(builder, &terms, constant)
+}
+
+/// Constrain the Pedersen hash of linear combinations
+pub(crate) fn hash_lc(builder: &mut R1csBuilder, inputs: &[Lc], domain_separator: u32) -> Lc {
+    let (x, _, _) = commitment_lc(
+        builder,
+        inputs,
+        domain_separator,
+        &length_point(inputs.len()),
+    );
+    x
+}
+
+fn input_lcs(inputs: &[FunctionInput]) -> Vec<Lc> {
+    inputs.iter().map(FunctionInput::lc).collect()
+}
+
+/// Constrain a `PedersenCommitment` black box
+pub(crate) fn commitment_gadget(
+    builder: &mut R1csBuilder,
+    inputs: &[FunctionInput],
+    domain_separator: u32,
+    outputs: (u32, u32),
+) {
+    let inputs = input_lcs(inputs);
+    let (x, y, _) = commitment_lc(builder, &inputs, domain_separator, &Point::INFINITY);
+    builder.assign_output(outputs.0, x);
+    builder.assign_output(outputs.1, y);
+}
+
+/// Constrain a `PedersenHash` black box
+pub(crate) fn hash_gadget(
+    builder: &mut R1csBuilder,
+    inputs: &[FunctionInput],
+    domain_separator: u32,
+    output: u32,
+) {
+    let hash = hash_lc(builder, &input_lcs(inputs), domain_separator);
+    builder.assign_output(output, hash);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acir_to_r1cs::{acir_to_r1cs, AcirR1cs, WitnessMap};
+    use crate::test_utils::program;
+    use ark_ff::{One, Zero};
+    use serde_json::json;
+
+    /// A commitment and a hash of the same inputs, on witnesses 1..=n
+    fn convert(n: u32, domain_separator: u32) -> AcirR1cs {
+        let inputs: Vec<_> = (1..=n)
+            .map(|w| json!({ "witness": w, "num_bits": 254 }))
+            .collect();
+        let program = program(
+            json!([
+                {
+                    "type": "BlackBoxFuncCall", "name": "PedersenCommitment",
+                    "inputs": inputs, "domain_separator": domain_separator,
+                    "outputs": [n + 1, n + 2]
+                },
+                {
+                    "type": "BlackBoxFuncCall", "name": "PedersenHash",
+                    "inputs": inputs, "domain_separator": domain_separator,
+                    "output": n + 3
+                }
+            ]),
+            n + 3,
+            1..=n,
+        );
+        acir_to_r1cs(&program).unwrap()
+    }
+
+    fn solve(r1cs: &AcirR1cs, inputs: &[Fr]) -> WitnessMap {
+        let mut witness: WitnessMap = (1..).zip(inputs.iter().copied()).collect();
+        r1cs.compute_aux_witnesses(&mut witness).unwrap();
+        assert!(r1cs
+            .constraints
+            .iter()
+            .all(|c| c.is_satisfied(&witness).unwrap()));
+        witness
+    }
+
+    #[test]
+    fn test_native() {
+        // A single input of 1 commits to its generator
+        let generator = grumpkin::derive_generators(DEFAULT_DOMAIN, 1, 7)[0];
+        assert_eq!(commitment(&[Fr::one()], 7), generator.coordinates());
+        assert_eq!(
+            commitment(&[Fr::zero(), Fr::zero()], 0),
+            (Fr::zero(), Fr::zero())
+        );
+        assert_eq!(hash(&[], 0), Fr::zero());
+        assert_eq!(hash(&[Fr::zero()], 0), length_point(1).x);
+    }
+
+    #[test]
+    fn test_gadgets() {
+        let r1cs = convert(3, 5);
+        let inputs = [Fr::from(1u64), -Fr::one(), Fr::from(0xdead_beefu64)];
+        let witness = solve(&r1cs, &inputs);
+        assert_eq!((witness[&4], witness[&5]), commitment(&inputs, 5));
+        assert_eq!(witness[&6], hash(&inputs, 5));
+
+        // The zero commitment is the point at infinity
+        let r1cs = convert(2, 0);
+        let inputs = [Fr::zero(), Fr::zero()];
+        let witness = solve(&r1cs, &inputs);
+        assert_eq!((witness[&3], witness[&4]), (Fr::zero(), Fr::zero()));
+        assert_eq!(witness[&5], hash(&inputs, 0));
+    }
+
+    #[test]
+    fn test_gadget_rejects_wrong_output() {
+        let r1cs = convert(1, 0);
+        let mut witness: WitnessMap = [(1, Fr::from(42u64)), (2, Fr::from(3u64))].into();
+        r1cs.compute_aux_witnesses(&mut witness).unwrap();
+        assert!(!r1cs
+            .constraints
+            .iter()
+            .all(|c| c.is_satisfied(&witness).unwrap()));
+    }
+}
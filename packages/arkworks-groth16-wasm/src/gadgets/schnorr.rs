@@ -0,0 +1,261 @@
+//! Schnorr signatures over Grumpkin, as Barretenberg verifies them.
+//!
+//! A signature is 64 bytes: s and then e, both big-endian. It is valid for
+//! a public key P when P is on the curve, s and e are nonzero modulo the
+//! curve order, R = s * G + e * P is finite and e is exactly the BLAKE2s
+//! digest of pedersen_hash(R.x, P.x, P.y) followed by the message.
+//!
+//! The gadget computes the validity flag instead of asserting it, so every
+//! check is a boolean combination: an off-curve key is replaced by the
+//! generator and only clears the flag. It costs about 30k constraints for
+//! a message of up to 32 bytes, mostly BLAKE2s and the Pedersen hash.
+
+use ark_bn254::{Fq, Fr};
+use ark_ff::{BigInteger, One, PrimeField, Zero};
+use num_bigint::BigUint;
+
+use super::blake2s::{self, blake2s};
+use super::grumpkin::{self, Point, PointVar};
+use super::{bits, combine, pedersen, Lc};
+use crate::acir_to_r1cs::R1csBuilder;
+use crate::acir_types::FunctionInput;
+use crate::error::ArkworksError;
+
+/// The challenge, blake2s(pedersen_hash(R.x, P.x, P.y) || message)
+fn challenge(r_x: Fr, public_key: &Point, message: &[u8]) -> [u8; 32] {
+    let hash = pedersen::hash(&[r_x, public_key.x, public_key.y], 0);
+    let mut preimage = hash.into_bigint().to_bytes_be();
+    preimage.extend_from_slice(message);
+    blake2s(&preimage)
+}
+
+/// Verify a signature of `message` by the key (x, y)
+pub fn verify_signature(
+    public_key_x: Fr,
+    public_key_y: Fr,
+    signature: &[u8; 64],
+    message: &[u8],
+) -> bool {
+    let public_key = Point::new(public_key_x, public_key_y);
+    if !public_key.is_on_curve() {
+        return false;
+    }
+    let order = BigUint::from(Fq::MODULUS);
+    let s = BigUint::from_bytes_be(&signature[..32]) % &order;
+    let e = BigUint::from_bytes_be(&signature[32..]) % &order;
+    if s.is_zero() || e.is_zero() {
+        return false;
+    }
+    let r = grumpkin::generator().mul(&s).add(&public_key.mul(&e));
+    !r.infinity && challenge(r.x, &public_key, message) == signature[32..]
+}
+
+fn not(bit: &Lc) -> Lc {
+    combine(&[(Fr::one(), &bits::constant(true)), (-Fr::one(), bit)])
+}
+
+/// Value of big-endian bytes (8 little-endian bits each)
+fn pack_bytes(bytes: &[Vec<Lc>]) -> Lc {
+    let bits: Vec<Lc> = bytes.iter().rev().flatten().cloned().collect();
+    bits::pack(&bits)
+}
+
+/// Constrain a `SchnorrVerify` black box: `output` is 1 when the signature
+/// is valid and 0 otherwise
+pub(crate) fn verify_gadget(
+    builder: &mut R1csBuilder,
+    public_key_x: &FunctionInput,
+    public_key_y: &FunctionInput,
+    signature: &[FunctionInput],
+    message: &[FunctionInput],
+    output: u32,
+) -> Result<(), ArkworksError> {
+    if signature.len() != 64 {
+        return Err(ArkworksError::InvalidInput(format!(
+            "Schnorr signatures have 64 bytes, got {}",
+            signature.len()
+        )));
+    }
+    let (x, y) = (public_key_x.lc(), public_key_y.lc());
+
+    // y^2 - x^3 + 17 = 0
+    let xx = vec![(Fr::one(), builder.product_lc(x.clone(), x.clone()))];
+    let xxx = vec![(Fr::one(), builder.product_lc(xx, x.clone()))];
+    let yy = vec![(Fr::one(), builder.product_lc(y.clone(), y.clone()))];
+    let constant = vec![(Fr::from(17u64), 0)];
+    let residual = combine(&[(Fr::one(), &yy), (-Fr::one(), &xxx), (Fr::one(), &constant)]);
+    let on_curve = builder.is_zero(residual);
+    let generator = PointVar::constant(&grumpkin::generator());
+    let key = PointVar {
+        x: x.clone(),
+        y: y.clone(),
+    };
+    let key = grumpkin::select(builder, &on_curve, &generator, &key);
+
+    // R = e * P + s * G, where a zero scalar gives the point at infinity
+    let signature = bits::input_bytes(builder, signature);
+    let scalar_bits =
+        |bytes: &[Vec<Lc>]| -> Vec<Lc> { bytes.iter().rev().flatten().cloned().collect() };
+    let (s, e) = (scalar_bits(&signature[..32]), scalar_bits(&signature[32..]));
+    let (ex, ey, e_zero) = grumpkin::variable_base_msm(builder, &[(key, bits::constant(false), e)]);
+    let terms = [(s, grumpkin::generator())];
+    let (sx, sy, s_zero) = grumpkin::fixed_base_msm(builder, &terms, &Point::INFINITY);
+    let (rx, _, r_infinite) = grumpkin::add_complete(
+        builder,
+        (&PointVar { x: ex, y: ey }, &e_zero),
+        (&PointVar { x: sx, y: sy }, &s_zero),
+    );
+
+    // The challenge digest must equal the bytes of e, compared in halves
+    // that fit in the field
+    let hash = pedersen::hash_lc(builder, &[rx, x, y], 0);
+    let mut hash_bits = bits::field_bits(builder, hash);
+    hash_bits.resize(256, bits::constant(false));
+    let mut preimage: Vec<Vec<Lc>> = hash_bits
+        .chunks(8)
+        .rev()
+        .map(|byte| byte.to_vec())
+        .collect();
+    preimage.extend(bits::input_bytes(builder, message));
+    let digest = blake2s::digest_gadget(builder, &preimage);
+    let mut matches = bits::constant(true);
+    for half in [0..16, 16..32] {
+        let expected = &signature[32..][half.clone()];
+        let diff = combine(&[
+            (Fr::one(), &pack_bytes(&digest[half])),
+            (-Fr::one(), &pack_bytes(expected)),
+        ]);
+        let equal = builder.is_zero(diff);
+        matches = bits::and(builder, &matches, &equal);
+    }
+
+    let mut valid = on_curve;
+    for flag in [not(&e_zero), not(&s_zero), not(&r_infinite), matches] {
+        valid = bits::and(builder, &valid, &flag);
+    }
+    builder.assign_output(output, valid);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acir_to_r1cs::{parse_field_element, AcirR1cs, WitnessMap};
+    use crate::test_utils::convert_opcode;
+    use serde_json::json;
+
+    /// Sign with private key `key` and nonce `k`
+    fn sign(key: u64, k: u64, message: &[u8]) -> (Point, [u8; 64]) {
+        let g = grumpkin::generator();
+        let public_key = g.mul(&BigUint::from(key));
+        let r = g.mul(&BigUint::from(k));
+        let e = challenge(r.x, &public_key, message);
+        let s = Fq::from(k) - Fq::from(key) * Fq::from_be_bytes_mod_order(&e);
+        let mut signature = [0u8; 64];
+        signature[..32].copy_from_slice(&s.into_bigint().to_bytes_be());
+        signature[32..].copy_from_slice(&e);
+        (public_key, signature)
+    }
+
+    fn convert(message_len: u32) -> AcirR1cs {
+        let bytes = |witnesses: std::ops::RangeInclusive<u32>| -> Vec<serde_json::Value> {
+            witnesses
+                .map(|w| json!({ "witness": w, "num_bits": 8 }))
+                .collect()
+        };
+        let output = 66 + message_len + 1;
+        convert_opcode(
+            json!({
+                "type": "BlackBoxFuncCall", "name": "SchnorrVerify",
+                "public_key_x": { "witness": 1, "num_bits": 254 },
+                "public_key_y": { "witness": 2, "num_bits": 254 },
+                "signature": bytes(3..=66),
+                "message": bytes(67..=66 + message_len),
+                "output": output
+            }),
+            output,
+            1..output,
+        )
+    }
+
+    /// The output of the gadget, after checking every constraint
+    fn gadget_output(r1cs: &AcirR1cs, key: (Fr, Fr), signature: &[u8; 64], message: &[u8]) -> Fr {
+        let mut values = vec![key.0, key.1];
+        values.extend(signature.iter().chain(message).map(|&b| Fr::from(b)));
+        let mut witness: WitnessMap = (1..).zip(values).collect();
+        r1cs.compute_aux_witnesses(&mut witness).unwrap();
+        assert!(r1cs
+            .constraints
+            .iter()
+            .all(|c| c.is_satisfied(&witness).unwrap()));
+        witness[&(67 + message.len() as u32)]
+    }
+
+    #[test]
+    fn test_noir_vector() {
+        // From Noir's schnorr test program
+        let x = parse_field_element(
+            "0x04b260954662e97f00cab9adb773a259097f7a274b83b113532bce27fa3fb96a",
+        )
+        .unwrap();
+        let y = parse_field_element(
+            "0x2fd51571db6c08666b0edfbfbc57d432068bccd0110a39b166ab243da0037197",
+        )
+        .unwrap();
+        let signature: [u8; 64] = [
+            1, 13, 119, 112, 212, 39, 233, 41, 84, 235, 255, 93, 245, 172, 186, 83, 157, 253, 76,
+            77, 33, 128, 178, 15, 214, 67, 105, 107, 177, 234, 77, 48, 27, 237, 155, 84, 39, 84,
+            247, 27, 22, 8, 176, 230, 24, 115, 145, 220, 254, 122, 135, 179, 171, 4, 214, 202, 64,
+            199, 19, 84, 239, 138, 124, 12,
+        ];
+        let message: Vec<u8> = (0..10).collect();
+        assert!(verify_signature(x, y, &signature, &message));
+        assert!(!verify_signature(x, y, &signature, &message[1..]));
+    }
+
+    #[test]
+    fn test_native() {
+        let message = b"hello schnorr";
+        let (key, signature) = sign(0x1234_5678, 0xabcdef, message);
+        assert!(verify_signature(key.x, key.y, &signature, message));
+        assert!(!verify_signature(
+            key.x,
+            key.y,
+            &signature,
+            b"hello schnorR"
+        ));
+        assert!(!verify_signature(key.x, -key.y, &signature, message));
+        assert!(!verify_signature(
+            key.x,
+            key.y + Fr::one(),
+            &signature,
+            message
+        ));
+        let mut zero_s = signature;
+        zero_s[..32].fill(0);
+        assert!(!verify_signature(key.x, key.y, &zero_s, message));
+    }
+
+    #[test]
+    fn test_verify_gadget() {
+        let message = b"stealth address";
+        let r1cs = convert(message.len() as u32);
+        let (key, signature) = sign(987_654_321, 123_456_789, message);
+        let key = (key.x, key.y);
+        assert_eq!(gadget_output(&r1cs, key, &signature, message), Fr::one());
+
+        // A wrong message, a tampered signature and an off-curve key all
+        // verify to 0
+        let mut tampered = signature;
+        tampered[40] ^= 1;
+        let off_curve = (key.0, key.1 + Fr::one());
+        for (key, signature, message) in [
+            (key, signature, b"stealth addresS"),
+            (key, tampered, message),
+            (off_curve, signature, message),
+        ] {
+            assert!(!verify_signature(key.0, key.1, &signature, message));
+            assert_eq!(gadget_output(&r1cs, key, &signature, message), Fr::zero());
+        }
+    }
+}
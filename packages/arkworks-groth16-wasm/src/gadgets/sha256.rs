@@ -0,0 +1,319 @@
+//! SHA-256 compression function and hash.
+//!
+//! The gadget works on bits (see `bits`): each round costs two constraints
+//! per bit for each Σ function, one for Ch, two for Maj, plus the bit
+//! decompositions of the two modular sums, about 27k constraints per block.
+
+use ark_bn254::Fr;
+use ark_ff::One;
+
+use super::{bits, combine, Lc};
+use crate::acir_to_r1cs::R1csBuilder;
+use crate::acir_types::FunctionInput;
+use crate::error::ArkworksError;
+
+/// Initial hash value H(0)
+pub const IV: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+/// Round constants K
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// Apply the compression function to a state and a 16-word block
+pub fn compress(state: &[u32; 8], block: &[u32; 16]) -> [u32; 8] {
+    let mut w = [0u32; 64];
+    w[..16].copy_from_slice(block);
+    for t in 16..64 {
+        let s0 = w[t - 15].rotate_right(7) ^ w[t - 15].rotate_right(18) ^ (w[t - 15] >> 3);
+        let s1 = w[t - 2].rotate_right(17) ^ w[t - 2].rotate_right(19) ^ (w[t - 2] >> 10);
+        w[t] = w[t - 16].wrapping_add(s0).wrapping_add(w[t - 7]).wrapping_add(s1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+    for t in 0..64 {
+        let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let ch = (e & f) ^ (!e & g);
+        let t1 = h.wrapping_add(s1).wrapping_add(ch).wrapping_add(K[t]).wrapping_add(w[t]);
+        let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let t2 = s0.wrapping_add(maj);
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(t1);
+        d = c;
+        c = b;
+        b = a;
+        a = t1.wrapping_add(t2);
+    }
+
+    let mut result = *state;
+    for (x, y) in result.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+        *x = x.wrapping_add(y);
+    }
+    result
+}
+
+/// Hash a message
+pub fn digest(message: &[u8]) -> [u8; 32] {
+    let mut state = IV;
+    for block in pad(message).chunks(64) {
+        let mut words = [0u32; 16];
+        for (word, bytes) in words.iter_mut().zip(block.chunks(4)) {
+            *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        state = compress(&state, &words);
+    }
+
+    let mut output = [0u8; 32];
+    for (bytes, word) in output.chunks_mut(4).zip(state) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+    output
+}
+
+/// Message padding: a 1 bit, zeros, then the bit length as a 64-bit integer
+fn pad(message: &[u8]) -> Vec<u8> {
+    let mut padded = message.to_vec();
+    padded.push(0x80);
+    while padded.len() % 64 != 56 {
+        padded.push(0);
+    }
+    padded.extend_from_slice(&((message.len() as u64) * 8).to_be_bytes());
+    padded
+}
+
+/// Constrain a state and block to the compression function output
+fn compress_gadget(builder: &mut R1csBuilder, state: &[Vec<Lc>], block: &[Vec<Lc>]) -> Vec<Vec<Lc>> {
+    let mut w: Vec<Vec<Lc>> = block.to_vec();
+    for t in 16..64 {
+        let s0 = sigma(builder, &w[t - 15], (7, 18), Shift::Right(3));
+        let s1 = sigma(builder, &w[t - 2], (17, 19), Shift::Right(10));
+        let sum = bits::add_words(builder, &[&w[t - 16], &s0, &w[t - 7], &s1], 0, 32);
+        w.push(sum);
+    }
+
+    let mut v: Vec<Vec<Lc>> = state.to_vec();
+    for t in 0..64 {
+        let (a, b, c, d, e, f, g, h) = (&v[0], &v[1], &v[2], &v[3], &v[4], &v[5], &v[6], &v[7]);
+        let s1 = sigma(builder, e, (6, 11), Shift::Rotate(25));
+        // Ch = g + e(f - g)
+        let ch: Vec<Lc> = (0..32)
+            .map(|i| {
+                let diff = combine(&[(Fr::one(), &f[i]), (-Fr::one(), &g[i])]);
+                let product = bits::and(builder, &e[i], &diff);
+                combine(&[(Fr::one(), &g[i]), (Fr::one(), &product)])
+            })
+            .collect();
+        let s0 = sigma(builder, a, (2, 13), Shift::Rotate(22));
+        // Maj = bc + a(b XOR c)
+        let maj: Vec<Lc> = (0..32)
+            .map(|i| {
+                let bc = bits::and(builder, &b[i], &c[i]);
+                let b_xor_c =
+                    combine(&[(Fr::one(), &b[i]), (Fr::one(), &c[i]), (-Fr::from(2u64), &bc)]);
+                let product = bits::and(builder, &a[i], &b_xor_c);
+                combine(&[(Fr::one(), &bc), (Fr::one(), &product)])
+            })
+            .collect();
+
+        let k = K[t] as u64;
+        let new_e = bits::add_words(builder, &[d, h, &s1, &ch, &w[t]], k, 32);
+        let new_a = bits::add_words(builder, &[h, &s1, &ch, &w[t], &s0, &maj], k, 32);
+        v.rotate_right(1);
+        v[0] = new_a;
+        v[4] = new_e;
+    }
+
+    state
+        .iter()
+        .zip(&v)
+        .map(|(x, y)| bits::add_words(builder, &[x, y], 0, 32))
+        .collect()
+}
+
+/// Third term of a σ/Σ function
+enum Shift {
+    Rotate(usize),
+    Right(usize),
+}
+
+/// ROTR^r0(x) XOR ROTR^r1(x) XOR (ROTR or SHR)(x)
+fn sigma(builder: &mut R1csBuilder, x: &[Lc], rotations: (usize, usize), third: Shift) -> Vec<Lc> {
+    let first = bits::rotate_right(x, rotations.0);
+    let second = bits::rotate_right(x, rotations.1);
+    let third = match third {
+        Shift::Rotate(n) => bits::rotate_right(x, n),
+        Shift::Right(n) => bits::shift_right(x, n),
+    };
+    let partial = bits::xor_words(builder, &first, &second);
+    bits::xor_words(builder, &partial, &third)
+}
+
+/// Constrain a `Sha256Compression` black box
+///
+/// `inputs` are the 16 block words and `hash_values` the 8 state words,
+/// each range-checked to 32 bits; `outputs` receive the new state.
+pub(crate) fn compression_gadget(
+    builder: &mut R1csBuilder,
+    inputs: &[FunctionInput],
+    hash_values: &[FunctionInput],
+    outputs: &[u32],
+) -> Result<(), ArkworksError> {
+    if inputs.len() != 16 || hash_values.len() != 8 || outputs.len() != 8 {
+        return Err(ArkworksError::InvalidInput(format!(
+            "SHA-256 compression takes 16 words and 8 hash values into 8 outputs, got {}, {} and {}",
+            inputs.len(),
+            hash_values.len(),
+            outputs.len()
+        )));
+    }
+    let mut word = |input: &FunctionInput| {
+        let input = FunctionInput { num_bits: 32, ..input.clone() };
+        bits::input_bits(builder, &input)
+    };
+    let block: Vec<Vec<Lc>> = inputs.iter().map(&mut word).collect();
+    let state: Vec<Vec<Lc>> = hash_values.iter().map(&mut word).collect();
+
+    let result = compress_gadget(builder, &state, &block);
+    for (word, &output) in result.iter().zip(outputs) {
+        builder.assign_output(output, bits::pack(word));
+    }
+    Ok(())
+}
+
+/// Constrain a `SHA256` black box: `outputs` are the 32 digest bytes of the
+/// message formed by `inputs` (see `bits::input_bytes`)
+///
+/// The message length is fixed by the circuit, so padding is constant and
+/// the compression gadget runs once per 64-byte block.
+pub(crate) fn hash_gadget(
+    builder: &mut R1csBuilder,
+    inputs: &[FunctionInput],
+    outputs: &[u32],
+) -> Result<(), ArkworksError> {
+    if outputs.len() != 32 {
+        return Err(ArkworksError::InvalidInput(format!(
+            "SHA-256 has 32 output bytes, got {}",
+            outputs.len()
+        )));
+    }
+    let mut bytes = bits::input_bytes(builder, inputs);
+
+    // The padding only depends on the message length, so it is constant
+    let padded = pad(&vec![0u8; bytes.len()]);
+    let padding = &padded[bytes.len()..];
+    bytes.extend(padding.iter().map(|&byte| bits::constant_word(byte as u64, 8)));
+
+    let mut state: Vec<Vec<Lc>> = IV.iter().map(|&h| bits::constant_word(h as u64, 32)).collect();
+    for block in bytes.chunks(64) {
+        // Big-endian words: the last byte holds the least significant bits
+        let words: Vec<Vec<Lc>> = block
+            .chunks(4)
+            .map(|word| word.iter().rev().flatten().cloned().collect())
+            .collect();
+        state = compress_gadget(builder, &state, &words);
+    }
+
+    for (word, outputs) in state.iter().zip(outputs.chunks(4)) {
+        for (byte, &output) in word.chunks(8).rev().zip(outputs) {
+            builder.assign_output(output, bits::pack(byte));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acir_to_r1cs::{acir_to_r1cs, WitnessMap};
+    use crate::test_utils::one_opcode_program;
+    use serde_json::json;
+
+    #[test]
+    fn test_digest_vectors() {
+        assert_eq!(
+            hex::encode(digest(b"")),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            hex::encode(digest(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        // Two blocks
+        assert_eq!(
+            hex::encode(digest(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+    }
+
+    #[test]
+    fn test_compression_gadget() {
+        let word = |w: u32| json!({ "witness": w, "num_bits": 32 });
+        let program = one_opcode_program(
+            json!({
+                "type": "BlackBoxFuncCall", "name": "Sha256Compression",
+                "inputs": (1..=16).map(word).collect::<Vec<_>>(),
+                "hash_values": (17..=24).map(word).collect::<Vec<_>>(),
+                "outputs": (25..=32).collect::<Vec<u32>>()
+            }),
+            32,
+            1..=24,
+        );
+        let r1cs = acir_to_r1cs(&program).unwrap();
+        assert_eq!(r1cs.black_box_constraints["Sha256Compression"], r1cs.constraints.len());
+
+        let block: [u32; 16] = std::array::from_fn(|i| (i as u32).wrapping_mul(0x9e3779b9));
+        let values: Vec<u32> = block.iter().chain(&IV).copied().collect();
+        let initial: WitnessMap = (1..=24).zip(values.iter().map(|&v| Fr::from(v))).collect();
+
+        let mut witness = initial.clone();
+        r1cs.compute_aux_witnesses(&mut witness).unwrap();
+        assert!(r1cs.constraints.iter().all(|c| c.is_satisfied(&witness).unwrap()));
+        let expected = compress(&IV, &block);
+        for (w, word) in (25..=32).zip(expected) {
+            assert_eq!(witness[&w], Fr::from(word));
+        }
+
+        // A wrong output is kept and fails its constraint
+        let mut witness = initial;
+        witness.insert(25, Fr::from(expected[0] ^ 1));
+        r1cs.compute_aux_witnesses(&mut witness).unwrap();
+        assert!(!r1cs.constraints.iter().all(|c| c.is_satisfied(&witness).unwrap()));
+    }
+
+    #[test]
+    fn test_hash_gadget() {
+        let message = b"abc";
+        let program = one_opcode_program(
+            json!({
+                "type": "BlackBoxFuncCall", "name": "SHA256",
+                "inputs": (1..=3).map(|w| json!({ "witness": w, "num_bits": 8 })).collect::<Vec<_>>(),
+                "outputs": (4..36).collect::<Vec<u32>>()
+            }),
+            35,
+            vec![1, 2, 3],
+        );
+        let r1cs = acir_to_r1cs(&program).unwrap();
+
+        let mut witness: WitnessMap =
+            (1..=3).zip(message.iter().map(|&b| Fr::from(b))).collect();
+        r1cs.compute_aux_witnesses(&mut witness).unwrap();
+        assert!(r1cs.constraints.iter().all(|c| c.is_satisfied(&witness).unwrap()));
+        let output: Vec<Fr> = (4..36).map(|w| witness[&w]).collect();
+        let expected: Vec<Fr> = digest(message).iter().map(|&b| Fr::from(b)).collect();
+        assert_eq!(output, expected);
+
+        assert_eq!(r1cs.black_box_constraints["SHA256"], r1cs.constraints.len());
+    }
+}
@@ -11,7 +11,7 @@
 
 use ark_bn254::{Bn254, Fq, Fq2, Fr, G1Affine, G2Affine};
 use ark_ec::AffineRepr;
-use ark_ff::{BigInteger, PrimeField};
+use ark_ff::{BigInt, BigInteger, PrimeField};
 use ark_groth16::{Proof, VerifyingKey};
 
 use crate::error::ArkworksError;
@@ -52,6 +52,9 @@ pub fn g1_to_gnark(point: &G1Affine) -> [u8; G1_SIZE] {
 }
 
 /// Convert gnark format to G1 affine point
+///
+/// Points off the curve or outside the prime-order subgroup are rejected
+/// with an error rather than a panic.
 pub fn g1_from_gnark(bytes: &[u8; G1_SIZE]) -> Result<G1Affine, ArkworksError> {
     if bytes.iter().all(|&b| b == 0) {
         return Ok(G1Affine::zero());
@@ -60,9 +63,12 @@ pub fn g1_from_gnark(bytes: &[u8; G1_SIZE]) -> Result<G1Affine, ArkworksError> {
     let x = fq_from_be_bytes(&bytes[..32])?;
     let y = fq_from_be_bytes(&bytes[32..])?;
 
-    G1Affine::new(x, y)
-        .try_into()
-        .map_err(|_| ArkworksError::ParseError("Invalid G1 point".to_string()))
+    let point = G1Affine::new_unchecked(x, y);
+    if !point.is_on_curve() || !point.is_in_correct_subgroup_assuming_on_curve() {
+        return Err(ArkworksError::ParseError("Invalid G1 point".to_string()));
+    }
+
+    Ok(point)
 }
 
 /// Convert a G2 affine point to gnark format (128 bytes, big-endian, uncompressed)
@@ -99,6 +105,9 @@ pub fn g2_to_gnark(point: &G2Affine) -> [u8; G2_SIZE] {
 
 /// Convert gnark format to G2 affine point
 /// EIP-196/197 format: [x.c1 | x.c0 | y.c1 | y.c0] (imaginary first!)
+///
+/// Points off the curve or outside the prime-order subgroup are rejected
+/// with an error rather than a panic.
 pub fn g2_from_gnark(bytes: &[u8; G2_SIZE]) -> Result<G2Affine, ArkworksError> {
     if bytes.iter().all(|&b| b == 0) {
         return Ok(G2Affine::zero());
@@ -113,9 +122,12 @@ pub fn g2_from_gnark(bytes: &[u8; G2_SIZE]) -> Result<G2Affine, ArkworksError> {
     let x = Fq2::new(x_c0, x_c1);
     let y = Fq2::new(y_c0, y_c1);
 
-    G2Affine::new(x, y)
-        .try_into()
-        .map_err(|_| ArkworksError::ParseError("Invalid G2 point".to_string()))
+    let point = G2Affine::new_unchecked(x, y);
+    if !point.is_on_curve() || !point.is_in_correct_subgroup_assuming_on_curve() {
+        return Err(ArkworksError::ParseError("Invalid G2 point".to_string()));
+    }
+
+    Ok(point)
 }
 
 /// Convert Fq element to 32 bytes big-endian
@@ -152,27 +164,21 @@ pub fn fr_to_be_bytes(fr: &Fr) -> [u8; FIELD_SIZE] {
 
 /// Convert 32 bytes big-endian to Fq element
 fn fq_from_be_bytes(bytes: &[u8]) -> Result<Fq, ArkworksError> {
-    if bytes.len() != FIELD_SIZE {
-        return Err(ArkworksError::ParseError(format!(
-            "Expected {} bytes, got {}",
-            FIELD_SIZE,
-            bytes.len()
-        )));
-    }
-
-    // Convert from big-endian to little-endian
-    let mut le_bytes = [0u8; FIELD_SIZE];
-    for (i, &b) in bytes.iter().enumerate() {
-        le_bytes[FIELD_SIZE - 1 - i] = b;
-    }
-
-    Fq::from_le_bytes_mod_order(&le_bytes)
-        .try_into()
-        .map_err(|_| ArkworksError::ParseError("Invalid Fq element".to_string()))
+    field_from_be_bytes(bytes, "Fq")
 }
 
 /// Convert 32 bytes big-endian to Fr element
+///
+/// Values not less than the modulus are rejected rather than reduced.
 pub fn fr_from_be_bytes(bytes: &[u8]) -> Result<Fr, ArkworksError> {
+    field_from_be_bytes(bytes, "Fr")
+}
+
+/// Convert 32 bytes big-endian to a canonical element of a BN254 field
+fn field_from_be_bytes<F: PrimeField<BigInt = BigInt<4>>>(
+    bytes: &[u8],
+    name: &str,
+) -> Result<F, ArkworksError> {
     if bytes.len() != FIELD_SIZE {
         return Err(ArkworksError::ParseError(format!(
             "Expected {} bytes, got {}",
@@ -181,15 +187,18 @@ pub fn fr_from_be_bytes(bytes: &[u8]) -> Result<Fr, ArkworksError> {
         )));
     }
 
-    // Convert from big-endian to little-endian
-    let mut le_bytes = [0u8; FIELD_SIZE];
-    for (i, &b) in bytes.iter().enumerate() {
-        le_bytes[FIELD_SIZE - 1 - i] = b;
+    // Limbs are little-endian, the bytes within each limb big-endian
+    let mut limbs = [0u64; 4];
+    for (limb, chunk) in limbs.iter_mut().zip(bytes.rchunks_exact(8)) {
+        *limb = u64::from_be_bytes(chunk.try_into().expect("chunks are 8 bytes"));
     }
 
-    Fr::from_le_bytes_mod_order(&le_bytes)
-        .try_into()
-        .map_err(|_| ArkworksError::ParseError("Invalid Fr element".to_string()))
+    F::from_bigint(BigInt::new(limbs)).ok_or_else(|| {
+        ArkworksError::ParseError(format!(
+            "Invalid {} element: not less than the field modulus",
+            name
+        ))
+    })
 }
 
 /// Convert arkworks Groth16 proof to gnark format (256 bytes)
@@ -237,7 +246,7 @@ pub fn public_inputs_to_gnark(inputs: &[Fr]) -> Vec<u8> {
 
 /// Convert gnark format public inputs to Fr elements
 pub fn public_inputs_from_gnark(bytes: &[u8]) -> Result<Vec<Fr>, ArkworksError> {
-    if bytes.len() % FIELD_SIZE != 0 {
+    if !bytes.len().is_multiple_of(FIELD_SIZE) {
         return Err(ArkworksError::ParseError(format!(
             "Invalid public inputs size: {} is not a multiple of {}",
             bytes.len(),
@@ -375,6 +384,50 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_invalid_points_rejected() {
+        // y^2 = x^3 + 3 does not hold for (1, 1)
+        let off_curve = G1Affine::new_unchecked(Fq::from(1u64), Fq::from(1u64));
+        assert!(g1_from_gnark(&g1_to_gnark(&off_curve)).is_err());
+
+        let generator = G2Affine::generator();
+        let off_curve = G2Affine::new_unchecked(generator.x, generator.x);
+        assert!(g2_from_gnark(&g2_to_gnark(&off_curve)).is_err());
+
+        // G2 has a cofactor, so most points on the twist lie outside the
+        // prime-order subgroup
+        let mut rng = ark_std::test_rng();
+        let outside = std::iter::repeat_with(|| Fq2::rand(&mut rng))
+            .filter_map(|x| G2Affine::get_point_from_x_unchecked(x, false))
+            .find(|p| !p.is_in_correct_subgroup_assuming_on_curve())
+            .unwrap();
+        assert!(outside.is_on_curve());
+        assert!(g2_from_gnark(&g2_to_gnark(&outside)).is_err());
+    }
+
+    #[test]
+    fn test_non_canonical_field_elements_rejected() {
+        let mut r_plus_one = Fr::MODULUS;
+        r_plus_one.add_with_carry(&BigInt::from(1u64));
+        let bytes = r_plus_one.to_bytes_be();
+        assert!(fr_from_be_bytes(&bytes).is_err());
+
+        let mut inputs = public_inputs_to_gnark(&[Fr::from(1u64)]);
+        inputs.extend_from_slice(&bytes);
+        assert!(public_inputs_from_gnark(&inputs).is_err());
+
+        let mut r_minus_one = Fr::MODULUS;
+        r_minus_one.sub_with_borrow(&BigInt::from(1u64));
+        assert_eq!(fr_from_be_bytes(&r_minus_one.to_bytes_be()).unwrap(), -Fr::from(1u64));
+
+        // (p + 1, 2) would reduce to the generator (1, 2)
+        let mut p_plus_one = Fq::MODULUS;
+        p_plus_one.add_with_carry(&BigInt::from(1u64));
+        let mut point = g1_to_gnark(&G1Affine::generator());
+        point[..32].copy_from_slice(&p_plus_one.to_bytes_be());
+        assert!(g1_from_gnark(&point).is_err());
+    }
+
     #[test]
     fn test_proof_size() {
         // Verify the expected sizes
@@ -12,6 +12,7 @@ use ark_snark::SNARK;
 use ark_std::rand::rngs::OsRng;
 
 use crate::acir_to_r1cs::{AcirCircuitSynthesizer, AcirR1cs, WitnessMap};
+use crate::checker;
 use crate::error::ArkworksError;
 use crate::gnark_compat;
 
@@ -66,6 +67,7 @@ impl Groth16Prover {
         r1cs: &AcirR1cs,
         witness: WitnessMap,
     ) -> Result<ProofResult, ArkworksError> {
+        let witness = complete_witness(r1cs, witness)?;
         let circuit = AcirCircuitSynthesizer::new(r1cs.clone(), Some(witness.clone()));
 
         // Generate proof
@@ -73,17 +75,8 @@ impl Groth16Prover {
         let proof = Groth16::<Bn254>::prove(&self.proving_key, circuit, &mut rng)
             .map_err(|e| ArkworksError::ProofError(e.to_string()))?;
 
-        // Extract public inputs in order
-        let public_inputs: Vec<Fr> = r1cs
-            .public_inputs
-            .iter()
-            .map(|&idx| {
-                witness
-                    .get(&idx)
-                    .copied()
-                    .ok_or(ArkworksError::MissingWitness(idx))
-            })
-            .collect::<Result<Vec<_>, _>>()?;
+        // Extract the public statement: public inputs, then return values
+        let public_inputs = r1cs.public_values(&witness)?;
 
         Ok(ProofResult {
             proof,
@@ -145,22 +138,14 @@ pub fn prove(
     r1cs: &AcirR1cs,
     witness: WitnessMap,
 ) -> Result<ProofResult, ArkworksError> {
+    let witness = complete_witness(r1cs, witness)?;
     let circuit = AcirCircuitSynthesizer::new(r1cs.clone(), Some(witness.clone()));
 
     let mut rng = OsRng;
     let proof = Groth16::<Bn254>::prove(proving_key, circuit, &mut rng)
         .map_err(|e| ArkworksError::ProofError(e.to_string()))?;
 
-    let public_inputs: Vec<Fr> = r1cs
-        .public_inputs
-        .iter()
-        .map(|&idx| {
-            witness
-                .get(&idx)
-                .copied()
-                .ok_or(ArkworksError::MissingWitness(idx))
-        })
-        .collect::<Result<Vec<_>, _>>()?;
+    let public_inputs = r1cs.public_values(&witness)?;
 
     Ok(ProofResult {
         proof,
@@ -168,6 +153,20 @@ pub fn prove(
     })
 }
 
+/// Fill in auxiliary witnesses and check that every constraint holds
+///
+/// arkworks does not report unsatisfied constraints in release builds,
+/// so an invalid witness would otherwise yield a proof that never verifies.
+fn complete_witness(r1cs: &AcirR1cs, witness: WitnessMap) -> Result<WitnessMap, ArkworksError> {
+    let witness = r1cs.assign_witness(witness)?;
+
+    if let Some(failure) = checker::first_failure(r1cs, &witness)? {
+        return Err(ArkworksError::UnsatisfiedConstraint(Box::new(failure)));
+    }
+
+    Ok(witness)
+}
+
 /// Verify a proof
 pub fn verify(
     verifying_key: &VerifyingKey<Bn254>,
@@ -236,6 +235,12 @@ mod tests {
                 b: vec![(Fr::one(), 2)], // y
                 c: vec![(Fr::one(), 3)], // z
             }],
+            aux_witnesses: vec![],
+            calls: vec![],
+            black_box_constraints: Default::default(),
+            origins: vec![],
+            assert_messages: Default::default(),
+            renumbering: None,
         }
     }
 
@@ -285,6 +290,13 @@ mod tests {
         // This should fail during proof generation because constraints aren't satisfied
         let result = prove(&setup_result.proving_key, &r1cs, witness);
         assert!(result.is_err(), "Proof generation should fail for invalid witness");
+        match result {
+            Err(err @ ArkworksError::UnsatisfiedConstraint(_)) => assert_eq!(
+                err.to_string(),
+                "Constraint 0 is not satisfied: A = 3, B = 4, C = 11"
+            ),
+            _ => panic!("expected the failing constraint to be reported"),
+        }
     }
 
     #[test]
@@ -321,4 +333,40 @@ mod tests {
 
         assert!(is_valid, "Recovered proof should be valid");
     }
+
+    #[test]
+    fn test_return_values_are_public() {
+        // x * y = z with z returned rather than taken as a public input
+        let mut r1cs = create_test_r1cs();
+        r1cs.public_inputs = vec![];
+        let setup_result = setup(&r1cs).expect("Setup failed");
+        assert_eq!(setup_result.verifying_key.gamma_abc_g1.len(), 2);
+
+        let mut witness = WitnessMap::new();
+        witness.insert(1, Fr::from(3u64));
+        witness.insert(2, Fr::from(4u64));
+        witness.insert(3, Fr::from(12u64));
+        let proof_result = prove(&setup_result.proving_key, &r1cs, witness)
+            .expect("Proof generation failed");
+        assert_eq!(proof_result.public_inputs, vec![Fr::from(12u64)]);
+
+        let vk = &setup_result.verifying_key;
+        assert!(verify(vk, &proof_result.proof, &[Fr::from(12u64)]).unwrap());
+        assert!(!verify(vk, &proof_result.proof, &[Fr::from(13u64)]).unwrap());
+
+        // A public input that is also returned appears twice in the statement
+        let r1cs = create_test_r1cs();
+        let setup_result = setup(&r1cs).expect("Setup failed");
+        let mut witness = WitnessMap::new();
+        witness.insert(1, Fr::from(3u64));
+        witness.insert(2, Fr::from(4u64));
+        witness.insert(3, Fr::from(12u64));
+        let proof_result = prove(&setup_result.proving_key, &r1cs, witness)
+            .expect("Proof generation failed");
+        assert_eq!(proof_result.public_inputs, vec![Fr::from(12u64); 2]);
+
+        let vk = &setup_result.verifying_key;
+        let mismatched = [Fr::from(12u64), Fr::from(13u64)];
+        assert!(!verify(vk, &proof_result.proof, &mismatched).unwrap_or(false));
+    }
 }
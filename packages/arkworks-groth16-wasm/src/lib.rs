@@ -25,19 +25,39 @@
 //! // Verify
 //! const isValid = await verify(verifyingKey, proof, publicInputs);
 //! ```
-
+//!
+//! # Field elements
+//!
+//! Witness values, public inputs and R1CS coefficients are strings: `0x`
+//! followed by hex digits, or decimal digits, optionally negated with a
+//! leading `-`. Strings without the prefix are decimal, so `"10"` is ten;
+//! earlier releases read it as hex. Values must be less than the BN254
+//! scalar field modulus, and are never reduced. Outputs are always
+//! 0x-prefixed, 32-byte hex.
+
+pub mod abi;
 pub mod acir_to_r1cs;
 pub mod acir_types;
+pub mod brillig;
+pub mod checker;
+pub mod debug_info;
 pub mod error;
+pub mod gadgets;
 pub mod gnark_compat;
 pub mod groth16;
+pub mod linter;
+pub mod optimizer;
+pub mod solver;
+
+#[cfg(test)]
+mod test_utils;
 
 use wasm_bindgen::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use acir_to_r1cs::{acir_to_r1cs, parse_field_element, WitnessMap};
-use acir_types::AcirProgram;
+use acir_types::{AcirProgram, CircuitAbi};
 
 // Initialize panic hook for better error messages in browser
 #[wasm_bindgen(start)]
@@ -64,14 +84,16 @@ pub struct JsProofResult {
     pub proof: String,
     /// Base64-encoded proof (gnark format for Solana, 256 bytes)
     pub proof_gnark: String,
-    /// Public inputs as hex strings
+    /// Public inputs followed by return values, as hex strings
     pub public_inputs: Vec<String>,
-    /// Public inputs in gnark format (32 bytes each, big-endian)
+    /// Public inputs and return values in gnark format (32 bytes each, big-endian)
     pub public_inputs_gnark: String,
 }
 
 /// Perform trusted setup for a circuit
 ///
+/// The converted R1CS is optimized first, exactly as `prove` does.
+///
 /// # Arguments
 /// * `acir_json` - JSON string of the ACIR program from Noir compiler
 ///
@@ -79,11 +101,34 @@ pub struct JsProofResult {
 /// * `JsSetupResult` with base64-encoded proving and verifying keys
 #[wasm_bindgen]
 pub fn setup(acir_json: &str) -> Result<JsValue, JsValue> {
+    setup_program(acir_json, false)
+}
+
+/// Perform trusted setup for a circuit, refusing one with soundness gaps
+///
+/// Like `setup`, but fails if `lint_circuit` reports any warning.
+///
+/// # Arguments
+/// * `acir_json` - JSON string of the ACIR program from Noir compiler
+///
+/// # Returns
+/// * `JsSetupResult` with base64-encoded proving and verifying keys
+#[wasm_bindgen]
+pub fn setup_strict(acir_json: &str) -> Result<JsValue, JsValue> {
+    setup_program(acir_json, true)
+}
+
+fn setup_program(acir_json: &str, strict: bool) -> Result<JsValue, JsValue> {
     let program: AcirProgram = serde_json::from_str(acir_json)
         .map_err(|e| JsValue::from_str(&format!("Failed to parse ACIR: {}", e)))?;
 
-    let r1cs = acir_to_r1cs(&program)
-        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let unoptimized = acir_to_r1cs(&program)
+        .map_err(|e| located_error(&program, &e))?;
+    if strict {
+        linter::ensure_sound(&program, &unoptimized)
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    }
+    let r1cs = optimizer::optimize(&unoptimized);
 
     let setup_result = groth16::setup(&r1cs)
         .map_err(|e| JsValue::from_str(&e.to_string()))?;
@@ -117,7 +162,8 @@ pub fn setup(acir_json: &str) -> Result<JsValue, JsValue> {
 /// # Arguments
 /// * `proving_key_b64` - Base64-encoded proving key from setup
 /// * `acir_json` - JSON string of the ACIR program
-/// * `witness_json` - JSON object mapping witness indices to hex values
+/// * `witness_json` - JSON object mapping witness indices to field elements;
+///   the inputs are enough, the remaining witnesses are solved
 ///
 /// # Returns
 /// * `JsProofResult` with proof and public inputs
@@ -142,27 +188,18 @@ pub fn prove(
         .map_err(|e| JsValue::from_str(&format!("Failed to parse ACIR: {}", e)))?;
 
     let r1cs = acir_to_r1cs(&program)
-        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        .map(|r1cs| optimizer::optimize(&r1cs))
+        .map_err(|e| located_error(&program, &e))?;
 
-    // Parse witness
-    let witness_map: HashMap<String, String> = serde_json::from_str(witness_json)
-        .map_err(|e| JsValue::from_str(&format!("Failed to parse witness: {}", e)))?;
-
-    let mut witness = WitnessMap::new();
-    // Always set w_0 = 1
-    witness.insert(0, ark_bn254::Fr::from(1u64));
-
-    for (key, value) in witness_map {
-        let idx: u32 = key.parse()
-            .map_err(|_| JsValue::from_str(&format!("Invalid witness index: {}", key)))?;
-        let fr = parse_field_element(&value)
-            .map_err(|e| JsValue::from_str(&e.to_string()))?;
-        witness.insert(idx, fr);
-    }
+    // Parse witness and solve whatever it leaves out
+    let initial = parse_witness_json(witness_json)?;
+    let witness = solver::solve_witness(&program, &initial)
+        .and_then(|solved| solved.into_r1cs_witness(&r1cs))
+        .map_err(|e| located_error(&program, &e))?;
 
     // Generate proof
     let proof_result = groth16::prove(&proving_key, &r1cs, witness)
-        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        .map_err(|e| located_error(&program, &e))?;
 
     // Serialize results
     let proof_bytes = groth16::proof_to_bytes(&proof_result.proof)
@@ -192,12 +229,186 @@ pub fn prove(
         .map_err(|e| JsValue::from_str(&e.to_string()))
 }
 
+/// Solve the full witness map of an ACIR program
+///
+/// # Arguments
+/// * `acir_json` - JSON string of the ACIR program
+/// * `inputs_json` - JSON object mapping input witness indices to field elements
+///
+/// # Returns
+/// * JSON object mapping every solved witness index to its hex value
+#[wasm_bindgen]
+pub fn solve_witness(acir_json: &str, inputs_json: &str) -> Result<JsValue, JsValue> {
+    let program: AcirProgram = serde_json::from_str(acir_json)
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse ACIR: {}", e)))?;
+
+    let initial = parse_witness_json(inputs_json)?;
+    let solved = solver::solve_witness(&program, &initial)
+        .map_err(|e| located_error(&program, &e))?;
+
+    to_js_object(&witness_to_json(&solved.witness))
+}
+
+/// Check a witness against the constraints of an ACIR program
+///
+/// The witness is solved first, as for `prove`. If solving fails, the
+/// error is reported and the witness is checked as given instead.
+///
+/// # Arguments
+/// * `acir_json` - JSON string of the ACIR program
+/// * `witness_json` - JSON object mapping witness indices to field elements
+///
+/// # Returns
+/// * Report with `satisfied`, `missing_witnesses`, `aux_errors`, any
+///   `solver_error`, and the first `failure`: its constraint, ACIR
+///   function and opcode, assert message, evaluated A, B and C, and, if
+///   the program has debug symbols, its Noir `call_stack`, innermost first
+#[wasm_bindgen]
+pub fn check_witness(acir_json: &str, witness_json: &str) -> Result<JsValue, JsValue> {
+    let program: AcirProgram = serde_json::from_str(acir_json)
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse ACIR: {}", e)))?;
+
+    let r1cs = acir_to_r1cs(&program)
+        .map_err(|e| located_error(&program, &e))?;
+    let debug = program.debug_info()
+        .map_err(|e| JsValue::from_str(&e.to_string()))?
+        .unwrap_or_default();
+
+    let initial = parse_witness_json(witness_json)?;
+    let (witness, solver_error) = match solver::solve_witness(&program, &initial)
+        .and_then(|solved| solved.into_r1cs_witness(&r1cs))
+    {
+        Ok(witness) => (witness, None),
+        Err(e) => (initial, Some(debug.describe(&e))),
+    };
+    let report = checker::check_witness(&r1cs, &witness);
+
+    #[derive(Serialize)]
+    struct Failure {
+        constraint: usize,
+        function: Option<u32>,
+        opcode: Option<usize>,
+        assert_message: Option<String>,
+        message: String,
+        a: String,
+        b: String,
+        c: String,
+        call_stack: Vec<debug_info::SourceLocation>,
+    }
+
+    #[derive(Serialize)]
+    struct Report {
+        satisfied: bool,
+        num_constraints: usize,
+        missing_witnesses: Vec<u32>,
+        aux_errors: Vec<String>,
+        solver_error: Option<String>,
+        failure: Option<Failure>,
+    }
+
+    let js_report = Report {
+        satisfied: report.is_satisfied() && solver_error.is_none(),
+        num_constraints: report.num_constraints,
+        missing_witnesses: report.missing_witnesses,
+        aux_errors: report.aux_errors,
+        solver_error,
+        failure: report.failure.map(|failure| Failure {
+            constraint: failure.constraint,
+            function: failure.origin.map(|o| o.function),
+            opcode: failure.origin.map(|o| o.opcode),
+            message: failure.to_string(),
+            assert_message: failure.assert_message,
+            a: fr_to_hex(&failure.a),
+            b: fr_to_hex(&failure.b),
+            c: fr_to_hex(&failure.c),
+            call_stack: failure.origin.map(|o| debug.call_stack(o)).unwrap_or_default(),
+        }),
+    };
+
+    to_js_object(&js_report)
+}
+
+/// Encode named inputs into an initial witness map using the circuit ABI
+///
+/// # Arguments
+/// * `abi_json` - JSON string of the circuit ABI from the Noir compiler
+/// * `acir_json` - JSON string of the ACIR program
+/// * `inputs_json` - JSON object mapping parameter names to values
+///
+/// # Returns
+/// * JSON object mapping input witness indices to hex values, ready for
+///   `solve_witness` or `prove`
+#[wasm_bindgen]
+pub fn encode_inputs(abi_json: &str, acir_json: &str, inputs_json: &str) -> Result<JsValue, JsValue> {
+    let abi: CircuitAbi = serde_json::from_str(abi_json)
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse ABI: {}", e)))?;
+
+    let program: AcirProgram = serde_json::from_str(acir_json)
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse ACIR: {}", e)))?;
+
+    let inputs: serde_json::Value = serde_json::from_str(inputs_json)
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse inputs: {}", e)))?;
+
+    let witness = abi::encode_inputs(&abi, &program, &inputs)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    to_js_object(&witness_to_json(&witness))
+}
+
+/// Error message for JavaScript, followed by the Noir call stack of the
+/// failing opcode when the program has debug symbols
+fn located_error(program: &AcirProgram, err: &error::ArkworksError) -> JsValue {
+    match program.debug_info() {
+        Ok(Some(debug)) => JsValue::from_str(&debug.describe(err)),
+        _ => JsValue::from_str(&err.to_string()),
+    }
+}
+
+/// Convert to a plain JS object (string-keyed maps become objects, not `Map`s)
+fn to_js_object<T: Serialize>(value: &T) -> Result<JsValue, JsValue> {
+    value
+        .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Format a witness map as a JSON object of hex values, leaving out w_0
+fn witness_to_json(witness: &WitnessMap) -> HashMap<String, String> {
+    witness
+        .iter()
+        .filter(|(idx, _)| **idx != 0)
+        .map(|(idx, fr)| {
+            let bytes = gnark_compat::fr_to_be_bytes(fr);
+            (idx.to_string(), format!("0x{}", hex::encode(bytes)))
+        })
+        .collect()
+}
+
+/// Parse a JSON object mapping witness indices to field elements
+fn parse_witness_json(witness_json: &str) -> Result<WitnessMap, JsValue> {
+    let witness_map: HashMap<String, String> = serde_json::from_str(witness_json)
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse witness: {}", e)))?;
+
+    let mut witness = WitnessMap::new();
+    // Always set w_0 = 1
+    witness.insert(0, ark_bn254::Fr::from(1u64));
+
+    for (key, value) in witness_map {
+        let idx: u32 = key.parse()
+            .map_err(|_| JsValue::from_str(&format!("Invalid witness index: {}", key)))?;
+        let fr = parse_field_element(&value)
+            .map_err(|e| JsValue::from_str(&format!("Witness {}: {}", key, e)))?;
+        witness.insert(idx, fr);
+    }
+
+    Ok(witness)
+}
+
 /// Verify a Groth16 proof
 ///
 /// # Arguments
 /// * `verifying_key_b64` - Base64-encoded verifying key from setup
 /// * `proof_b64` - Base64-encoded proof (arkworks format)
-/// * `public_inputs_json` - JSON array of public inputs as hex strings
+/// * `public_inputs_json` - JSON array of public inputs as field element strings
 ///
 /// # Returns
 /// * `true` if proof is valid, `false` otherwise
@@ -225,15 +436,7 @@ pub fn verify(
         .map_err(|e| JsValue::from_str(&e.to_string()))?;
 
     // Parse public inputs
-    let inputs_hex: Vec<String> = serde_json::from_str(public_inputs_json)
-        .map_err(|e| JsValue::from_str(&format!("Failed to parse public inputs: {}", e)))?;
-
-    let mut public_inputs = Vec::new();
-    for hex_str in inputs_hex {
-        let fr = parse_field_element(&hex_str)
-            .map_err(|e| JsValue::from_str(&e.to_string()))?;
-        public_inputs.push(fr);
-    }
+    let public_inputs = parse_public_inputs_json(public_inputs_json)?;
 
     // Verify
     groth16::verify(&verifying_key, &proof, &public_inputs)
@@ -246,7 +449,7 @@ pub fn verify(
 /// * `verifying_key_gnark_b64` - Base64-encoded verifying key (gnark format)
 /// * `proof_gnark_b64` - Base64-encoded proof (gnark format, 256 bytes)
 /// * `public_inputs_gnark_b64` - Base64-encoded public inputs (gnark format)
-/// * `num_public_inputs` - Number of public inputs
+/// * `num_public_inputs` - Number of public inputs, return values included
 ///
 /// # Returns
 /// * `true` if proof is valid, `false` otherwise
@@ -285,34 +488,188 @@ pub fn verify_gnark(
         .map_err(|e| JsValue::from_str(&e.to_string()))
 }
 
+/// Decode public inputs into named, typed values using the circuit ABI
+///
+/// # Arguments
+/// * `abi_json` - JSON string of the circuit ABI from the Noir compiler
+/// * `public_inputs_json` - JSON array of public inputs as field element strings
+///
+/// # Returns
+/// * `{ parameters, return_value }` with the public parameters by name
+#[wasm_bindgen]
+pub fn decode_public_inputs(abi_json: &str, public_inputs_json: &str) -> Result<JsValue, JsValue> {
+    let abi: CircuitAbi = serde_json::from_str(abi_json)
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse ABI: {}", e)))?;
+
+    let public_inputs = parse_public_inputs_json(public_inputs_json)?;
+
+    let decoded = abi::decode_public_inputs(&abi, &public_inputs)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    to_js_object(&decoded)
+}
+
+/// Decode gnark-format public inputs into named, typed values
+///
+/// # Arguments
+/// * `abi_json` - JSON string of the circuit ABI from the Noir compiler
+/// * `public_inputs_gnark_b64` - Base64-encoded public inputs (gnark format)
+///
+/// # Returns
+/// * `{ parameters, return_value }` with the public parameters by name
+#[wasm_bindgen]
+pub fn decode_public_inputs_gnark(
+    abi_json: &str,
+    public_inputs_gnark_b64: &str,
+) -> Result<JsValue, JsValue> {
+    use base64::{Engine, engine::general_purpose::STANDARD};
+
+    let abi: CircuitAbi = serde_json::from_str(abi_json)
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse ABI: {}", e)))?;
+
+    let inputs_bytes = STANDARD.decode(public_inputs_gnark_b64)
+        .map_err(|e| JsValue::from_str(&format!("Invalid public inputs base64: {}", e)))?;
+
+    let public_inputs = groth16::public_inputs_from_gnark_bytes(&inputs_bytes)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    let decoded = abi::decode_public_inputs(&abi, &public_inputs)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    to_js_object(&decoded)
+}
+
+/// Parse a JSON array of public inputs as hex or decimal strings
+fn parse_public_inputs_json(public_inputs_json: &str) -> Result<Vec<ark_bn254::Fr>, JsValue> {
+    let inputs: Vec<String> = serde_json::from_str(public_inputs_json)
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse public inputs: {}", e)))?;
+
+    let mut public_inputs = Vec::new();
+    for (i, input) in inputs.iter().enumerate() {
+        let fr = parse_field_element(input)
+            .map_err(|e| JsValue::from_str(&format!("Public input {}: {}", i, e)))?;
+        public_inputs.push(fr);
+    }
+
+    Ok(public_inputs)
+}
+
+/// Apply the Poseidon2 permutation (BN254, Barretenberg parameters)
+///
+/// # Arguments
+/// * `inputs_json` - JSON array of 4 field element strings
+///
+/// # Returns
+/// * The permuted state as hex strings
+#[wasm_bindgen]
+pub fn poseidon2_permutation(inputs_json: &str) -> Result<Vec<String>, JsValue> {
+    let inputs = parse_public_inputs_json(inputs_json)?;
+    let outputs = gadgets::poseidon2::permutation(&inputs)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    Ok(outputs.iter().map(fr_to_hex).collect())
+}
+
+/// Hash field elements with Noir's `Poseidon2::hash`
+///
+/// # Arguments
+/// * `inputs_json` - JSON array of field element strings
+///
+/// # Returns
+/// * The hash as a hex string
+#[wasm_bindgen]
+pub fn poseidon2_hash(inputs_json: &str) -> Result<String, JsValue> {
+    let inputs = parse_public_inputs_json(inputs_json)?;
+    let hash = gadgets::poseidon2::hash(&inputs)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    Ok(fr_to_hex(&hash))
+}
+
+/// Format a field element as a 0x-prefixed, 32-byte hex string
+fn fr_to_hex(fr: &ark_bn254::Fr) -> String {
+    format!("0x{}", hex::encode(gnark_compat::fr_to_be_bytes(fr)))
+}
+
+/// Check an ACIR program for soundness gaps
+///
+/// # Arguments
+/// * `acir_json` - JSON string of the ACIR program
+///
+/// # Returns
+/// * List of warnings, each with its `kind`, `message`, the `witness` it
+///   leaves free and its ACIR `function` and `opcode` where known, and, if
+///   the program has debug symbols, the Noir `call_stack`, innermost first
+#[wasm_bindgen]
+pub fn lint_circuit(acir_json: &str) -> Result<JsValue, JsValue> {
+    let program: AcirProgram = serde_json::from_str(acir_json)
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse ACIR: {}", e)))?;
+
+    let r1cs = acir_to_r1cs(&program)
+        .map_err(|e| located_error(&program, &e))?;
+    let debug = program.debug_info()
+        .map_err(|e| JsValue::from_str(&e.to_string()))?
+        .unwrap_or_default();
+
+    #[derive(Serialize)]
+    struct Warning {
+        kind: &'static str,
+        message: String,
+        witness: Option<u32>,
+        function: Option<u32>,
+        opcode: Option<usize>,
+        call_stack: Vec<debug_info::SourceLocation>,
+    }
+
+    let warnings: Vec<Warning> = linter::lint(&program, &r1cs)
+        .iter()
+        .map(|warning| Warning {
+            kind: warning.kind(),
+            message: warning.to_string(),
+            witness: warning.witness(),
+            function: warning.origin().map(|o| o.function),
+            opcode: warning.origin().map(|o| o.opcode),
+            call_stack: warning.origin().map(|o| debug.call_stack(o)).unwrap_or_default(),
+        })
+        .collect();
+
+    to_js_object(&warnings)
+}
+
 /// Convert ACIR JSON to R1CS information (for debugging)
 #[wasm_bindgen]
 pub fn acir_to_r1cs_info(acir_json: &str) -> Result<JsValue, JsValue> {
     let program: AcirProgram = serde_json::from_str(acir_json)
         .map_err(|e| JsValue::from_str(&format!("Failed to parse ACIR: {}", e)))?;
 
-    let r1cs = acir_to_r1cs(&program)
-        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let unoptimized = acir_to_r1cs(&program)
+        .map_err(|e| located_error(&program, &e))?;
+    let r1cs = optimizer::optimize(&unoptimized);
 
     #[derive(Serialize)]
     struct R1csInfo {
         num_witnesses: usize,
         num_constraints: usize,
+        /// Constraints before optimization
+        num_unoptimized_constraints: usize,
+        num_public_inputs: usize,
         public_inputs: Vec<u32>,
         private_inputs: Vec<u32>,
         return_values: Vec<u32>,
+        /// Constraints added by each black box function (e.g. SHA256)
+        black_box_constraints: BTreeMap<String, usize>,
     }
 
     let info = R1csInfo {
         num_witnesses: r1cs.num_witnesses,
         num_constraints: r1cs.constraints.len(),
+        num_unoptimized_constraints: unoptimized.constraints.len(),
+        num_public_inputs: r1cs.public_witnesses().len(),
         public_inputs: r1cs.public_inputs,
         private_inputs: r1cs.private_inputs,
         return_values: r1cs.return_values,
+        black_box_constraints: r1cs.black_box_constraints,
     };
 
-    serde_wasm_bindgen::to_value(&info)
-        .map_err(|e| JsValue::from_str(&e.to_string()))
+    to_js_object(&info)
 }
 
 /// Get library version
@@ -345,6 +702,9 @@ pub struct JsR1csDefinition {
     pub public_inputs: Vec<u32>,
     /// Private input witness indices
     pub private_inputs: Vec<u32>,
+    /// Return value witness indices (public, after the public inputs)
+    #[serde(default)]
+    pub return_values: Vec<u32>,
     /// Constraints
     pub constraints: Vec<JsR1csConstraint>,
 }
@@ -397,7 +757,7 @@ pub fn setup_from_r1cs(r1cs_json: &str) -> Result<JsValue, JsValue> {
 /// # Arguments
 /// * `proving_key_b64` - Base64-encoded proving key from setup
 /// * `r1cs_json` - JSON string of R1CS definition
-/// * `witness_json` - JSON object mapping witness indices to hex values
+/// * `witness_json` - JSON object mapping witness indices to field elements
 ///
 /// # Returns
 /// * `JsProofResult` with proof and public inputs
@@ -425,20 +785,7 @@ pub fn prove_from_r1cs(
         .map_err(|e| JsValue::from_str(&e.to_string()))?;
 
     // Parse witness
-    let witness_map: HashMap<String, String> = serde_json::from_str(witness_json)
-        .map_err(|e| JsValue::from_str(&format!("Failed to parse witness: {}", e)))?;
-
-    let mut witness = WitnessMap::new();
-    // Always set w_0 = 1
-    witness.insert(0, ark_bn254::Fr::from(1u64));
-
-    for (key, value) in witness_map {
-        let idx: u32 = key.parse()
-            .map_err(|_| JsValue::from_str(&format!("Invalid witness index: {}", key)))?;
-        let fr = parse_field_element(&value)
-            .map_err(|e| JsValue::from_str(&e.to_string()))?;
-        witness.insert(idx, fr);
-    }
+    let witness = parse_witness_json(witness_json)?;
 
     // Generate proof
     let proof_result = groth16::prove(&proving_key, &r1cs, witness)
@@ -496,8 +843,14 @@ fn convert_js_r1cs(js_r1cs: &JsR1csDefinition) -> Result<acir_to_r1cs::AcirR1cs,
         num_witnesses: js_r1cs.num_witnesses,
         public_inputs: js_r1cs.public_inputs.clone(),
         private_inputs: js_r1cs.private_inputs.clone(),
-        return_values: js_r1cs.public_inputs.clone(), // Return values = public outputs
+        return_values: js_r1cs.return_values.clone(),
         constraints,
+        aux_witnesses: Vec::new(),
+        calls: Vec::new(),
+        black_box_constraints: Default::default(),
+        origins: Vec::new(),
+        assert_messages: Default::default(),
+        renumbering: None,
     })
 }
 
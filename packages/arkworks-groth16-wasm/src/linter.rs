@@ -0,0 +1,347 @@
+//! Soundness linter.
+//!
+//! Conversion can leave witnesses free to take any value: a Brillig output
+//! is only as constrained as the opcodes that use it, some opcodes convert
+//! to no constraints, and synthesis drops every reference to a witness at
+//! or beyond `num_witnesses`. `lint` looks for these gaps in a converted
+//! circuit, and `ensure_sound` refuses a circuit that has any.
+
+use ark_bn254::Fr;
+use ark_ff::PrimeField;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+use crate::acir_to_r1cs::{AcirR1cs, OpcodeOrigin};
+use crate::acir_types::{AcirProgram, BlackBoxFuncCall, BrilligOutputs, Opcode};
+use crate::error::ArkworksError;
+
+/// Gaps listed in full by `summary`; the rest are counted
+const MAX_LISTED: usize = 10;
+
+/// A soundness gap found by `lint`
+///
+/// Witnesses are numbered like the constraints, so the witnesses of an
+/// inlined call have their R1CS indices.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LintWarning {
+    /// A witness at or beyond `num_witnesses`, which synthesis drops
+    OutOfRangeWitness {
+        witness: u32,
+        origin: Option<OpcodeOrigin>,
+    },
+    /// An opcode that converted to no constraints
+    SkippedOpcode { origin: OpcodeOrigin, kind: String },
+    /// A Brillig output that appears in no constraint
+    UnconstrainedBrilligOutput { witness: u32, origin: OpcodeOrigin },
+    /// A witness that appears in no constraint
+    UnusedWitness { witness: u32 },
+}
+
+impl LintWarning {
+    /// Short name of the kind of gap
+    pub fn kind(&self) -> &'static str {
+        match self {
+            LintWarning::OutOfRangeWitness { .. } => "out_of_range_witness",
+            LintWarning::SkippedOpcode { .. } => "skipped_opcode",
+            LintWarning::UnconstrainedBrilligOutput { .. } => "unconstrained_brillig_output",
+            LintWarning::UnusedWitness { .. } => "unused_witness",
+        }
+    }
+
+    /// Witness the gap leaves free, if it concerns one
+    pub fn witness(&self) -> Option<u32> {
+        match self {
+            LintWarning::OutOfRangeWitness { witness, .. }
+            | LintWarning::UnconstrainedBrilligOutput { witness, .. }
+            | LintWarning::UnusedWitness { witness } => Some(*witness),
+            LintWarning::SkippedOpcode { .. } => None,
+        }
+    }
+
+    /// ACIR opcode the gap arises at, if known
+    pub fn origin(&self) -> Option<OpcodeOrigin> {
+        match self {
+            LintWarning::OutOfRangeWitness { origin, .. } => *origin,
+            LintWarning::SkippedOpcode { origin, .. }
+            | LintWarning::UnconstrainedBrilligOutput { origin, .. } => Some(*origin),
+            LintWarning::UnusedWitness { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LintWarning::OutOfRangeWitness { witness, .. } => {
+                write!(f, "Witness {} is beyond the circuit's witnesses", witness)?
+            }
+            LintWarning::SkippedOpcode { kind, .. } => write!(f, "{} adds no constraints", kind)?,
+            LintWarning::UnconstrainedBrilligOutput { witness, .. } => {
+                write!(f, "Brillig output witness {} is unconstrained", witness)?
+            }
+            LintWarning::UnusedWitness { witness } => {
+                write!(f, "Witness {} appears in no constraint", witness)?
+            }
+        }
+        if let Some(origin) = self.origin() {
+            write!(f, " (opcode {} of function {})", origin.opcode, origin.function)?;
+        }
+        Ok(())
+    }
+}
+
+/// Look for soundness gaps in `r1cs`, the unoptimized conversion of
+/// `program`
+///
+/// Out-of-range witnesses are listed first, then skipped opcodes and
+/// unconstrained Brillig outputs in opcode order, then unused witnesses.
+/// Each witness is reported once.
+pub fn lint(program: &AcirProgram, r1cs: &AcirR1cs) -> Vec<LintWarning> {
+    let num_witnesses = r1cs.num_witnesses as u32;
+    let mut warnings = Vec::new();
+    let mut reported = BTreeSet::new();
+
+    let mut constrained = BTreeSet::new();
+    for (i, constraint) in r1cs.constraints.iter().enumerate() {
+        for &(_, witness) in constraint.a.iter().chain(&constraint.b).chain(&constraint.c) {
+            if witness >= num_witnesses && reported.insert(witness) {
+                let origin = r1cs.origins.get(i).copied();
+                warnings.push(LintWarning::OutOfRangeWitness { witness, origin });
+            }
+            constrained.insert(witness);
+        }
+    }
+    for &witness in r1cs.public_witnesses().iter().chain(&r1cs.private_inputs) {
+        if witness >= num_witnesses && reported.insert(witness) {
+            warnings.push(LintWarning::OutOfRangeWitness { witness, origin: None });
+        }
+    }
+
+    // Walk main and every inlined call, with where its witnesses live.
+    // Skipped opcodes are per function, so each is checked once.
+    let converted: BTreeSet<OpcodeOrigin> = r1cs.origins.iter().copied().collect();
+    let frames = std::iter::once((0, None))
+        .chain(r1cs.calls.iter().map(|call| (call.function_id, Some(&call.witnesses))));
+    let mut checked = BTreeSet::new();
+    for (function, witnesses) in frames {
+        let Some(circuit) = program.functions.get(function as usize) else {
+            continue;
+        };
+        let first = checked.insert(function);
+        for (opcode, op) in circuit.opcodes.iter().enumerate() {
+            let origin = OpcodeOrigin { function, opcode };
+            match op {
+                Opcode::BrilligCall(call) => {
+                    let outputs = call.outputs.iter().flat_map(|output| match output {
+                        BrilligOutputs::Simple(witness) => std::slice::from_ref(witness),
+                        BrilligOutputs::Array(witnesses) => witnesses.as_slice(),
+                    });
+                    for output in outputs {
+                        let Some(witness) = relocate(*output, witnesses) else {
+                            continue;
+                        };
+                        if !constrained.contains(&witness) && reported.insert(witness) {
+                            warnings.push(if witness >= num_witnesses {
+                                LintWarning::OutOfRangeWitness { witness, origin: Some(origin) }
+                            } else {
+                                LintWarning::UnconstrainedBrilligOutput { witness, origin }
+                            });
+                        }
+                    }
+                }
+                _ if first && always_constrains(op) && !converted.contains(&origin) => {
+                    warnings.push(LintWarning::SkippedOpcode { origin, kind: opcode_kind(op) });
+                }
+                _ => {}
+            }
+        }
+    }
+
+    for witness in 1..num_witnesses {
+        if !constrained.contains(&witness) && reported.insert(witness) {
+            warnings.push(LintWarning::UnusedWitness { witness });
+        }
+    }
+    warnings
+}
+
+/// Fail if `lint` finds any soundness gap
+pub fn ensure_sound(program: &AcirProgram, r1cs: &AcirR1cs) -> Result<(), ArkworksError> {
+    let warnings = lint(program, r1cs);
+    if warnings.is_empty() {
+        return Ok(());
+    }
+    Err(ArkworksError::UnderConstrained(warnings))
+}
+
+/// One-line description of a list of gaps, for error messages
+pub fn summary(warnings: &[LintWarning]) -> String {
+    let mut listed: Vec<String> = warnings.iter().take(MAX_LISTED).map(|w| w.to_string()).collect();
+    if warnings.len() > MAX_LISTED {
+        listed.push(format!("and {} more", warnings.len() - MAX_LISTED));
+    }
+    format!("{} soundness gap(s): {}", warnings.len(), listed.join("; "))
+}
+
+/// R1CS index of a witness of an inlined call, or of main if `witnesses`
+/// is None
+fn relocate(witness: u32, witnesses: Option<&HashMap<u32, u32>>) -> Option<u32> {
+    match witnesses {
+        None => Some(witness),
+        Some(map) => map.get(&witness).copied(),
+    }
+}
+
+/// Whether an opcode converts to at least one constraint
+///
+/// Memory initialization, writes to a constant index and calls only move
+/// witnesses around, and Brillig calls are checked through their outputs.
+/// A range check of a constant is checked when parsed, and one as wide as
+/// the field holds for every element.
+fn always_constrains(opcode: &Opcode) -> bool {
+    match opcode {
+        Opcode::AssertZero { .. } => true,
+        Opcode::BlackBoxFuncCall(BlackBoxFuncCall::Range { input }) => {
+            input.to_witness().is_some() && input.num_bits < Fr::MODULUS_BIT_SIZE
+        }
+        Opcode::BlackBoxFuncCall(_) => true,
+        Opcode::MemoryOp(op) => op.op.inner != 1,
+        Opcode::MemoryInit(_) | Opcode::BrilligCall(_) | Opcode::Call(_) => false,
+    }
+}
+
+fn opcode_kind(opcode: &Opcode) -> String {
+    match opcode {
+        Opcode::AssertZero { .. } => "AssertZero".to_string(),
+        Opcode::BlackBoxFuncCall(bb) => format!("Black box {}", bb.name()),
+        Opcode::MemoryOp(_) => "MemoryOp".to_string(),
+        Opcode::MemoryInit(_) => "MemoryInit".to_string(),
+        Opcode::BrilligCall(_) => "BrilligCall".to_string(),
+        Opcode::Call(_) => "Call".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acir_to_r1cs::acir_to_r1cs;
+    use serde_json::json;
+
+    fn minus_one() -> String {
+        format!("0x{}", hex::encode(crate::gnark_compat::fr_to_be_bytes(&-Fr::from(1u64))))
+    }
+
+    fn brillig_call(output: u32) -> serde_json::Value {
+        json!({
+            "type": "BrilligCall", "id": 0,
+            "inputs": [{ "Single": { "linear_combinations": [["0x1", 1]], "q_c": "0x0" } }],
+            "outputs": [{ "Simple": output }]
+        })
+    }
+
+    /// main: w2 = 1 / w1 from Brillig, checked by w1 * w2 = 1; w3 is a
+    /// Brillig output nothing checks; w4 is an unused input; w5 = f(w1)
+    /// f: w2 from Brillig, returned unchecked
+    fn program() -> AcirProgram {
+        serde_json::from_value(json!({
+            "functions": [
+                {
+                    "current_witness_index": 5,
+                    "opcodes": [
+                        brillig_call(2),
+                        { "type": "AssertZero", "value": { "mul_terms": [["0x1", 1, 2]], "q_c": minus_one() } },
+                        brillig_call(3),
+                        { "type": "BlackBoxFuncCall", "name": "RANGE", "input": { "witness": 1, "num_bits": 8 } },
+                        {
+                            "type": "Call", "id": 1,
+                            "inputs": [{ "linear_combinations": [["0x1", 1]], "q_c": "0x0" }],
+                            "outputs": [5]
+                        }
+                    ],
+                    "private_parameters": [1, 4],
+                    "public_parameters": { "witnesses": [] },
+                    "return_values": { "witnesses": [5] }
+                },
+                {
+                    "current_witness_index": 3,
+                    "opcodes": [
+                        brillig_call(2),
+                        { "type": "AssertZero", "value": { "linear_combinations": [["0x1", 2], [minus_one(), 3]], "q_c": "0x0" } }
+                    ],
+                    "private_parameters": [1],
+                    "public_parameters": { "witnesses": [] },
+                    "return_values": { "witnesses": [3] }
+                }
+            ],
+            "unconstrained_functions": []
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_lint_finds_free_witnesses() {
+        let program = program();
+        let r1cs = acir_to_r1cs(&program).unwrap();
+        let warnings = lint(&program, &r1cs);
+
+        let main = |opcode| OpcodeOrigin { function: 0, opcode };
+        assert_eq!(warnings[0], LintWarning::UnconstrainedBrilligOutput { witness: 3, origin: main(2) });
+        assert_eq!(warnings[1], LintWarning::UnusedWitness { witness: 4 });
+        assert_eq!(warnings.len(), 2, "{:?}", warnings);
+        assert_eq!(
+            warnings[0].to_string(),
+            "Brillig output witness 3 is unconstrained (opcode 2 of function 0)"
+        );
+
+        // Dropping the range check's constraints skips its opcode, and a
+        // reference past the witnesses is reported at its constraint
+        let mut gappy = r1cs.clone();
+        let (constraints, origins) = r1cs
+            .constraints
+            .iter()
+            .zip(&r1cs.origins)
+            .filter(|(_, origin)| **origin != main(3))
+            .map(|(c, o)| (c.clone(), *o))
+            .unzip();
+        gappy.constraints = constraints;
+        gappy.origins = origins;
+        gappy.constraints[0].c.push((Fr::from(1u64), r1cs.num_witnesses as u32));
+
+        let warnings = lint(&program, &gappy);
+        assert_eq!(
+            warnings[0],
+            LintWarning::OutOfRangeWitness { witness: r1cs.num_witnesses as u32, origin: Some(main(1)) }
+        );
+        assert!(warnings.contains(&LintWarning::SkippedOpcode { origin: main(3), kind: "Black box RANGE".into() }));
+
+        let err = ensure_sound(&program, &gappy).unwrap_err();
+        assert!(matches!(&err, ArkworksError::UnderConstrained(w) if *w == warnings));
+        assert!(err.to_string().starts_with(&format!(
+            "Circuit is under-constrained: {} soundness gap(s): Witness {} is beyond",
+            warnings.len(),
+            r1cs.num_witnesses
+        )));
+    }
+
+    #[test]
+    fn test_lint_follows_inlined_calls() {
+        let program = program();
+        let r1cs = acir_to_r1cs(&program).unwrap();
+        let callee = r1cs.calls[0].witnesses[&2];
+
+        // f's Brillig output is only checked against its return value, so
+        // dropping that check frees it at its R1CS index
+        let mut unchecked = r1cs.clone();
+        let keep: Vec<bool> = r1cs.origins.iter().map(|o| o.function == 0).collect();
+        unchecked.constraints.retain({
+            let mut keep = keep.iter();
+            move |_| *keep.next().unwrap()
+        });
+        unchecked.origins.retain(|o| o.function == 0);
+
+        let warnings = lint(&program, &unchecked);
+        let f = |opcode| OpcodeOrigin { function: 1, opcode };
+        assert!(warnings.contains(&LintWarning::UnconstrainedBrilligOutput { witness: callee, origin: f(0) }));
+        assert!(warnings.contains(&LintWarning::SkippedOpcode { origin: f(1), kind: "AssertZero".into() }));
+    }
+}
@@ -0,0 +1,341 @@
+//! R1CS optimizer.
+//!
+//! Shrinks a converted circuit before setup. Linear constraints are solved
+//! for one of their witnesses, which is then substituted into the rest of
+//! the circuit; duplicate constraints are merged; and the witnesses nothing
+//! uses any more are dropped, the remaining ones being renumbered compactly.
+//!
+//! The result is deterministic, so `setup` and `prove` optimizing the same
+//! circuit independently agree on the constraint system.
+
+use ark_bn254::Fr;
+use ark_ff::{Field, One, Zero};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use crate::acir_to_r1cs::{AcirR1cs, OpcodeOrigin, R1csConstraint};
+
+/// Most terms one substitution may add to the constraints it rewrites;
+/// larger ones are skipped to keep the linear combinations sparse
+const MAX_FILL: usize = 1024;
+
+/// Linear combination keyed by witness index, without zero coefficients
+type Lc = BTreeMap<u32, Fr>;
+
+/// Constraint A * B = C being rewritten
+struct Row {
+    a: Lc,
+    b: Lc,
+    c: Lc,
+    origin: Option<OpcodeOrigin>,
+}
+
+impl Row {
+    fn new(constraint: &R1csConstraint, origin: Option<OpcodeOrigin>) -> Self {
+        Self {
+            a: collect(&constraint.a),
+            b: collect(&constraint.b),
+            c: collect(&constraint.c),
+            origin,
+        }
+    }
+
+    fn witnesses(&self) -> impl Iterator<Item = u32> + '_ {
+        self.a.keys().chain(self.b.keys()).chain(self.c.keys()).copied().filter(|&w| w != 0)
+    }
+
+    /// The constraint as a linear combination that must be zero, when A or
+    /// B is a constant
+    fn linear(&self) -> Option<Lc> {
+        let (k, other) = match (constant(&self.a), constant(&self.b)) {
+            (Some(k), _) => (k, &self.b),
+            (None, Some(k)) => (k, &self.a),
+            (None, None) => return None,
+        };
+        let mut lc = Lc::new();
+        for (&w, &coeff) in other {
+            add(&mut lc, w, k * coeff);
+        }
+        for (&w, &coeff) in &self.c {
+            add(&mut lc, w, -coeff);
+        }
+        Some(lc)
+    }
+
+    /// Replace `witness` by `value` in A, B and C
+    fn substitute(&mut self, witness: u32, value: &Lc) {
+        for lc in [&mut self.a, &mut self.b, &mut self.c] {
+            if let Some(coeff) = lc.remove(&witness) {
+                for (&w, &v) in value {
+                    add(lc, w, coeff * v);
+                }
+            }
+        }
+    }
+
+    /// Canonical form of the constraint, or `None` if it always holds
+    ///
+    /// Linear constraints become L * 1 = 0 with L scaled to a leading
+    /// coefficient of 1, and A and B are ordered, so that equivalent
+    /// constraints compare equal.
+    fn into_constraint(self) -> Option<R1csConstraint> {
+        let terms = |lc: &Lc| lc.iter().map(|(&w, &coeff)| (coeff, w)).collect::<Vec<_>>();
+        if let Some(lc) = self.linear() {
+            let (_, &lead) = lc.iter().next()?;
+            let scale = lead.inverse().expect("coefficients are non-zero");
+            return Some(R1csConstraint {
+                a: lc.iter().map(|(&w, &coeff)| (coeff * scale, w)).collect(),
+                b: vec![(Fr::one(), 0)],
+                c: vec![],
+            });
+        }
+        let (a, b) = (terms(&self.a), terms(&self.b));
+        let (a, b) = if a <= b { (a, b) } else { (b, a) };
+        Some(R1csConstraint { a, b, c: terms(&self.c) })
+    }
+}
+
+/// Optimize a converted circuit
+///
+/// Public inputs and return values are never substituted out or dropped.
+/// The new numbering is recorded in `renumbering`, and auxiliary
+/// computations and calls are kept as they are, so witnesses solved for
+/// the original circuit are assigned with `AcirR1cs::assign_witness`.
+/// `black_box_constraints` still counts the constraints as converted.
+pub fn optimize(r1cs: &AcirR1cs) -> AcirR1cs {
+    let public: HashSet<u32> = r1cs.public_witnesses().into_iter().collect();
+    let mut rows: Vec<Option<Row>> = r1cs
+        .constraints
+        .iter()
+        .enumerate()
+        .map(|(i, c)| Some(Row::new(c, r1cs.origins.get(i).copied())))
+        .collect();
+
+    // Constraints each witness may appear in; a superset once terms cancel
+    let mut occurrences: HashMap<u32, BTreeSet<usize>> = HashMap::new();
+    for (i, row) in rows.iter().enumerate() {
+        for w in row.as_ref().into_iter().flat_map(Row::witnesses) {
+            occurrences.entry(w).or_default().insert(i);
+        }
+    }
+
+    for i in 0..rows.len() {
+        let Some(linear) = rows[i].as_ref().and_then(Row::linear) else {
+            continue;
+        };
+
+        // Solve for the private witness appearing in the fewest constraints
+        let pivot = linear
+            .keys()
+            .copied()
+            .filter(|&w| w != 0 && !public.contains(&w))
+            .min_by_key(|w| (occurrences[w].len(), *w));
+        let pivot = match pivot {
+            Some(pivot) => pivot,
+            // Always true: the constraint can go as it is
+            None if linear.is_empty() => {
+                remove_row(&mut rows, &mut occurrences, i);
+                continue;
+            }
+            None => continue,
+        };
+        if (occurrences[&pivot].len() - 1) * (linear.len() - 1) > MAX_FILL {
+            continue;
+        }
+
+        // pivot = -(L - c * pivot) / c
+        let mut value = linear;
+        let coeff = value.remove(&pivot).expect("pivot is a term of the constraint");
+        let scale = -coeff.inverse().expect("coefficients are non-zero");
+        for v in value.values_mut() {
+            *v *= scale;
+        }
+
+        remove_row(&mut rows, &mut occurrences, i);
+        for j in occurrences.remove(&pivot).unwrap_or_default() {
+            let Some(row) = rows[j].as_mut() else {
+                continue;
+            };
+            row.substitute(pivot, &value);
+            for &w in value.keys().filter(|&&w| w != 0) {
+                occurrences.entry(w).or_default().insert(j);
+            }
+        }
+    }
+
+    // Merge duplicates, keeping the first of each and its origin
+    let mut seen = HashSet::new();
+    let (constraints, origins): (Vec<R1csConstraint>, Vec<Option<OpcodeOrigin>>) = rows
+        .into_iter()
+        .flatten()
+        .filter_map(|row| {
+            let origin = row.origin;
+            row.into_constraint().map(|constraint| (constraint, origin))
+        })
+        .filter(|(constraint, _)| seen.insert(constraint.clone()))
+        .unzip();
+
+    // Number the witnesses still in use compactly, in their original order
+    let mut used: BTreeSet<u32> = public.iter().copied().collect();
+    for constraint in &constraints {
+        for lc in [&constraint.a, &constraint.b, &constraint.c] {
+            used.extend(lc.iter().map(|&(_, w)| w).filter(|&w| w != 0));
+        }
+    }
+    let original: Vec<u32> = std::iter::once(0).chain(used.iter().copied()).collect();
+    let map: HashMap<u32, u32> = original.iter().enumerate().map(|(new, &old)| (old, new as u32)).collect();
+    let renumber = |witnesses: &[u32]| witnesses.iter().map(|w| map[w]).collect::<Vec<_>>();
+
+    let private_inputs: Vec<u32> = r1cs.private_inputs.iter().copied().filter(|w| map.contains_key(w)).collect();
+    let renumbering = match &r1cs.renumbering {
+        Some(previous) => original.iter().map(|&w| previous[w as usize]).collect(),
+        None => original.clone(),
+    };
+
+    AcirR1cs {
+        num_witnesses: original.len(),
+        public_inputs: renumber(&r1cs.public_inputs),
+        private_inputs: renumber(&private_inputs),
+        return_values: renumber(&r1cs.return_values),
+        constraints: constraints.iter().map(|c| c.relocate(&map)).collect(),
+        aux_witnesses: r1cs.aux_witnesses.clone(),
+        calls: r1cs.calls.clone(),
+        black_box_constraints: r1cs.black_box_constraints.clone(),
+        origins: origins.into_iter().flatten().collect(),
+        assert_messages: r1cs.assert_messages.clone(),
+        renumbering: Some(renumbering),
+    }
+}
+
+/// Drop a row and its occurrences
+fn remove_row(rows: &mut [Option<Row>], occurrences: &mut HashMap<u32, BTreeSet<usize>>, i: usize) {
+    if let Some(row) = rows[i].take() {
+        for w in row.witnesses() {
+            if let Some(rows) = occurrences.get_mut(&w) {
+                rows.remove(&i);
+            }
+        }
+    }
+}
+
+/// Merge the terms of a linear combination, dropping zero coefficients
+fn collect(terms: &[(Fr, u32)]) -> Lc {
+    let mut lc = Lc::new();
+    for &(coeff, w) in terms {
+        add(&mut lc, w, coeff);
+    }
+    lc
+}
+
+/// Add coeff * w to a linear combination
+fn add(lc: &mut Lc, w: u32, coeff: Fr) {
+    let sum = lc.get(&w).copied().unwrap_or_default() + coeff;
+    if sum.is_zero() {
+        lc.remove(&w);
+    } else {
+        lc.insert(w, sum);
+    }
+}
+
+/// Value of a linear combination of w_0 alone
+fn constant(lc: &Lc) -> Option<Fr> {
+    lc.keys()
+        .all(|&w| w == 0)
+        .then(|| lc.get(&0).copied().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acir_to_r1cs::{acir_to_r1cs, WitnessMap};
+    use crate::acir_types::AcirProgram;
+    use crate::groth16;
+    use crate::solver::solve_witness;
+    use serde_json::json;
+
+    fn minus_one() -> String {
+        format!("0x{}", hex::encode(crate::gnark_compat::fr_to_be_bytes(&-Fr::one())))
+    }
+
+    /// w1, w2 private, w3 public:
+    /// w4 = w1 + w2; w5 = 2 * w4; w6 = w5 * w5; w3 = w6 + w1; the product
+    /// again as w2 + w1 (a duplicate); and RANGE(w1, 8)
+    fn program() -> AcirProgram {
+        serde_json::from_value(json!({
+            "functions": [{
+                "current_witness_index": 6,
+                "opcodes": [
+                    { "type": "AssertZero", "value": { "linear_combinations": [["0x1", 1], ["0x1", 2], [minus_one(), 4]], "q_c": "0x0" } },
+                    { "type": "AssertZero", "value": { "linear_combinations": [["0x2", 4], [minus_one(), 5]], "q_c": "0x0" } },
+                    { "type": "AssertZero", "value": { "mul_terms": [["0x1", 5, 5]], "linear_combinations": [[minus_one(), 6]], "q_c": "0x0" } },
+                    { "type": "AssertZero", "value": { "linear_combinations": [["0x1", 6], ["0x1", 1], [minus_one(), 3]], "q_c": "0x0" } },
+                    { "type": "AssertZero", "value": { "linear_combinations": [["0x1", 2], ["0x1", 1], [minus_one(), 4]], "q_c": "0x0" } },
+                    { "type": "BlackBoxFuncCall", "name": "RANGE", "input": { "witness": 1, "num_bits": 8 } }
+                ],
+                "private_parameters": [1, 2],
+                "public_parameters": { "witnesses": [3] },
+                "return_values": { "witnesses": [] }
+            }],
+            "unconstrained_functions": []
+        }))
+        .unwrap()
+    }
+
+    fn solve(program: &AcirProgram, r1cs: &AcirR1cs, w1: u64, w2: u64) -> WitnessMap {
+        let w3 = (2 * (w1 + w2)).pow(2) + w1;
+        let initial: WitnessMap = [(1, Fr::from(w1)), (2, Fr::from(w2)), (3, Fr::from(w3))].into_iter().collect();
+        solve_witness(program, &initial).unwrap().into_r1cs_witness(r1cs).unwrap()
+    }
+
+    fn satisfied(r1cs: &AcirR1cs, witness: &WitnessMap) -> bool {
+        r1cs.constraints.iter().all(|c| c.is_satisfied(witness).unwrap())
+    }
+
+    #[test]
+    fn test_optimize_substitutes_and_merges() {
+        let program = program();
+        let r1cs = acir_to_r1cs(&program).unwrap();
+        let optimized = optimize(&r1cs);
+
+        // The linear constraints all go, the duplicate with them, leaving
+        // 4 (w1 + w2)^2 = w3 - w1 with w1 recomposed from its bits, and the
+        // eight bit checks
+        assert_eq!(r1cs.constraints.len(), 14);
+        assert_eq!(optimized.constraints.len(), 9);
+        assert_eq!(optimized.num_witnesses, 11);
+        let original = optimized.renumbering.as_ref().unwrap();
+        assert_eq!(original[optimized.public_inputs[0] as usize], 3);
+        for constraint in &optimized.constraints {
+            for lc in [&constraint.a, &constraint.b, &constraint.c] {
+                assert!(lc.iter().all(|(coeff, w)| !coeff.is_zero() && (*w as usize) < optimized.num_witnesses));
+            }
+        }
+
+        // Witnesses solved for the original circuit line up after renumbering
+        let witness = optimized.assign_witness(solve(&program, &r1cs, 3, 4)).unwrap();
+        assert_eq!(witness.len(), optimized.num_witnesses - 1);
+        assert!(satisfied(&optimized, &witness));
+
+        // A wrong public output is still rejected
+        let mut wrong = solve(&program, &r1cs, 3, 4);
+        *wrong.get_mut(&3).unwrap() += Fr::one();
+        let wrong = optimized.assign_witness(wrong).unwrap();
+        assert!(!satisfied(&optimized, &wrong));
+
+        // Optimizing again changes nothing but keeps the original numbering
+        let twice = optimize(&optimized);
+        assert_eq!(twice.constraints, optimized.constraints);
+        assert_eq!(twice.renumbering, optimized.renumbering);
+    }
+
+    #[test]
+    fn test_optimized_circuit_proves() {
+        let program = program();
+        let r1cs = optimize(&acir_to_r1cs(&program).unwrap());
+        let setup = groth16::setup(&r1cs).unwrap();
+
+        let witness = solve(&program, &r1cs, 5, 6);
+        let proof = groth16::prove(&setup.proving_key, &r1cs, witness).unwrap();
+        assert_eq!(proof.public_inputs, vec![Fr::from(489u64)]);
+        assert!(groth16::verify(&setup.verifying_key, &proof.proof, &proof.public_inputs).unwrap());
+    }
+}
@@ -0,0 +1,869 @@
+//! ACIR witness solver.
+//!
+//! Computes the full witness map from the circuit inputs, the way ACVM does:
+//! opcodes are walked in order and each one either fills in the witnesses it
+//! determines or checks them when they are already known.
+
+use ark_bn254::Fr;
+use ark_ff::{BigInteger, PrimeField, Zero};
+use num_bigint::BigUint;
+use std::collections::HashMap;
+
+use crate::acir_to_r1cs::{parse_field_element, AcirR1cs, WitnessMap};
+use crate::acir_types::{
+    AcirCall, AcirProgram, BlackBoxFuncCall, BrilligCall, BrilligOutputs, ConstantOrWitness,
+    Expression, FunctionInput, MemoryOp, Opcode,
+};
+use crate::brillig::solve_brillig_call;
+use crate::error::ArkworksError;
+use crate::gadgets::bigint::{self, BigIntTable};
+use crate::gadgets::{
+    blake2s, blake3, ecdsa, embedded_curve, keccak, pedersen, poseidon2, schnorr, sha256,
+};
+
+/// Witness values for a solved program
+#[derive(Clone, Debug, Default)]
+pub struct SolvedWitness {
+    /// ACIR witnesses of the entry function
+    pub witness: WitnessMap,
+    /// ACIR witnesses of every executed call, in the order of `AcirR1cs.calls`
+    ///
+    /// Calls skipped by their predicate leave an empty map.
+    pub calls: Vec<WitnessMap>,
+}
+
+impl SolvedWitness {
+    /// Merge the call frames into a witness map for the converted R1CS
+    pub fn into_r1cs_witness(self, r1cs: &AcirR1cs) -> Result<WitnessMap, ArkworksError> {
+        let mut witness = self.witness;
+        for (call, frame) in self.calls.iter().enumerate() {
+            r1cs.insert_call_witnesses(call, frame, &mut witness)?;
+        }
+        Ok(witness)
+    }
+}
+
+/// Solve every witness of the entry function from its initial witnesses
+///
+/// `initial` usually holds just the inputs, but may contain any witness;
+/// known values are checked against the opcodes instead of recomputed.
+/// Brillig calls whose outputs are all known are not executed, so witness
+/// maps from noir_js (which handles oracles) are accepted as they are.
+pub fn solve_witness(
+    program: &AcirProgram,
+    initial: &WitnessMap,
+) -> Result<SolvedWitness, ArkworksError> {
+    let mut solver = Solver {
+        program,
+        calls: Vec::new(),
+        call_stack: vec![0],
+    };
+    let mut witness = initial.clone();
+    solver.solve_function(0, &mut witness)?;
+
+    Ok(SolvedWitness {
+        witness,
+        calls: solver.calls,
+    })
+}
+
+/// Location of the opcode being solved, for error reporting
+#[derive(Clone, Copy)]
+struct Position {
+    function: u32,
+    opcode: usize,
+}
+
+impl Position {
+    fn error(self, reason: impl Into<String>) -> ArkworksError {
+        ArkworksError::UnsolvableOpcode {
+            function: self.function,
+            opcode: self.opcode,
+            reason: reason.into(),
+        }
+    }
+}
+
+struct Solver<'a> {
+    program: &'a AcirProgram,
+    /// Call frames solved so far, see `SolvedWitness.calls`
+    calls: Vec<WitnessMap>,
+    /// Functions currently executing, to reject recursion
+    call_stack: Vec<u32>,
+}
+
+impl Solver<'_> {
+    fn solve_function(&mut self, id: u32, witness: &mut WitnessMap) -> Result<(), ArkworksError> {
+        let circuit = self
+            .program
+            .functions
+            .get(id as usize)
+            .ok_or_else(|| ArkworksError::ParseError(format!("No function {} in ACIR", id)))?;
+
+        // w_0 is the constant 1 throughout the crate
+        witness.insert(0, Fr::from(1u64));
+        let mut memory_blocks: HashMap<u32, Vec<Fr>> = HashMap::new();
+        let mut bigints = BigIntTable::default();
+
+        for (opcode_index, opcode) in circuit.opcodes.iter().enumerate() {
+            let at = Position {
+                function: id,
+                opcode: opcode_index,
+            };
+            match opcode {
+                Opcode::AssertZero { value } => {
+                    solve_expression(value, Fr::zero(), witness, at)?;
+                }
+                Opcode::BlackBoxFuncCall(bb) => {
+                    solve_black_box(bb, &mut bigints, witness, at)?;
+                }
+                Opcode::MemoryInit(init) => {
+                    let values = init
+                        .init
+                        .iter()
+                        .map(|&w| known(witness, w, at))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    memory_blocks.insert(init.block_id, values);
+                }
+                Opcode::MemoryOp(op) => {
+                    solve_memory_op(op, &mut memory_blocks, witness, at)?;
+                }
+                Opcode::BrilligCall(call) => {
+                    self.solve_brillig(call, &memory_blocks, witness, at)?;
+                }
+                Opcode::Call(call) => {
+                    self.solve_call(call, witness, at)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn solve_brillig(
+        &self,
+        call: &BrilligCall,
+        memory_blocks: &HashMap<u32, Vec<Fr>>,
+        witness: &mut WitnessMap,
+        at: Position,
+    ) -> Result<(), ArkworksError> {
+        let all_known = call.outputs.iter().all(|output| match output {
+            BrilligOutputs::Simple(w) => witness.contains_key(w),
+            BrilligOutputs::Array(ws) => ws.iter().all(|w| witness.contains_key(w)),
+        });
+        if all_known {
+            return Ok(());
+        }
+
+        solve_brillig_call(call, self.program, witness, memory_blocks).map_err(|e| match e {
+            ArkworksError::MissingWitness(w) => at.error(format!("witness {} is unknown", w)),
+            e => at.error(e.to_string()),
+        })
+    }
+
+    /// Execute an ACIR call, recording its frame as `acir_to_r1cs` inlines it
+    fn solve_call(
+        &mut self,
+        call: &AcirCall,
+        witness: &mut WitnessMap,
+        at: Position,
+    ) -> Result<(), ArkworksError> {
+        if self.call_stack.contains(&call.id) {
+            return Err(ArkworksError::UnsupportedOpcode(format!(
+                "Recursive ACIR call to function {}",
+                call.id
+            )));
+        }
+        let callee = self
+            .program
+            .functions
+            .get(call.id as usize)
+            .ok_or_else(|| ArkworksError::ParseError(format!("No function {} in ACIR", call.id)))?;
+
+        if let Some(predicate) = &call.predicate {
+            if evaluate(predicate, witness, at)?.is_zero() {
+                // Skipped calls have zero outputs, and placeholder frames
+                // unless the converter dropped them for a constant predicate
+                for &output in &call.outputs {
+                    assign(witness, output, Fr::zero(), at)?;
+                }
+                if !is_constant_zero(predicate)? {
+                    let frames = 1 + count_frames(self.program, call.id, &mut self.call_stack)?;
+                    self.calls.extend((0..frames).map(|_| WitnessMap::new()));
+                }
+                return Ok(());
+            }
+        }
+
+        let mut parameters: Vec<u32> = callee
+            .private_parameters
+            .iter()
+            .chain(&callee.public_parameters.witnesses)
+            .copied()
+            .collect();
+        parameters.sort_unstable();
+        parameters.dedup();
+        let return_values = &callee.return_values.witnesses;
+        if parameters.len() != call.inputs.len() || return_values.len() != call.outputs.len() {
+            return Err(at.error(format!(
+                "call to function {} has {} inputs and {} outputs, expected {} and {}",
+                call.id,
+                call.inputs.len(),
+                call.outputs.len(),
+                parameters.len(),
+                return_values.len()
+            )));
+        }
+
+        let mut callee_witness = WitnessMap::new();
+        for (&param, input) in parameters.iter().zip(&call.inputs) {
+            callee_witness.insert(param, evaluate(input, witness, at)?);
+        }
+
+        let frame = self.calls.len();
+        self.calls.push(WitnessMap::new());
+        self.call_stack.push(call.id);
+        self.solve_function(call.id, &mut callee_witness)?;
+        self.call_stack.pop();
+
+        for (&ret, &output) in return_values.iter().zip(&call.outputs) {
+            let value = callee_witness.get(&ret).copied().ok_or_else(|| {
+                at.error(format!(
+                    "return value witness {} of function {} is unknown",
+                    ret, call.id
+                ))
+            })?;
+            assign(witness, output, value, at)?;
+        }
+        self.calls[frame] = callee_witness;
+
+        Ok(())
+    }
+}
+
+/// Number of frames `acir_to_r1cs` records for the calls inside a function
+fn count_frames(
+    program: &AcirProgram,
+    id: u32,
+    call_stack: &mut Vec<u32>,
+) -> Result<usize, ArkworksError> {
+    let circuit = program
+        .functions
+        .get(id as usize)
+        .ok_or_else(|| ArkworksError::ParseError(format!("No function {} in ACIR", id)))?;
+
+    let mut frames = 0;
+    for opcode in &circuit.opcodes {
+        if let Opcode::Call(call) = opcode {
+            if let Some(predicate) = &call.predicate {
+                if is_constant_zero(predicate)? {
+                    continue;
+                }
+            }
+            if call_stack.contains(&call.id) {
+                return Err(ArkworksError::UnsupportedOpcode(format!(
+                    "Recursive ACIR call to function {}",
+                    call.id
+                )));
+            }
+            call_stack.push(call.id);
+            frames += 1 + count_frames(program, call.id, call_stack)?;
+            call_stack.pop();
+        }
+    }
+    Ok(frames)
+}
+
+/// Whether an expression is the constant zero (witness 0 being the constant 1)
+fn is_constant_zero(expr: &Expression) -> Result<bool, ArkworksError> {
+    if !expr.mul_terms.is_empty() || expr.linear_combinations.iter().any(|&(_, w)| w != 0) {
+        return Ok(false);
+    }
+    let mut constant = parse_field_element(&expr.q_c)?;
+    for (coeff, _) in &expr.linear_combinations {
+        constant += parse_field_element(coeff)?;
+    }
+    Ok(constant.is_zero())
+}
+
+fn known(witness: &WitnessMap, w: u32, at: Position) -> Result<Fr, ArkworksError> {
+    witness
+        .get(&w)
+        .copied()
+        .ok_or_else(|| at.error(format!("witness {} is unknown", w)))
+}
+
+fn evaluate(expr: &Expression, witness: &WitnessMap, at: Position) -> Result<Fr, ArkworksError> {
+    crate::acir_to_r1cs::evaluate_expression(expr, witness).map_err(|e| match e {
+        ArkworksError::MissingWitness(w) => at.error(format!("witness {} is unknown", w)),
+        e => e,
+    })
+}
+
+/// Set a witness, or check it if it is already known
+fn assign(witness: &mut WitnessMap, w: u32, value: Fr, at: Position) -> Result<(), ArkworksError> {
+    match witness.get(&w) {
+        Some(existing) if *existing != value => Err(at.error(format!(
+            "witness {} is {} but must be {}",
+            w, existing, value
+        ))),
+        _ => {
+            witness.insert(w, value);
+            Ok(())
+        }
+    }
+}
+
+/// Values of black box inputs, which must all be known
+fn input_values(
+    inputs: &[FunctionInput],
+    witness: &WitnessMap,
+    at: Position,
+) -> Result<Vec<Fr>, ArkworksError> {
+    inputs.iter().map(|input| input_value(witness, input, at)).collect()
+}
+
+/// Value of a black box input: a constant, or a witness that must be known
+fn input_value(witness: &WitnessMap, input: &FunctionInput, at: Position) -> Result<Fr, ArkworksError> {
+    match input.input {
+        ConstantOrWitness::Constant(value) => Ok(value),
+        ConstantOrWitness::Witness(w) => known(witness, w, at),
+    }
+}
+
+/// Value of an input that must fit in `num_bits` bits (at most 64)
+fn small_value(
+    input: &FunctionInput,
+    num_bits: u32,
+    witness: &WitnessMap,
+    at: Position,
+) -> Result<u64, ArkworksError> {
+    let value = input_value(witness, input, at)?.into_bigint();
+    if value.num_bits() > num_bits {
+        return Err(at.error(format!("{} does not fit in {} bits", input.input, num_bits)));
+    }
+    Ok(value.as_ref()[0])
+}
+
+/// Message bytes of hash inputs: the big-endian bytes of each input's bit size
+fn input_bytes(
+    inputs: &[FunctionInput],
+    witness: &WitnessMap,
+    at: Position,
+) -> Result<Vec<u8>, ArkworksError> {
+    let mut bytes = Vec::new();
+    for input in inputs {
+        let value = input_value(witness, input, at)?.into_bigint();
+        if value.num_bits() > input.num_bits {
+            return Err(at.error(format!(
+                "{} does not fit in {} bits",
+                input.input, input.num_bits
+            )));
+        }
+        let be = value.to_bytes_be();
+        let len = input.num_bits.div_ceil(8) as usize;
+        bytes.extend(std::iter::repeat_n(0, len.saturating_sub(be.len())));
+        bytes.extend_from_slice(&be[be.len().saturating_sub(len)..]);
+    }
+    Ok(bytes)
+}
+
+/// Assign black box outputs from natively computed values
+fn assign_all(
+    witness: &mut WitnessMap,
+    outputs: &[u32],
+    values: &[Fr],
+    at: Position,
+) -> Result<(), ArkworksError> {
+    if outputs.len() != values.len() {
+        return Err(at.error(format!(
+            "black box has {} outputs but computes {} values",
+            outputs.len(),
+            values.len()
+        )));
+    }
+    for (&w, &value) in outputs.iter().zip(values) {
+        assign(witness, w, value, at)?;
+    }
+    Ok(())
+}
+
+/// Solve `expr = target` for its one unknown witness
+///
+/// The unknown may appear in several linear terms and in products with
+/// known witnesses. With no unknowns left the equation is checked instead.
+fn solve_expression(
+    expr: &Expression,
+    target: Fr,
+    witness: &mut WitnessMap,
+    at: Position,
+) -> Result<(), ArkworksError> {
+    let mut unknown: Option<u32> = None;
+    let mut note_unknown = |w: u32| match unknown {
+        Some(u) if u != w => Err(at.error(format!("witnesses {} and {} are both unknown", u, w))),
+        _ => {
+            unknown = Some(w);
+            Ok(())
+        }
+    };
+
+    // expr - target = coeff * unknown + rest
+    let mut coeff = Fr::zero();
+    let mut rest = parse_field_element(&expr.q_c)? - target;
+
+    for (c, w) in &expr.linear_combinations {
+        let c = parse_field_element(c)?;
+        match witness.get(w) {
+            Some(v) => rest += c * v,
+            None => {
+                note_unknown(*w)?;
+                coeff += c;
+            }
+        }
+    }
+    for (c, a, b) in &expr.mul_terms {
+        let c = parse_field_element(c)?;
+        match (witness.get(a), witness.get(b)) {
+            (Some(x), Some(y)) => rest += c * x * y,
+            (Some(x), None) => {
+                note_unknown(*b)?;
+                coeff += c * x;
+            }
+            (None, Some(y)) => {
+                note_unknown(*a)?;
+                coeff += c * y;
+            }
+            (None, None) if a == b => {
+                return Err(at.error(format!("witness {} is unknown and squared", a)));
+            }
+            (None, None) => {
+                return Err(at.error(format!("witnesses {} and {} are both unknown", a, b)));
+            }
+        }
+    }
+
+    match unknown {
+        None if rest.is_zero() => Ok(()),
+        None => Err(at.error(format!("expression is not satisfied (off by {})", rest))),
+        Some(w) if coeff.is_zero() => Err(at.error(format!(
+            "witness {} cancels out of the expression",
+            w
+        ))),
+        Some(w) => {
+            let inverse = ark_ff::Field::inverse(&coeff).unwrap_or_default();
+            witness.insert(w, -rest * inverse);
+            Ok(())
+        }
+    }
+}
+
+fn solve_memory_op(
+    op: &MemoryOp,
+    memory_blocks: &mut HashMap<u32, Vec<Fr>>,
+    witness: &mut WitnessMap,
+    at: Position,
+) -> Result<(), ArkworksError> {
+    let index = evaluate(&op.index, witness, at)?;
+    let block = memory_blocks
+        .get_mut(&op.block_id)
+        .ok_or_else(|| at.error(format!("memory block {} is not initialized", op.block_id)))?;
+
+    let position = index.into_bigint();
+    if position.num_bits() > 64 || position.as_ref()[0] as usize >= block.len() {
+        return Err(at.error(format!(
+            "memory index {} out of bounds for block {} of size {}",
+            index,
+            op.block_id,
+            block.len()
+        )));
+    }
+    let position = position.as_ref()[0] as usize;
+
+    if op.op.inner == 1 {
+        block[position] = evaluate(&op.value, witness, at)?;
+        Ok(())
+    } else {
+        let cell = block[position];
+        solve_expression(&op.value, cell, witness, at)
+    }
+}
+
+fn solve_black_box(
+    bb: &BlackBoxFuncCall,
+    bigints: &mut BigIntTable,
+    witness: &mut WitnessMap,
+    at: Position,
+) -> Result<(), ArkworksError> {
+    match bb {
+        BlackBoxFuncCall::Range { input } => {
+            let value = input_value(witness, input, at)?;
+            if value.into_bigint().num_bits() > input.num_bits {
+                return Err(at.error(format!(
+                    "{} = {} does not fit in {} bits",
+                    input.input, value, input.num_bits
+                )));
+            }
+            Ok(())
+        }
+        BlackBoxFuncCall::And { lhs, rhs, output } | BlackBoxFuncCall::Xor { lhs, rhs, output } => {
+            let l: BigUint = input_value(witness, lhs, at)?.into();
+            let r: BigUint = input_value(witness, rhs, at)?.into();
+            let result = if matches!(bb, BlackBoxFuncCall::Xor { .. }) {
+                l ^ r
+            } else {
+                l & r
+            };
+            assign(witness, *output, Fr::from(result), at)
+        }
+        BlackBoxFuncCall::Poseidon2Permutation { inputs, outputs, .. } => {
+            let inputs = input_values(inputs, witness, at)?;
+            let results = poseidon2::permutation(&inputs).map_err(|e| at.error(e.to_string()))?;
+            assign_all(witness, outputs, &results, at)
+        }
+        BlackBoxFuncCall::Sha256 { inputs, outputs } => {
+            let message = input_bytes(inputs, witness, at)?;
+            let digest = sha256::digest(&message);
+            assign_all(witness, outputs, &digest.map(Fr::from), at)
+        }
+        BlackBoxFuncCall::Sha256Compression { inputs, hash_values, outputs } => {
+            let words = |inputs: &[FunctionInput]| -> Result<Vec<u32>, ArkworksError> {
+                inputs
+                    .iter()
+                    .map(|input| Ok(small_value(input, 32, witness, at)? as u32))
+                    .collect()
+            };
+            let (block, state) = (words(inputs)?, words(hash_values)?);
+            let block: [u32; 16] = block
+                .try_into()
+                .map_err(|_| at.error("SHA-256 compression takes 16 input words"))?;
+            let state: [u32; 8] = state
+                .try_into()
+                .map_err(|_| at.error("SHA-256 compression takes 8 hash values"))?;
+            let result = sha256::compress(&state, &block).map(Fr::from);
+            assign_all(witness, outputs, &result, at)
+        }
+        BlackBoxFuncCall::Blake2s { inputs, outputs } => {
+            let digest = blake2s::blake2s(&input_bytes(inputs, witness, at)?);
+            assign_all(witness, outputs, &digest.map(Fr::from), at)
+        }
+        BlackBoxFuncCall::Blake3 { inputs, outputs } => {
+            let digest = blake3::blake3(&input_bytes(inputs, witness, at)?);
+            assign_all(witness, outputs, &digest.map(Fr::from), at)
+        }
+        BlackBoxFuncCall::Keccak256 { inputs, var_message_size, outputs } => {
+            let mut message = input_bytes(inputs, witness, at)?;
+            if let Some(size) = var_message_size {
+                let size = small_value(size, size.num_bits.min(64), witness, at)?;
+                if size > message.len() as u64 {
+                    return Err(at.error(format!(
+                        "message size {} exceeds the {} input bytes",
+                        size,
+                        message.len()
+                    )));
+                }
+                message.truncate(size as usize);
+            }
+            let digest = keccak::keccak256(&message);
+            assign_all(witness, outputs, &digest.map(Fr::from), at)
+        }
+        BlackBoxFuncCall::Keccakf1600 { inputs, outputs } => {
+            let lanes = inputs
+                .iter()
+                .map(|input| small_value(input, 64, witness, at))
+                .collect::<Result<Vec<_>, _>>()?;
+            let state: [u64; 25] =
+                lanes.try_into().map_err(|_| at.error("Keccak-f[1600] takes 25 lanes"))?;
+            assign_all(witness, outputs, &keccak::permutation(state).map(Fr::from), at)
+        }
+        BlackBoxFuncCall::PedersenCommitment { inputs, domain_separator, outputs } => {
+            let (x, y) = pedersen::commitment(&input_values(inputs, witness, at)?, *domain_separator);
+            assign_all(witness, &[outputs.0, outputs.1], &[x, y], at)
+        }
+        BlackBoxFuncCall::PedersenHash { inputs, domain_separator, output } => {
+            let hash = pedersen::hash(&input_values(inputs, witness, at)?, *domain_separator);
+            assign(witness, *output, hash, at)
+        }
+        BlackBoxFuncCall::EmbeddedCurveAdd { input1, input2, outputs } => {
+            let point = |(x, y, infinite): &(FunctionInput, FunctionInput, FunctionInput)| {
+                Ok::<_, ArkworksError>([
+                    input_value(witness, x, at)?,
+                    input_value(witness, y, at)?,
+                    input_value(witness, infinite, at)?,
+                ])
+            };
+            let sum = embedded_curve::embedded_curve_add(point(input1)?, point(input2)?)
+                .map_err(|e| at.error(e.to_string()))?;
+            assign_all(witness, &[outputs.0, outputs.1, outputs.2], &sum, at)
+        }
+        BlackBoxFuncCall::MultiScalarMul { points, scalars, outputs } => {
+            let points = input_values(points, witness, at)?;
+            let scalars = input_values(scalars, witness, at)?;
+            let result = embedded_curve::multi_scalar_mul(&points, &scalars)
+                .map_err(|e| at.error(e.to_string()))?;
+            assign_all(witness, &[outputs.0, outputs.1, outputs.2], &result, at)
+        }
+        BlackBoxFuncCall::FixedBaseScalarMul { low, high, outputs } => {
+            let (x, y) = embedded_curve::fixed_base_scalar_mul(
+                input_value(witness, low, at)?,
+                input_value(witness, high, at)?,
+            )
+            .map_err(|e| at.error(e.to_string()))?;
+            assign_all(witness, &[outputs.0, outputs.1], &[x, y], at)
+        }
+        BlackBoxFuncCall::SchnorrVerify {
+            public_key_x,
+            public_key_y,
+            signature,
+            message,
+            output,
+        } => {
+            let signature: [u8; 64] = input_bytes(signature, witness, at)?
+                .try_into()
+                .map_err(|_| at.error("Schnorr signatures have 64 bytes"))?;
+            let valid = schnorr::verify_signature(
+                input_value(witness, public_key_x, at)?,
+                input_value(witness, public_key_y, at)?,
+                &signature,
+                &input_bytes(message, witness, at)?,
+            );
+            assign(witness, *output, Fr::from(valid), at)
+        }
+        BlackBoxFuncCall::EcdsaSecp256k1 {
+            public_key_x,
+            public_key_y,
+            signature,
+            hashed_message,
+            output,
+        }
+        | BlackBoxFuncCall::EcdsaSecp256r1 {
+            public_key_x,
+            public_key_y,
+            signature,
+            hashed_message,
+            output,
+        } => {
+            let curve = if matches!(bb, BlackBoxFuncCall::EcdsaSecp256k1 { .. }) {
+                ecdsa::Curve::secp256k1()
+            } else {
+                ecdsa::Curve::secp256r1()
+            };
+            let coordinate = |inputs: &[FunctionInput]| -> Result<[u8; 32], ArkworksError> {
+                input_bytes(inputs, witness, at)?
+                    .try_into()
+                    .map_err(|_| at.error("ECDSA key coordinates have 32 bytes"))
+            };
+            let (x, y) = (coordinate(public_key_x)?, coordinate(public_key_y)?);
+            let signature: [u8; 64] = input_bytes(signature, witness, at)?
+                .try_into()
+                .map_err(|_| at.error("ECDSA signatures have 64 bytes"))?;
+            let hashed_message = input_bytes(hashed_message, witness, at)?;
+            let valid = ecdsa::verify_signature(&curve, &x, &y, &signature, &hashed_message);
+            assign(witness, *output, Fr::from(valid), at)
+        }
+        BlackBoxFuncCall::BigIntAdd { lhs, rhs, output } => bigints
+            .operate(bigint::Operation::Add, *lhs, *rhs, *output)
+            .map_err(|e| at.error(e.to_string())),
+        BlackBoxFuncCall::BigIntSub { lhs, rhs, output } => bigints
+            .operate(bigint::Operation::Sub, *lhs, *rhs, *output)
+            .map_err(|e| at.error(e.to_string())),
+        BlackBoxFuncCall::BigIntMul { lhs, rhs, output } => bigints
+            .operate(bigint::Operation::Mul, *lhs, *rhs, *output)
+            .map_err(|e| at.error(e.to_string())),
+        BlackBoxFuncCall::BigIntDiv { lhs, rhs, output } => bigints
+            .operate(bigint::Operation::Div, *lhs, *rhs, *output)
+            .map_err(|e| at.error(e.to_string())),
+        BlackBoxFuncCall::BigIntFromLeBytes { inputs, modulus, output } => {
+            let bytes = inputs
+                .iter()
+                .map(|input| Ok(small_value(input, input.num_bits.min(8), witness, at)? as u8))
+                .collect::<Result<Vec<u8>, ArkworksError>>()?;
+            bigints
+                .from_le_bytes(&bytes, modulus, *output)
+                .map_err(|e| at.error(e.to_string()))
+        }
+        BlackBoxFuncCall::BigIntToLeBytes { input, outputs } => {
+            let bytes = bigints.to_le_bytes(*input).map_err(|e| at.error(e.to_string()))?;
+            let values: Vec<Fr> = bytes.into_iter().map(Fr::from).collect();
+            assign_all(witness, outputs, &values, at)
+        }
+        _ => Err(at.error("black box function has no native solver")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acir_to_r1cs::acir_to_r1cs;
+    use serde_json::json;
+
+    fn minus_one() -> String {
+        format!("0x{}", hex::encode(crate::gnark_compat::fr_to_be_bytes(&-Fr::from(1u64))))
+    }
+
+    fn program(functions: serde_json::Value, unconstrained: serde_json::Value) -> AcirProgram {
+        serde_json::from_value(json!({
+            "functions": functions,
+            "unconstrained_functions": unconstrained
+        }))
+        .unwrap()
+    }
+
+    fn function(
+        current_witness_index: u32,
+        parameters: Vec<u32>,
+        returns: Vec<u32>,
+        opcodes: serde_json::Value,
+    ) -> serde_json::Value {
+        json!({
+            "current_witness_index": current_witness_index,
+            "opcodes": opcodes,
+            "private_parameters": parameters,
+            "public_parameters": { "witnesses": [] },
+            "return_values": { "witnesses": returns }
+        })
+    }
+
+    fn inputs(values: &[(u32, u64)]) -> WitnessMap {
+        values.iter().map(|&(w, v)| (w, Fr::from(v))).collect()
+    }
+
+    /// Solve, then check the solution against the converted R1CS
+    fn solve_and_check(program: &AcirProgram, initial: &WitnessMap) -> WitnessMap {
+        let r1cs = acir_to_r1cs(program).unwrap();
+        let solved = solve_witness(program, initial).unwrap();
+        let acir_witness = solved.witness.clone();
+        let mut witness = solved.into_r1cs_witness(&r1cs).unwrap();
+        r1cs.compute_aux_witnesses(&mut witness).unwrap();
+        assert!(r1cs.constraints.iter().all(|c| c.is_satisfied(&witness).unwrap()));
+        acir_witness
+    }
+
+    #[test]
+    fn test_solve_assert_zero_chain() {
+        // w2 = w1 * w1 + 3; 2 * w3 = w2 * w1 (w3 appears twice); w4 = w1 AND w3
+        let program = program(
+            json!([function(4, vec![1], vec![], json!([
+                {
+                    "type": "AssertZero",
+                    "value": { "mul_terms": [["0x1", 1, 1]], "linear_combinations": [[minus_one(), 2]], "q_c": "0x3" }
+                },
+                {
+                    "type": "AssertZero",
+                    "value": { "mul_terms": [["0x1", 2, 1]], "linear_combinations": [[minus_one(), 3], [minus_one(), 3]], "q_c": "0x0" }
+                },
+                { "type": "BlackBoxFuncCall", "name": "AND", "lhs": { "witness": 1, "num_bits": 8 }, "rhs": { "witness": 3, "num_bits": 8 }, "output": 4 }
+            ]))]),
+            json!([]),
+        );
+
+        let witness = solve_and_check(&program, &inputs(&[(1, 6)]));
+        assert_eq!(witness[&2], Fr::from(39u64));
+        assert_eq!(witness[&3], Fr::from(117u64));
+        assert_eq!(witness[&4], Fr::from(6u64 & 117));
+
+        // Inconsistent known values are reported at the opcode that checks them
+        let err = solve_witness(&program, &inputs(&[(1, 6), (2, 40)])).unwrap_err();
+        assert!(matches!(err, ArkworksError::UnsolvableOpcode { function: 0, opcode: 0, .. }));
+    }
+
+    #[test]
+    fn test_solve_reports_stuck_opcode() {
+        // w3 = w1 + w2 with only w1 known
+        let program = program(
+            json!([function(3, vec![1], vec![], json!([
+                {
+                    "type": "AssertZero",
+                    "value": { "linear_combinations": [["0x1", 1], ["0x1", 2], [minus_one(), 3]], "q_c": "0x0" }
+                }
+            ]))]),
+            json!([]),
+        );
+
+        match solve_witness(&program, &inputs(&[(1, 1)])) {
+            Err(ArkworksError::UnsolvableOpcode { function, opcode, reason }) => {
+                assert_eq!((function, opcode), (0, 0));
+                assert_eq!(reason, "witnesses 2 and 3 are both unknown");
+            }
+            other => panic!("expected a stuck opcode, got {:?}", other.map(|s| s.witness)),
+        }
+    }
+
+    #[test]
+    fn test_solve_brillig_and_memory() {
+        // w2 = 1 / w1 (Brillig), w1 * w2 = 1; block [w1, w2, w3]; w5 = block[w4]
+        let u32_size = json!({ "Integer": "U32" });
+        let inverse = json!({ "bytecode": [
+            { "type": "Const", "destination": { "Direct": 10 }, "bit_size": u32_size, "value": "0x1" },
+            { "type": "Const", "destination": { "Direct": 11 }, "bit_size": u32_size, "value": "0x0" },
+            { "type": "CalldataCopy", "destination_address": { "Direct": 20 }, "size_address": { "Direct": 10 }, "offset_address": { "Direct": 11 } },
+            { "type": "Const", "destination": { "Direct": 1 }, "bit_size": "Field", "value": "0x1" },
+            { "type": "BinaryFieldOp", "destination": { "Direct": 21 }, "op": "Div", "lhs": { "Direct": 1 }, "rhs": { "Direct": 20 } },
+            { "type": "Const", "destination": { "Direct": 2 }, "bit_size": u32_size, "value": "0x15" },
+            { "type": "Stop", "return_data": { "pointer": { "Direct": 2 }, "size": { "Direct": 10 } } }
+        ]});
+        let program = program(
+            json!([function(5, vec![1, 3, 4], vec![], json!([
+                {
+                    "type": "BrilligCall", "id": 0,
+                    "inputs": [{ "Single": { "linear_combinations": [["0x1", 1]], "q_c": "0x0" } }],
+                    "outputs": [{ "Simple": 2 }]
+                },
+                {
+                    "type": "AssertZero",
+                    "value": { "mul_terms": [["0x1", 1, 2]], "q_c": minus_one() }
+                },
+                { "type": "MemoryInit", "block_id": 0, "init": [1, 2, 3] },
+                {
+                    "type": "MemoryOp", "block_id": 0, "op": { "inner": 0 },
+                    "index": { "linear_combinations": [["0x1", 4]], "q_c": "0x0" },
+                    "value": { "linear_combinations": [["0x1", 5]], "q_c": "0x0" }
+                }
+            ]))]),
+            json!([inverse]),
+        );
+
+        let witness = solve_and_check(&program, &inputs(&[(1, 7), (3, 9), (4, 1)]));
+        assert_eq!(witness[&2] * Fr::from(7u64), Fr::from(1u64));
+        assert_eq!(witness[&5], witness[&2]);
+
+        let err = solve_witness(&program, &inputs(&[(1, 7), (3, 9), (4, 3)])).unwrap_err();
+        assert!(matches!(err, ArkworksError::UnsolvableOpcode { opcode: 3, .. }));
+    }
+
+    #[test]
+    fn test_solve_calls() {
+        // main: w3 = double(w1); w4 = if w2 { double(w3) } else { 0 }
+        // double: RANGE(w1, 8); w2 = 2 * w1
+        let double = function(2, vec![1], vec![2], json!([
+            { "type": "BlackBoxFuncCall", "name": "RANGE", "input": { "witness": 1, "num_bits": 8 } },
+            {
+                "type": "AssertZero",
+                "value": { "linear_combinations": [["0x2", 1], [minus_one(), 2]], "q_c": "0x0" }
+            }
+        ]));
+        let main = function(4, vec![1, 2], vec![], json!([
+            {
+                "type": "Call", "id": 1,
+                "inputs": [{ "linear_combinations": [["0x1", 1]], "q_c": "0x0" }],
+                "outputs": [3]
+            },
+            {
+                "type": "Call", "id": 1,
+                "inputs": [{ "linear_combinations": [["0x1", 3]], "q_c": "0x0" }],
+                "outputs": [4],
+                "predicate": { "linear_combinations": [["0x1", 2]], "q_c": "0x0" }
+            }
+        ]));
+        let program = program(json!([main, double]), json!([]));
+
+        let witness = solve_and_check(&program, &inputs(&[(1, 5), (2, 1)]));
+        assert_eq!(witness[&4], Fr::from(20u64));
+
+        let witness = solve_and_check(&program, &inputs(&[(1, 5), (2, 0)]));
+        assert_eq!(witness[&4], Fr::zero());
+
+        // Failures inside a callee point at the callee's opcode
+        let err = solve_witness(&program, &inputs(&[(1, 300), (2, 0)])).unwrap_err();
+        assert!(matches!(err, ArkworksError::UnsolvableOpcode { function: 1, opcode: 0, .. }));
+    }
+}
@@ -0,0 +1,43 @@
+//! Fixtures shared by the unit tests.
+
+use serde_json::{json, Value};
+
+use crate::acir_to_r1cs::{acir_to_r1cs, AcirR1cs};
+use crate::acir_types::AcirProgram;
+
+/// Program of a single function running `opcodes`, a JSON list, with
+/// `private_parameters` as its inputs and no public inputs or return values
+pub(crate) fn program(
+    opcodes: Value,
+    current_witness_index: u32,
+    private_parameters: impl IntoIterator<Item = u32>,
+) -> AcirProgram {
+    serde_json::from_value(json!({
+        "functions": [{
+            "current_witness_index": current_witness_index,
+            "opcodes": opcodes,
+            "private_parameters": private_parameters.into_iter().collect::<Vec<u32>>(),
+            "public_parameters": { "witnesses": [] },
+            "return_values": { "witnesses": [] }
+        }]
+    }))
+    .unwrap()
+}
+
+/// Program of a single function running one opcode
+pub(crate) fn one_opcode_program(
+    opcode: Value,
+    current_witness_index: u32,
+    private_parameters: impl IntoIterator<Item = u32>,
+) -> AcirProgram {
+    program(json!([opcode]), current_witness_index, private_parameters)
+}
+
+/// Convert a one-opcode program, which must succeed
+pub(crate) fn convert_opcode(
+    opcode: Value,
+    current_witness_index: u32,
+    private_parameters: impl IntoIterator<Item = u32>,
+) -> AcirR1cs {
+    acir_to_r1cs(&one_opcode_program(opcode, current_witness_index, private_parameters)).unwrap()
+}